@@ -0,0 +1,69 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use chip8emu::opcode;
+use chip8emu::{Chip8Emulator, Palette};
+
+// A loop of typical game instructions: arithmetic, a skip, a call, I arithmetic, a key check,
+// a timer read and a sprite draw, so that emulate_cycle is measured on a realistic mix.
+const MIXED_ROM: [u8; 28] = [
+    0x60, 0x05, // 200: V0 := 5
+    0x71, 0x01, // 202: V1 += 1
+    0x81, 0x04, // 204: V1 += V0
+    0x31, 0x00, // 206: if V1 == 0 skip
+    0x22, 0x18, // 208: call 218
+    0xF0, 0x1E, // 20A: I += V0
+    0xE0, 0xA1, // 20C: skip if key V0 isn't held
+    0xF2, 0x07, // 20E: V2 := delay
+    0xD0, 0x15, // 210: draw 5 rows at V0, V1
+    0x12, 0x02, // 212: jump 202
+    0x00, 0x00, // 214
+    0x00, 0x00, // 216
+    0xA2, 0x00, // 218: I := 200
+    0x00, 0xEE, // 21A: return
+];
+
+fn machine(rom: &[u8]) -> Chip8Emulator {
+    let mut emu = Chip8Emulator::new();
+    emu.set_rng_seed(0);
+    emu.initialize();
+    emu.load_rom_bytes(rom).unwrap();
+    emu
+}
+
+fn emulate_cycle(c: &mut Criterion) {
+    let mut emu = machine(&MIXED_ROM);
+    c.bench_function("emulate_cycle", |b| b.iter(|| emu.emulate_cycle().unwrap()));
+}
+
+fn decode(c: &mut Criterion) {
+    c.bench_function("decode all opcodes", |b| {
+        b.iter(|| {
+            for value in 0..=u16::MAX {
+                let _ = black_box(opcode::from_value(black_box(value)));
+            }
+        })
+    });
+}
+
+fn draw(c: &mut Criterion) {
+    // I := 0 (the font), then draw 15 rows at V0, V1 forever, moving one pixel right each time.
+    let rom = [0xA0, 0x00, 0xD0, 0x1F, 0x70, 0x01, 0x12, 0x02];
+    let mut emu = machine(&rom);
+    emu.emulate_cycle().unwrap();
+    c.bench_function("draw sprite", |b| {
+        b.iter(|| {
+            for _ in 0..3 {
+                emu.emulate_cycle().unwrap();
+            }
+        })
+    });
+
+    let mut frame = vec![0; 4 * emu.screen().len()];
+    let palette = Palette::default();
+    c.bench_function("draw screen", |b| {
+        b.iter(|| emu.draw_screen_with_palette(black_box(&mut frame), &palette))
+    });
+}
+
+criterion_group!(benches, emulate_cycle, decode, draw);
+criterion_main!(benches);
@@ -0,0 +1,51 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use chip8emu::chip8::Variant;
+use chip8emu::clock::Clock;
+use chip8emu::quirks::QUIRK_NAMES;
+use chip8emu::{run_headless, Chip8Emulator};
+
+// Enough for most loops a ROM can get into to come round a few times, while keeping each run
+// short.
+const CYCLES: u64 = 10_000;
+
+// The first byte picks the dialect and the quirks, and the rest is the ROM. Running it can fail,
+// but must never panic.
+//
+// A second byte N other than 0 also copies the ROM to the last 2N bytes of memory, carrying on
+// from its start, and runs it from there, so that skips and XO-CHIP's `i := long` step off the
+// end of memory.
+fuzz_target!(|data: &[u8]| {
+    let [settings, from_end, rom @ ..] = data else {
+        return;
+    };
+    let (settings, from_end) = (*settings, usize::from(*from_end));
+    let variant = match settings & 0b11 {
+        0 => Variant::Chip8,
+        1 => Variant::SuperChip,
+        _ => Variant::XoChip,
+    };
+    let mut emu = Chip8Emulator::new();
+    emu.set_variant(variant);
+    let mut quirks = emu.quirks();
+    for (bit, name) in QUIRK_NAMES.iter().enumerate() {
+        quirks.set(name, settings & (4 << bit) != 0).unwrap();
+    }
+    emu.set_quirks(quirks);
+    emu.set_rng_seed(0);
+    emu.initialize();
+    if emu.load_rom_bytes(rom).is_err() {
+        return;
+    }
+    if from_end > 0 {
+        let len = emu.memory().len();
+        let start = len - 2 * from_end;
+        for (offset, byte) in rom.iter().enumerate() {
+            emu.write_memory((start + offset) % len, &[*byte]).unwrap();
+        }
+        emu.set_pc(start);
+    }
+    let _ = run_headless(&mut emu, CYCLES, Clock::from_ipf(30));
+});
@@ -0,0 +1,271 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use crate::opcode::Opcode;
+
+/// Operand names that can't be used as labels.
+const KEYWORDS: [&str; 10] = ["I", "[I]", "DT", "ST", "K", "F", "HF", "B", "R", "LONG"];
+
+/// Why a line of source couldn't be assembled.
+#[derive(Debug, PartialEq, Eq)]
+pub struct AsmError {
+    /// 1-based line number.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for AsmError {}
+
+#[derive(Clone, Copy)]
+enum Operand<'a> {
+    Register(u8),
+    Keyword(&'static str),
+    /// A number or a label.
+    Value(&'a str),
+}
+
+fn operand(text: &str) -> Operand<'_> {
+    let upper = text.to_ascii_uppercase();
+    if let Some(keyword) = KEYWORDS.iter().find(|keyword| **keyword == upper) {
+        return Operand::Keyword(keyword);
+    }
+    match upper.strip_prefix('V') {
+        Some(digit) if digit.len() == 1 => match u8::from_str_radix(digit, 16) {
+            Ok(x) => Operand::Register(x),
+            Err(_) => Operand::Value(text),
+        },
+        _ => Operand::Value(text),
+    }
+}
+
+/// A line with its comment and labels removed.
+struct Statement<'a> {
+    line: usize,
+    mnemonic: String,
+    operands: Vec<Operand<'a>>,
+}
+
+impl Statement<'_> {
+    fn size(&self) -> usize {
+        match (self.mnemonic.as_str(), self.operands.as_slice()) {
+            ("DB", operands) => operands.len(),
+            ("LD", [Operand::Keyword("I"), Operand::Keyword("LONG"), _]) => 4,
+            _ => 2,
+        }
+    }
+}
+
+fn is_label(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !matches!(operand(name), Operand::Register(_) | Operand::Keyword(_))
+}
+
+/// Split a line into its labels and statement.
+fn parse_line(line: usize, text: &str) -> Result<(Vec<&str>, Option<Statement<'_>>), AsmError> {
+    let error = |message: String| AsmError { line, message };
+    let mut rest = text.split(';').next().unwrap().trim();
+    let mut labels = Vec::new();
+    while let Some((label, after)) = rest.split_once(':') {
+        let label = label.trim();
+        if !is_label(label) {
+            return Err(error(format!("invalid label {:?}", label)));
+        }
+        labels.push(label);
+        rest = after.trim();
+    }
+    if rest.is_empty() {
+        return Ok((labels, None));
+    }
+
+    let (mnemonic, operands) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    let mnemonic = mnemonic.to_ascii_uppercase();
+    let mut operands: Vec<&str> = operands
+        .split(',')
+        .map(str::trim)
+        .filter(|operand| !operand.is_empty())
+        .collect();
+    // `SAVE VX - VY` and `LOAD VX - VY` separate their registers with a dash, and
+    // `LD I, LONG NNNN` its address with a space.
+    if let [i, long] = operands[..] {
+        match long.split_once(char::is_whitespace) {
+            Some((keyword, address)) if i.eq_ignore_ascii_case("I") => {
+                operands = vec![i, keyword, address.trim()];
+            }
+            _ => {}
+        }
+    }
+    if (mnemonic == "SAVE" || mnemonic == "LOAD") && operands.len() == 1 {
+        if let Some((x, y)) = operands[0].split_once('-') {
+            operands = vec![x.trim(), y.trim()];
+        }
+    }
+    let statement = Statement {
+        line,
+        mnemonic,
+        operands: operands.into_iter().map(operand).collect(),
+    };
+    Ok((labels, Some(statement)))
+}
+
+struct Assembler<'a> {
+    labels: HashMap<&'a str, usize>,
+}
+
+impl Assembler<'_> {
+    /// A number like `0x2A0`, `0b1010` or `42`, or the address of a label, that fits in `bits`.
+    fn value(&self, line: usize, text: &str, bits: u32) -> Result<u16, AsmError> {
+        let error = |message: String| AsmError { line, message };
+        let lower = text.to_ascii_lowercase();
+        let parsed = if let Some(hex) = lower.strip_prefix("0x") {
+            usize::from_str_radix(hex, 16).ok()
+        } else if let Some(binary) = lower.strip_prefix("0b") {
+            usize::from_str_radix(binary, 2).ok()
+        } else if text.starts_with(|c: char| c.is_ascii_digit()) {
+            text.parse().ok()
+        } else if let Some(address) = self.labels.get(text) {
+            Some(*address)
+        } else {
+            return Err(error(format!("unknown label {:?}", text)));
+        };
+        match parsed {
+            Some(value) if value < 1 << bits => Ok(value as u16),
+            Some(_) => Err(error(format!("{} doesn't fit in {} bits", text, bits))),
+            None => Err(error(format!("invalid number {:?}", text))),
+        }
+    }
+
+    fn encode(&self, statement: &Statement, out: &mut Vec<u8>) -> Result<(), AsmError> {
+        use Opcode::*;
+        use Operand::{Keyword, Register, Value};
+        let line = statement.line;
+        let address = |text| self.value(line, text, 12);
+        let byte = |text| self.value(line, text, 8).map(|value| value as u8);
+        let nibble = |text| self.value(line, text, 4).map(|value| value as u8);
+
+        let opcode = match (statement.mnemonic.as_str(), statement.operands.as_slice()) {
+            ("DB", operands) => {
+                for operand in operands {
+                    match operand {
+                        Value(text) => out.push(byte(text)?),
+                        _ => {
+                            return Err(AsmError {
+                                line,
+                                message: "db takes numbers".to_string(),
+                            })
+                        }
+                    }
+                }
+                return Ok(());
+            }
+            ("LD", [Keyword("I"), Keyword("LONG"), Value(a)]) => {
+                out.extend_from_slice(&SetILong.to_value().to_be_bytes());
+                out.extend_from_slice(&self.value(line, a, 16)?.to_be_bytes());
+                return Ok(());
+            }
+            ("SYS", [Value(a)]) => MachineCode(address(a)?),
+            ("CLS", []) => ClearScreen,
+            ("RET", []) => ReturnFromSub,
+            ("JP", [Value(a)]) => Goto(address(a)?),
+            ("JP", [Register(0), Value(a)]) => Jump(address(a)?),
+            ("CALL", [Value(a)]) => CallSub(address(a)?),
+            ("SE", [Register(x), Value(c)]) => SkipEQ(*x, byte(c)?),
+            ("SE", [Register(x), Register(y)]) => SkipRegEQ(*x, *y),
+            ("SNE", [Register(x), Value(c)]) => SkipNEQ(*x, byte(c)?),
+            ("SNE", [Register(x), Register(y)]) => SkipRegNEQ(*x, *y),
+            ("LD", [Register(x), Value(c)]) => SetConst(*x, byte(c)?),
+            ("LD", [Register(x), Register(y)]) => SetReg(*x, *y),
+            ("LD", [Keyword("I"), Value(a)]) => SetAR(address(a)?),
+            ("LD", [Keyword("I"), Keyword("LONG")]) => SetILong,
+            ("LD", [Register(x), Keyword("DT")]) => GetDelayTimer(*x),
+            ("LD", [Register(x), Keyword("K")]) => GetKey(*x),
+            ("LD", [Keyword("DT"), Register(x)]) => SetDelayTimer(*x),
+            ("LD", [Keyword("ST"), Register(x)]) => SetSoundTimer(*x),
+            ("LD", [Keyword("F"), Register(x)]) => SetISprite(*x),
+            ("LD", [Keyword("HF"), Register(x)]) => SetIBigSprite(*x),
+            ("LD", [Keyword("B"), Register(x)]) => BCD(*x),
+            ("LD", [Keyword("[I]"), Register(x)]) => RegDump(*x),
+            ("LD", [Register(x), Keyword("[I]")]) => RegLoad(*x),
+            ("LD", [Keyword("R"), Register(x)]) => SaveFlags(*x),
+            ("LD", [Register(x), Keyword("R")]) => LoadFlags(*x),
+            ("ADD", [Register(x), Value(c)]) => AddConst(*x, byte(c)?),
+            ("ADD", [Register(x), Register(y)]) => AddReg(*x, *y),
+            ("ADD", [Keyword("I"), Register(x)]) => AddToI(*x),
+            ("OR", [Register(x), Register(y)]) => Or(*x, *y),
+            ("AND", [Register(x), Register(y)]) => And(*x, *y),
+            ("XOR", [Register(x), Register(y)]) => Xor(*x, *y),
+            ("SUB", [Register(x), Register(y)]) => SubReg(*x, *y),
+            ("SHR", [Register(x), Register(y)]) => Div2(*x, *y),
+            ("SUBN", [Register(x), Register(y)]) => DiffReg(*x, *y),
+            ("SHL", [Register(x), Register(y)]) => Mul2(*x, *y),
+            ("RND", [Register(x), Value(c)]) => Rand(*x, byte(c)?),
+            ("DRW", [Register(x), Register(y), Value(n)]) => Draw(*x, *y, nibble(n)?),
+            ("SKP", [Register(x)]) => KeyEQ(*x),
+            ("SKNP", [Register(x)]) => KeyNEQ(*x),
+            ("SCD", [Value(n)]) => ScrollDown(nibble(n)?),
+            ("SCU", [Value(n)]) => ScrollUp(nibble(n)?),
+            ("SCR", []) => ScrollRight,
+            ("SCL", []) => ScrollLeft,
+            ("EXIT", []) => Exit,
+            ("LOW", []) => LowRes,
+            ("HIGH", []) => HighRes,
+            ("SAVE", [Register(x), Register(y)]) => SaveRange(*x, *y),
+            ("LOAD", [Register(x), Register(y)]) => LoadRange(*x, *y),
+            ("PLANE", [Value(n)]) => SelectPlanes(nibble(n)?),
+            ("AUDIO", []) => LoadAudio,
+            ("PITCH", [Register(x)]) => SetPitch(*x),
+            (mnemonic, _) => {
+                return Err(AsmError {
+                    line,
+                    message: format!("invalid operands for {}", mnemonic),
+                })
+            }
+        };
+        out.extend_from_slice(&opcode.to_value().to_be_bytes());
+        Ok(())
+    }
+}
+
+/// Assemble source into a ROM to be loaded at `origin`.
+///
+/// The syntax is the one `disasm::disassemble` writes: Cowgod's mnemonics, one instruction per
+/// line, `label:` definitions, `db` for bytes, and `;` comments. Numbers can be decimal, or hex
+/// or binary with a `0x` or `0b` prefix, and labels can be used anywhere an address can.
+/// `LD I, LONG NNNN` assembles XO-CHIP's `F000 NNNN` in one line.
+pub fn assemble(source: &str, origin: u16) -> Result<Vec<u8>, AsmError> {
+    let mut statements = Vec::new();
+    let mut labels = HashMap::new();
+    let mut address = usize::from(origin);
+    for (index, text) in source.lines().enumerate() {
+        let (names, statement) = parse_line(index + 1, text)?;
+        for name in names {
+            if labels.insert(name, address).is_some() {
+                return Err(AsmError {
+                    line: index + 1,
+                    message: format!("label {:?} is already defined", name),
+                });
+            }
+        }
+        if let Some(statement) = statement {
+            address += statement.size();
+            statements.push(statement);
+        }
+    }
+
+    let assembler = Assembler { labels };
+    let mut rom = Vec::with_capacity(address - usize::from(origin));
+    for statement in &statements {
+        assembler.encode(statement, &mut rom)?;
+    }
+    Ok(rom)
+}
@@ -0,0 +1,192 @@
+use chip8emu::Beeper;
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SampleFormat, SizedSample};
+use log::error;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+
+use crate::cli::Waveform;
+
+/// How the buzzer sounds.
+#[derive(Clone, Copy, Debug)]
+pub struct Tone {
+    pub waveform: Waveform,
+    /// Pitch in Hz.
+    pub frequency: f32,
+    /// From 0.0 to 1.0.
+    pub volume: f32,
+    /// Seconds to fade in when the buzzer starts.
+    pub attack: f32,
+    /// Seconds to fade out when it stops.
+    pub release: f32,
+}
+
+// What the machine wants played, shared with the audio thread.
+#[derive(Clone, Copy, Default)]
+struct Voice {
+    beeping: bool,
+    pattern: Option<([u8; 16], f64)>,
+}
+
+/// Plays the buzzer on the default output device: the tone, or XO-CHIP's audio pattern when a
+/// ROM has loaded one.
+pub struct Buzzer {
+    voice: Arc<Mutex<Voice>>,
+    // Sound stops when the stream is dropped.
+    _stream: cpal::Stream,
+}
+
+impl Buzzer {
+    pub fn new(tone: Tone) -> Result<Self, Box<dyn Error>> {
+        let device = cpal::default_host()
+            .default_output_device()
+            .ok_or("no audio output device")?;
+        let config = device.default_output_config()?;
+        let voice = Arc::new(Mutex::new(Voice::default()));
+        let stream = match config.sample_format() {
+            SampleFormat::F32 => build_stream::<f32>(&device, &config, &voice, tone),
+            SampleFormat::I16 => build_stream::<i16>(&device, &config, &voice, tone),
+            SampleFormat::U16 => build_stream::<u16>(&device, &config, &voice, tone),
+            format => return Err(format!("unsupported sample format {}", format).into()),
+        }?;
+        stream.play()?;
+        Ok(Self {
+            voice,
+            _stream: stream,
+        })
+    }
+}
+
+fn build_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::SupportedStreamConfig,
+    voice: &Arc<Mutex<Voice>>,
+    tone: Tone,
+) -> Result<cpal::Stream, cpal::BuildStreamError>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    let channels = usize::from(config.channels());
+    let sample_rate = config.sample_rate().0 as f32;
+    let mut synth = Synth::new(tone, sample_rate);
+    let voice = Arc::clone(voice);
+    device.build_output_stream(
+        &config.config(),
+        move |data: &mut [T], _| {
+            let voice = *voice.lock().unwrap();
+            for frame in data.chunks_mut(channels) {
+                frame.fill(T::from_sample(synth.next(voice)));
+            }
+        },
+        |e| error!("audio stream failed: {}", e),
+        None,
+    )
+}
+
+// Generates the buzzer's samples one at a time.
+struct Synth {
+    tone: Tone,
+    sample_rate: f32,
+    // Position within the current period of the tone, from 0.0 to 1.0.
+    phase: f32,
+    // Current noise level, and the generator for the next.
+    noise: f32,
+    noise_state: u32,
+    // How far the envelope has faded in, from 0.0 to 1.0.
+    level: f32,
+    // Position in the audio pattern, in pattern samples.
+    position: f64,
+}
+
+impl Synth {
+    fn new(tone: Tone, sample_rate: f32) -> Self {
+        Self {
+            tone,
+            sample_rate,
+            phase: 0.0,
+            noise: 1.0,
+            noise_state: 0x1234_5678,
+            level: 0.0,
+            position: 0.0,
+        }
+    }
+
+    fn next(&mut self, voice: Voice) -> f32 {
+        // Fade towards full volume while beeping and silence otherwise, at once if the fade
+        // takes no time.
+        let (target, seconds) = if voice.beeping {
+            (1.0, self.tone.attack)
+        } else {
+            (0.0, self.tone.release)
+        };
+        let step = 1.0 / (seconds * self.sample_rate).max(1.0);
+        self.level = if self.level < target {
+            (self.level + step).min(target)
+        } else {
+            (self.level - step).max(target)
+        };
+        if self.level == 0.0 {
+            return 0.0;
+        }
+        let value = match voice.pattern {
+            Some((pattern, rate)) => self.pattern(&pattern, rate),
+            None => self.tone(),
+        };
+        value * self.level * self.tone.volume
+    }
+
+    fn tone(&mut self) -> f32 {
+        let value = match self.tone.waveform {
+            Waveform::Square if self.phase < 0.5 => 1.0,
+            Waveform::Square => -1.0,
+            Waveform::Triangle => 4.0 * (self.phase - 0.5).abs() - 1.0,
+            Waveform::Noise => self.noise,
+        };
+        self.phase += self.tone.frequency / self.sample_rate;
+        if self.phase >= 1.0 {
+            self.phase %= 1.0;
+            // xorshift32, for a new noise level each period.
+            let mut x = self.noise_state;
+            x ^= x << 13;
+            x ^= x >> 17;
+            x ^= x << 5;
+            self.noise_state = x;
+            self.noise = if x & 1 == 0 { 1.0 } else { -1.0 };
+        }
+        value
+    }
+
+    /// The pattern's 1 bit samples resampled to the output rate, by averaging those the output
+    /// sample spans so that fast patterns don't alias.
+    fn pattern(&mut self, pattern: &[u8; 16], rate: f64) -> f32 {
+        let bit = |index: f64| {
+            let index = index as usize % 128;
+            if pattern[index / 8] & (0x80 >> (index % 8)) != 0 {
+                1.0
+            } else {
+                -1.0
+            }
+        };
+        let end = self.position + rate / f64::from(self.sample_rate);
+        let mut sum = 0.0;
+        let mut at = self.position;
+        while at < end {
+            let next = (at.floor() + 1.0).min(end);
+            sum += bit(at) * (next - at);
+            at = next;
+        }
+        let span = end - self.position;
+        self.position = end % 128.0;
+        (sum / span) as f32
+    }
+}
+
+impl Beeper for Buzzer {
+    fn set_beeping(&mut self, beeping: bool) {
+        self.voice.lock().unwrap().beeping = beeping;
+    }
+
+    fn set_pattern(&mut self, pattern: Option<([u8; 16], f64)>) {
+        self.voice.lock().unwrap().pattern = pattern;
+    }
+}
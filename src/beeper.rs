@@ -0,0 +1,18 @@
+/// Something that can sound the CHIP-8 buzzer. Frontends provide one backed by their audio
+/// output; the core only reports whether the buzzer should be on.
+pub trait Beeper {
+    /// Called after every timer tick with whether the sound timer is running.
+    fn set_beeping(&mut self, beeping: bool);
+
+    /// Called after every timer tick with the XO-CHIP audio pattern to play while beeping, and
+    /// its rate in samples per second, or `None` for the plain buzzer. Beepers that can only
+    /// buzz can ignore it.
+    fn set_pattern(&mut self, _pattern: Option<([u8; 16], f64)>) {}
+}
+
+/// A beeper that stays silent, for headless use or when audio is unavailable.
+pub struct NullBeeper;
+
+impl Beeper for NullBeeper {
+    fn set_beeping(&mut self, _beeping: bool) {}
+}
@@ -0,0 +1,197 @@
+//! Reading and writing `.c8b` files, which carry a ROM along with the platform, quirks, speed,
+//! colors and keys it should run with, so that it can be shared without a list of settings.
+//!
+//! ```text
+//! offset  size  contents
+//! 0       3     "CBF"
+//! 3       1     version, 0
+//! 4       1     platform: 0 for CHIP-8, 1 for SUPER-CHIP, 2 for XO-CHIP
+//! 5       ...   properties, each a tag, the length of its value and the value, ending with a 0
+//!               tag
+//! ...     ...   the program
+//! ```
+//!
+//! The properties, all optional, are:
+//!
+//! - 1: instructions per second, as a big-endian `u32`.
+//! - 2: quirks, one bit for each of `QUIRK_NAMES` from the lowest up.
+//! - 3: up to four RGB colors: the background, the foreground, XO-CHIP's second plane and both
+//!   planes.
+//! - 4: keys, pairs of a CHIP-8 key and the ASCII letter or digit of the keyboard key for it.
+//!
+//! Readers skip properties they don't know, so that later versions can add more.
+
+use std::io;
+
+use crate::chip8::Variant;
+use crate::palette::Palette;
+use crate::quirks::{Quirks, QUIRK_NAMES};
+
+const MAGIC: &[u8] = b"CBF";
+const VERSION: u8 = 0;
+
+const END: u8 = 0;
+const CLOCK: u8 = 1;
+const QUIRKS: u8 = 2;
+const COLORS: u8 = 3;
+const KEYS: u8 = 4;
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// A ROM and the settings stored with it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct C8b {
+    pub variant: Variant,
+    pub quirks: Option<Quirks>,
+    pub clock_hz: Option<u32>,
+    pub palette: Option<Palette>,
+    /// The keyboard key for each CHIP-8 key, as an uppercase ASCII letter or digit.
+    pub keys: [Option<char>; 16],
+    pub program: Vec<u8>,
+}
+
+/// Whether a file is a `.c8b` rather than a plain ROM.
+pub fn is_c8b(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+impl C8b {
+    /// Parse a `.c8b` file.
+    pub fn parse(data: &[u8]) -> io::Result<Self> {
+        let header = data
+            .strip_prefix(MAGIC)
+            .ok_or_else(|| invalid("not a .c8b file"))?;
+        let (&version, header) = header.split_first().ok_or_else(|| invalid("no version"))?;
+        if version != VERSION {
+            return Err(invalid(&format!("unsupported .c8b version {}", version)));
+        }
+        let (&platform, mut rest) = header.split_first().ok_or_else(|| invalid("no platform"))?;
+        let mut c8b = Self {
+            variant: match platform {
+                0 => Variant::Chip8,
+                1 => Variant::SuperChip,
+                2 => Variant::XoChip,
+                _ => return Err(invalid(&format!("unknown platform {}", platform))),
+            },
+            ..Self::default()
+        };
+        loop {
+            let (&tag, after_tag) = rest.split_first().ok_or_else(|| invalid("truncated"))?;
+            if tag == END {
+                c8b.program = after_tag.to_vec();
+                return Ok(c8b);
+            }
+            let (&length, after_length) = after_tag
+                .split_first()
+                .ok_or_else(|| invalid("truncated"))?;
+            if after_length.len() < length as usize {
+                return Err(invalid("truncated"));
+            }
+            let (value, after_value) = after_length.split_at(length as usize);
+            c8b.read_property(tag, value)?;
+            rest = after_value;
+        }
+    }
+
+    fn read_property(&mut self, tag: u8, value: &[u8]) -> io::Result<()> {
+        match tag {
+            CLOCK => {
+                let bytes = value.try_into().map_err(|_| invalid("bad clock"))?;
+                self.clock_hz = Some(u32::from_be_bytes(bytes));
+            }
+            QUIRKS => {
+                let [bits] = value else {
+                    return Err(invalid("bad quirks"));
+                };
+                let mut quirks = Quirks::default();
+                for (i, name) in QUIRK_NAMES.iter().enumerate() {
+                    quirks.set(name, bits & 1 << i != 0).unwrap();
+                }
+                self.quirks = Some(quirks);
+            }
+            COLORS => {
+                if value.is_empty() || value.len() > 12 || !value.len().is_multiple_of(3) {
+                    return Err(invalid("bad colors"));
+                }
+                let mut colors = Palette::default().colors();
+                for (color, rgb) in colors.iter_mut().zip(value.chunks(3)) {
+                    *color = [rgb[0], rgb[1], rgb[2], 0xff];
+                }
+                let [background, foreground, plane_2, both_planes] = colors;
+                self.palette = Some(Palette {
+                    background,
+                    foreground,
+                    plane_2,
+                    both_planes,
+                });
+            }
+            KEYS => {
+                if !value.len().is_multiple_of(2) {
+                    return Err(invalid("bad keys"));
+                }
+                for pair in value.chunks(2) {
+                    let key = pair[0] as usize;
+                    let ascii = pair[1].to_ascii_uppercase();
+                    if key > 0xF || !ascii.is_ascii_alphanumeric() {
+                        return Err(invalid("bad keys"));
+                    }
+                    self.keys[key] = Some(ascii as char);
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Write the ROM and its settings as a `.c8b` file. Keys that aren't ASCII letters or
+    /// digits are left out.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = MAGIC.to_vec();
+        data.push(VERSION);
+        data.push(match self.variant {
+            Variant::Chip8 => 0,
+            Variant::SuperChip => 1,
+            Variant::XoChip => 2,
+        });
+        let mut property = |tag: u8, value: &[u8]| {
+            data.push(tag);
+            data.push(value.len() as u8);
+            data.extend_from_slice(value);
+        };
+        if let Some(hz) = self.clock_hz {
+            property(CLOCK, &hz.to_be_bytes());
+        }
+        if let Some(quirks) = &self.quirks {
+            let bits = QUIRK_NAMES
+                .iter()
+                .enumerate()
+                .filter(|(_, name)| quirks.is_set(name))
+                .fold(0, |bits, (i, _)| bits | 1 << i);
+            property(QUIRKS, &[bits]);
+        }
+        if let Some(palette) = &self.palette {
+            let rgb: Vec<u8> = palette
+                .colors()
+                .iter()
+                .flat_map(|color| &color[..3])
+                .copied()
+                .collect();
+            property(COLORS, &rgb);
+        }
+        let keys: Vec<u8> = (0..16u8)
+            .zip(self.keys)
+            .filter_map(|(key, ascii)| {
+                Some([key, ascii.filter(char::is_ascii_alphanumeric)? as u8])
+            })
+            .flatten()
+            .collect();
+        if !keys.is_empty() {
+            property(KEYS, &keys);
+        }
+        data.push(END);
+        data.extend_from_slice(&self.program);
+        data
+    }
+}
@@ -0,0 +1,160 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use chip8emu::chip8::Chip8Emulator;
+use chip8emu::disasm::{self, Flow};
+
+#[derive(Default)]
+struct Edge {
+    // Found by following the ROM's code without running it.
+    in_static: bool,
+    // Number of times the call was made while the ROM ran.
+    observed: u64,
+}
+
+/// The graph of which subroutines call which, found statically from the ROM and optionally
+/// from calls observed while it runs. Exported as Graphviz DOT.
+pub struct CallGraph {
+    entry: u16,
+    edges: BTreeMap<(u16, u16), Edge>,
+    observing: bool,
+    // Entry points of the subroutines currently being executed.
+    call_stack: Vec<u16>,
+}
+
+impl CallGraph {
+    /// Build the static call graph of a ROM loaded at `origin`, which is also the entry point.
+    pub fn from_rom(rom: &[u8], origin: u16) -> Self {
+        let mut graph = Self {
+            entry: origin,
+            edges: BTreeMap::new(),
+            observing: false,
+            call_stack: vec![origin],
+        };
+
+        let word_at = |address: u16| {
+            let offset = usize::from(address.checked_sub(origin)?);
+            let bytes = rom.get(offset..offset + 2)?;
+            Some(u16::from(bytes[0]) << 8 | u16::from(bytes[1]))
+        };
+
+        let mut subroutines = vec![origin];
+        let mut seen_subroutines = BTreeSet::from([origin]);
+        while let Some(subroutine) = subroutines.pop() {
+            // Walk the body of the subroutine without descending into the ones it calls.
+            let mut pending = vec![subroutine];
+            let mut visited = BTreeSet::new();
+            while let Some(address) = pending.pop() {
+                if !visited.insert(address) {
+                    continue;
+                }
+                let word = match word_at(address) {
+                    Some(word) if disasm::is_instruction(word) => word,
+                    _ => continue,
+                };
+                match disasm::flow(word) {
+                    Flow::Next => pending.push(address.wrapping_add(2)),
+                    Flow::Skip => {
+                        pending.extend([address.wrapping_add(2), address.wrapping_add(4)])
+                    }
+                    Flow::Stop => {}
+                    Flow::Jump(target) => pending.push(target),
+                    Flow::Call(target) => {
+                        graph
+                            .edges
+                            .entry((subroutine, target))
+                            .or_default()
+                            .in_static = true;
+                        if seen_subroutines.insert(target) {
+                            subroutines.push(target);
+                        }
+                        pending.push(address.wrapping_add(2));
+                    }
+                }
+            }
+        }
+        graph
+    }
+
+    /// Record the instruction the emulator is about to execute. Call this before every cycle
+    /// to add the calls actually made at runtime to the graph.
+    pub fn observe(&mut self, emu: &Chip8Emulator) {
+        self.observing = true;
+
+        let pc = emu.pc();
+        let word = match emu.memory().get(pc..pc + 2) {
+            Some(bytes) => u16::from(bytes[0]) << 8 | u16::from(bytes[1]),
+            None => return,
+        };
+        match disasm::flow(word) {
+            Flow::Call(target) => {
+                let caller = *self.call_stack.last().unwrap_or(&self.entry);
+                self.edges.entry((caller, target)).or_default().observed += 1;
+                self.call_stack.push(target);
+            }
+            Flow::Stop if word == 0x00EE => {
+                self.call_stack.pop();
+            }
+            _ => {}
+        }
+    }
+
+    /// Export the graph as Graphviz DOT. When calls were observed at runtime, edges are labelled
+    /// with their call counts and static edges that were never taken are greyed out.
+    pub fn to_dot(&self) -> String {
+        let mut nodes = BTreeSet::from([self.entry]);
+        for (caller, callee) in self.edges.keys() {
+            nodes.insert(*caller);
+            nodes.insert(*callee);
+        }
+
+        let mut out = String::new();
+        writeln!(out, "digraph calls {{").unwrap();
+        writeln!(out, "    node [shape=box, fontname=\"monospace\"];").unwrap();
+        for node in &nodes {
+            let name = if *node == self.entry {
+                "main".to_string()
+            } else {
+                format!("sub_{:03X}", node)
+            };
+            writeln!(out, "    \"{:#05X}\" [label=\"{}\"];", node, name).unwrap();
+        }
+        for ((caller, callee), edge) in &self.edges {
+            let mut attributes = Vec::new();
+            if self.observing {
+                if edge.observed > 0 {
+                    attributes.push(format!("label=\"{}\"", edge.observed));
+                } else {
+                    attributes.push("color=gray".to_string());
+                }
+            }
+            if !edge.in_static {
+                // Only reachable through something static analysis can't follow, like BNNN.
+                attributes.push("style=dashed".to_string());
+            }
+            writeln!(
+                out,
+                "    \"{:#05X}\" -> \"{:#05X}\" [{}];",
+                caller,
+                callee,
+                attributes.join(", ")
+            )
+            .unwrap();
+        }
+        writeln!(out, "}}").unwrap();
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn code_at_the_end_of_the_address_space_is_walked() {
+        // A skip and then a call in the last four bytes, both followed by addresses past 0xFFFF.
+        let graph = CallGraph::from_rom(&[0x30, 0x00, 0x2F, 0xFC], 0xFFFC);
+        assert!(graph.edges.contains_key(&(0xFFFC, 0xFFC)));
+        assert!(graph.to_dot().contains("\"0xFFFC\" -> \"0xFFC\""));
+    }
+}
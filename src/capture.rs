@@ -0,0 +1,134 @@
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+use std::time::SystemTime;
+
+use chip8emu::chip8::Chip8Emulator;
+use chip8emu::palette::Palette;
+
+use crate::clipboard::scale_frame;
+
+// Image pixels per CHIP-8 pixel in screenshots.
+const SCREENSHOT_SCALE: usize = 8;
+// Size of recorded GIFs: SUPER-CHIP's high resolution at 4 image pixels per CHIP-8 pixel, and
+// the low resolution at 8, so switching doesn't change the size.
+const GIF_WIDTH: usize = 512;
+const GIF_HEIGHT: usize = 256;
+
+/// A file name in the working directory that won't collide with earlier captures.
+pub fn capture_path(kind: &str, extension: &str) -> String {
+    let timestamp = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    format!("chip8-{}-{}.{}", kind, timestamp, extension)
+}
+
+/// Save the emulator's screen, without any effects or overlays, as a PNG with each CHIP-8
+/// pixel drawn as a square block.
+pub fn write_png(path: impl AsRef<Path>, emu: &Chip8Emulator, palette: &Palette) -> io::Result<()> {
+    let mut frame = vec![0; 4 * emu.screen().len()];
+    emu.draw_screen_with_palette(&mut frame, palette);
+    for pixel in frame.chunks_exact_mut(4) {
+        pixel[3] = 0xff;
+    }
+    let (width, height) = (emu.screen_width(), emu.screen_height());
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(
+        file,
+        (width * SCREENSHOT_SCALE) as u32,
+        (height * SCREENSHOT_SCALE) as u32,
+    );
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| {
+            writer.write_image_data(&scale_frame(&frame, width, SCREENSHOT_SCALE))
+        })
+        .map_err(io::Error::other)
+}
+
+/// Records the emulator's screen into an animated GIF, one frame per 60 Hz tick. Frames that
+/// don't change the picture just lengthen the one before, which keeps files small.
+pub struct GifRecorder {
+    encoder: gif::Encoder<BufWriter<File>>,
+    // The last captured frame as palette indices, which is only written once the next
+    // different one shows how long it lasted.
+    pending: Option<Vec<u8>>,
+    // Ticks captured so far, and the tick the pending frame started on.
+    ticks: u64,
+    pending_since: u64,
+}
+
+impl GifRecorder {
+    /// Start a recording in the palette's colors.
+    pub fn create(path: impl AsRef<Path>, palette: &Palette) -> io::Result<Self> {
+        let colors: Vec<u8> = palette
+            .colors()
+            .iter()
+            .flat_map(|color| color[..3].to_vec())
+            .collect();
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = gif::Encoder::new(file, GIF_WIDTH as u16, GIF_HEIGHT as u16, &colors)
+            .map_err(io::Error::other)?;
+        encoder
+            .set_repeat(gif::Repeat::Infinite)
+            .map_err(io::Error::other)?;
+        Ok(Self {
+            encoder,
+            pending: None,
+            ticks: 0,
+            pending_since: 0,
+        })
+    }
+
+    /// Capture the screen as it is at this tick.
+    pub fn capture(&mut self, emu: &Chip8Emulator) -> io::Result<()> {
+        let width = emu.screen_width();
+        let scale = GIF_WIDTH / width;
+        let mut indices = Vec::with_capacity(GIF_WIDTH * GIF_HEIGHT);
+        for row in emu.screen().chunks(width) {
+            let scaled_row: Vec<u8> = row
+                .iter()
+                .flat_map(|planes| std::iter::repeat_n(planes & 3, scale))
+                .collect();
+            for _ in 0..scale {
+                indices.extend_from_slice(&scaled_row);
+            }
+        }
+
+        if self.pending.as_ref() != Some(&indices) {
+            self.write_pending()?;
+            self.pending = Some(indices);
+            self.pending_since = self.ticks;
+        }
+        self.ticks += 1;
+        Ok(())
+    }
+
+    /// Write the last frame and the end of the file.
+    pub fn finish(mut self) -> io::Result<()> {
+        self.write_pending()?;
+        self.encoder.into_inner().map(drop)
+    }
+
+    fn write_pending(&mut self) -> io::Result<()> {
+        let Some(indices) = self.pending.take() else {
+            return Ok(());
+        };
+        // GIF delays are in hundredths of a second, so round each frame's start and end to
+        // those rather than each length, which would drift.
+        let centiseconds = |ticks: u64| (ticks * 100 + 30) / 60;
+        let delay = centiseconds(self.ticks) - centiseconds(self.pending_since);
+        let frame = gif::Frame {
+            width: GIF_WIDTH as u16,
+            height: GIF_HEIGHT as u16,
+            delay: delay.min(u64::from(u16::MAX)) as u16,
+            buffer: Cow::Owned(indices),
+            ..gif::Frame::default()
+        };
+        self.encoder.write_frame(&frame).map_err(io::Error::other)
+    }
+}
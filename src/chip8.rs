@@ -1,28 +1,150 @@
-use std::fs::File;
-use std::io::Read;
+use log::{info, trace, warn};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+#[cfg(not(target_arch = "wasm32"))]
+use std::fs;
+use std::num::ParseIntError;
+use std::ops::Range;
+use std::str::FromStr;
+use std::sync::Arc;
 
-macro_rules! join_nibbles {
-    ($r0:ident) => {
-        u8::from($r0)
-    };
-    ($r0:ident, $r1:ident) => {
-        (u8::from($r0) << 4) | (u8::from($r1))
-    };
-    ($r0:ident, $r1:ident, $r2:ident) => {
-        (u16::from($r0) << 8) | (u16::from($r1) << 4) | (u16::from($r2))
-    };
-    ($r0:ident, $r1:ident, $r2:ident, $r3:ident) => {
-        (u16::from($r0) << 12) | (u16::from($r1) << 8) | (u16::from($r2) << 4) | (u16::from($r3))
-    };
+use crate::clock::CycleTable;
+use crate::error::Chip8Error;
+use crate::opcode::{self, Opcode};
+use crate::palette::Palette;
+use crate::quirks::Quirks;
+
+/// What memory outside the font and ROM holds when the machine is initialized. Some ROMs read
+/// memory they never wrote and rely on whatever their original interpreter left there.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MemoryFill {
+    /// Every byte set to the same value.
+    Byte(u8),
+    /// Pseudo-random bytes from a seed, so a run can be reproduced.
+    Random(u64),
 }
 
+impl Default for MemoryFill {
+    fn default() -> Self {
+        MemoryFill::Byte(0)
+    }
+}
+
+impl FromStr for MemoryFill {
+    type Err = ParseIntError;
+
+    /// Parses `0xFF`, `255`, `random` or `random:<seed>`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "random" {
+            return Ok(MemoryFill::Random(rand::random()));
+        }
+        if let Some(seed) = s.strip_prefix("random:") {
+            return Ok(MemoryFill::Random(seed.parse()?));
+        }
+        match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+            Some(hex) => u8::from_str_radix(hex, 16).map(MemoryFill::Byte),
+            None => s.parse().map(MemoryFill::Byte),
+        }
+    }
+}
+
+/// The CHIP-8 dialect being emulated. Each one extends the ones before it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Variant {
+    #[default]
+    Chip8,
+    /// SUPER-CHIP 1.1: a 128x64 high resolution mode, scrolling, 16x16 sprites, a large font and
+    /// RPL user flags.
+    SuperChip,
+    /// Octo's XO-CHIP: 64K of memory, two display planes, register ranges and an audio pattern
+    /// buffer.
+    XoChip,
+}
+
+impl FromStr for Variant {
+    type Err = String;
+
+    /// Parses `chip8`, `schip` or `xochip`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "chip8" => Ok(Variant::Chip8),
+            "schip" => Ok(Variant::SuperChip),
+            "xochip" => Ok(Variant::XoChip),
+            _ => Err(format!(
+                "unknown variant {}, expected chip8, schip or xochip",
+                s
+            )),
+        }
+    }
+}
+
+/// Why `run_for` or `run_until` stopped running instructions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StepOutcome {
+    /// All the instructions asked for ran.
+    Completed,
+    /// The condition given to `run_until` held, before the instruction at `pc()` ran.
+    HitBreakpoint,
+    /// FX0A is waiting for a key, and nothing more runs until one is pressed.
+    WaitingForKey,
+    /// A sprite was drawn with the display wait quirk on, and nothing more runs until the next
+    /// `tick_timers()`.
+    DisplayUpdated,
+    /// The ROM exited with the SUPER-CHIP exit instruction.
+    Halted,
+}
+
+/// Hooks for frontends and tools that react to what a ROM does, instead of polling the machine
+/// after every instruction. Register one with `Chip8Emulator::add_observer`. Every hook does
+/// nothing by default, and each is called after the machine has changed.
+pub trait Chip8Observer: Send {
+    /// The screen changed: a sprite was drawn, or it was cleared, scrolled or resized.
+    fn on_draw(&mut self, _emu: &Chip8Emulator) {}
+
+    /// The sound timer was set above zero, so the buzzer should start.
+    fn on_sound_start(&mut self, _emu: &Chip8Emulator) {}
+
+    /// The sound timer ran out or was cleared, so the buzzer should stop.
+    fn on_sound_stop(&mut self, _emu: &Chip8Emulator) {}
+
+    /// FX0A started waiting for a key.
+    fn on_key_wait(&mut self, _emu: &Chip8Emulator) {}
+
+    /// An instruction stored `value` at `address`.
+    fn on_memory_write(&mut self, _emu: &Chip8Emulator, _address: usize, _value: u8) {}
+
+    /// The word at `pc()` isn't an instruction the machine can run, and execution stopped with
+    /// an error.
+    fn on_unknown_opcode(&mut self, _emu: &Chip8Emulator, _opcode: u16) {}
+}
+
+// The registered observers. A cloned machine starts without any, so that a copy run ahead or
+// alongside doesn't report to the original's frontend.
+#[derive(Default)]
+struct Observers(Vec<Box<dyn Chip8Observer>>);
+
+impl Clone for Observers {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+/// A whole machine. Cloning it is cheap, and the clone runs on exactly as the original would,
+/// though without the original's observers.
+#[derive(Clone)]
+#[allow(non_snake_case)]
 pub struct Chip8Emulator {
     // The Chip 8 has 4k memory.
     // Bytes 0x000 to 0x1FF were used to store the Chip 8 intepreter itself, but this isn't needed
     // with modern emulators, so this range is usually used to store font data now.
     // The uppermost 256 bytes (0xF00 to 0xFFF) are reserved for display refresh?
     // Bytes 0xEA0 to 0xEFF were reserved for the call stack, internal use, and other variables?
-    memory: [u8; 4096],
+    // XO-CHIP has 64k instead.
+    memory: Vec<u8>,
+    // What initialize() fills memory with before loading the font.
+    memory_fill: MemoryFill,
     // It has 15 8-bit general purpose registers named V0, V1, ..., VE.
     // The 16th VF register is used for the "carry flag" and other instruction specific flags.
     V: [u8; 16],
@@ -30,9 +152,23 @@ pub struct Chip8Emulator {
     // bits).
     I: usize,
     pc: usize,
+    // Where ROMs are loaded and execution starts.
+    load_address: usize,
+
+    variant: Variant,
 
-    // The Chip 8 has 64 by 32 screen of black and white pixels.
-    pub screen: [bool; 64 * 32],
+    // The Chip 8 has 64 by 32 screen of black and white pixels. SUPER-CHIP can switch to 128 by 64,
+    // so there is room for that, with rows as wide as the current resolution.
+    // XO-CHIP has two display planes, so each pixel is a bit mask of the planes lit there.
+    screen: [u8; 128 * 64],
+    // Set whenever the screen changes, until take_screen_changed() collects it.
+    screen_changed: bool,
+    hires: bool,
+    quirks: Quirks,
+    // Bit mask of the planes that drawing, clearing and scrolling apply to.
+    planes: u8,
+    // Set by the SUPER-CHIP exit instruction.
+    halted: bool,
 
     // The Chip 8 has two timer registers which count at 60hz.
     // When set above zero they count back down to zero.
@@ -47,13 +183,178 @@ pub struct Chip8Emulator {
     // The Chip 8 uses a hex keyboard for input. This has 16 keys ranging from '0' to 'F'.
     // We can use a boolean array to store the state of each key.
     keys: [bool; 16],
+    // Keys the ROM has checked via EX9E, EXA1 or FX0A since they were last collected.
+    polled_keys: [bool; 16],
 
     // True when we are waiting for a key input. The emulator is effectively paused until it
     // receives a key.
     waiting_for_key: bool,
     key_reg: usize,
+    // Set by a draw with the display wait quirk, until the next timer tick.
+    waiting_for_display: bool,
+
+    // SUPER-CHIP's RPL user flags, named after the HP48 calculator registers they lived in.
+    rpl_flags: [u8; 8],
+
+    // XO-CHIP's 1 bit audio samples, played while the sound timer runs, and their playback rate.
+    audio_pattern: [u8; 16],
+    pitch: u8,
+
+    // The last ROM loaded, so that reset() can load it again.
+    rom: Vec<u8>,
+
+    // Source of CXNN's random numbers, and the seed initialize() restarts it from, if one was
+    // given. Without a seed it starts from entropy and carries on across resets.
+    rng: SplitMix64,
+    rng_seed: Option<u64>,
+
+    // Instructions run since the machine was created.
+    executed: u64,
+    // COSMAC VIP machine cycles those instructions would have taken, see Opcode::vip_cycles(),
+    // or cycles on another interpreter with a cycle table.
+    cycles: u64,
+    cycle_table: Option<Arc<CycleTable>>,
+
+    observers: Observers,
+}
+
+/// A small PRNG. Unlike the generators in `rand` its whole state is one number, so it can be
+/// saved with the rest of the machine and resumed runs stay reproducible.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+// Bumped whenever SavedState changes, so old snapshots are rejected instead of misread.
+const STATE_VERSION: u32 = 3;
+// Version 2 stored memory and the screen as plain arrays, which version 3 still reads.
+const OLDEST_STATE_VERSION: u32 = 2;
+
+/// Everything needed to resume a machine exactly where it was.
+#[derive(Serialize, Deserialize)]
+#[allow(non_snake_case)]
+struct SavedState {
+    version: u32,
+    memory: StateBytes,
+    V: [u8; 16],
+    I: usize,
+    pc: usize,
+    variant: Variant,
+    quirks: Quirks,
+    screen: StateBytes,
+    hires: bool,
+    planes: u8,
+    halted: bool,
+    delay_timer: u8,
+    sound_timer: u8,
+    stack: [u16; 16],
+    sp: usize,
+    keys: [bool; 16],
+    waiting_for_key: bool,
+    key_reg: usize,
+    rpl_flags: [u8; 8],
+    audio_pattern: [u8; 16],
+    pitch: u8,
+    rng: SplitMix64,
 }
 
+/// Memory or the screen in a save state. Both are mostly runs of the same byte, like unused
+/// memory and unlit pixels, so they are saved compressed.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum StateBytes {
+    /// PackBits runs as hex.
+    Packed(String),
+    /// Every byte, as in version 2.
+    Plain(Vec<u8>),
+}
+
+impl StateBytes {
+    fn pack(data: &[u8]) -> Self {
+        let packed = pack_bits(data);
+        StateBytes::Packed(packed.iter().map(|byte| format!("{:02x}", byte)).collect())
+    }
+
+    fn unpack(self) -> Option<Vec<u8>> {
+        match self {
+            StateBytes::Plain(data) => Some(data),
+            StateBytes::Packed(hex) => {
+                if !hex.is_ascii() || !hex.len().is_multiple_of(2) {
+                    return None;
+                }
+                let packed = (0..hex.len())
+                    .step_by(2)
+                    .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+                    .collect::<Option<Vec<u8>>>()?;
+                unpack_bits(&packed)
+            }
+        }
+    }
+}
+
+/// Compress with PackBits: a control byte `n` below 128 is followed by `n + 1` bytes as they
+/// are, and one above 128 by a byte repeated `257 - n` times.
+fn pack_bits(data: &[u8]) -> Vec<u8> {
+    let run_at = |i: usize| {
+        data[i..]
+            .iter()
+            .take(128)
+            .take_while(|byte| **byte == data[i])
+            .count()
+    };
+    let mut packed = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let run = run_at(i);
+        if run >= 3 {
+            packed.extend_from_slice(&[(257 - run) as u8, data[i]]);
+            i += run;
+            continue;
+        }
+        // Bytes as they are, up to where a run worth packing starts.
+        let start = i;
+        while i < data.len() && i - start < 128 && run_at(i) < 3 {
+            i += 1;
+        }
+        packed.push((i - start - 1) as u8);
+        packed.extend_from_slice(&data[start..i]);
+    }
+    packed
+}
+
+/// Undo pack_bits(), or `None` if the data is damaged.
+fn unpack_bits(packed: &[u8]) -> Option<Vec<u8>> {
+    let mut data = Vec::new();
+    let mut rest = packed;
+    while let Some((&control, after)) = rest.split_first() {
+        rest = match control {
+            0..=127 => {
+                let literal = after.get(..usize::from(control) + 1)?;
+                data.extend_from_slice(literal);
+                &after[literal.len()..]
+            }
+            128 => return None,
+            _ => {
+                let (&byte, after) = after.split_first()?;
+                data.resize(data.len() + 257 - usize::from(control), byte);
+                after
+            }
+        };
+    }
+    Some(data)
+}
+
+/// Where ROMs are loaded unless set_load_address() says otherwise.
+pub const DEFAULT_LOAD_ADDRESS: usize = 0x200;
+
 const CHIP_8_FONTSET: [u8; 80] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
     0x20, 0x60, 0x20, 0x20, 0x70, // 1
@@ -73,142 +374,778 @@ const CHIP_8_FONTSET: [u8; 80] = [
     0xF0, 0x80, 0xF0, 0x80, 0x80, // F
 ];
 
+// SUPER-CHIP's 8x10 digits, stored after the regular font.
+const BIG_FONT_ADDRESS: usize = 0x50;
+const SCHIP_BIG_FONTSET: [u8; 100] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+];
+
+// SCHIP ROMs archived from HP48 calculators may still carry the binary transfer header: the
+// "HPHP48-" magic and a version letter, followed by the 5 byte prologue and length of the
+// object holding the program.
+const HP48_MAGIC: &[u8] = b"HPHP48-";
+const HP48_HEADER_LEN: usize = 13;
+
+fn strip_hp48_header(rom: &[u8]) -> &[u8] {
+    if rom.starts_with(HP48_MAGIC) && rom.len() >= HP48_HEADER_LEN {
+        info!("stripping HP48 transfer header from ROM");
+        &rom[HP48_HEADER_LEN..]
+    } else {
+        rom
+    }
+}
+
+impl Default for Chip8Emulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Chip8Emulator {
     pub fn new() -> Self {
         Self {
-            memory: [0; 4096],
+            memory: vec![0; 4096],
+            memory_fill: MemoryFill::default(),
             V: [0; 16],
             I: 0,
             pc: 0,
-            screen: [false; 64 * 32],
+            load_address: DEFAULT_LOAD_ADDRESS,
+            variant: Variant::default(),
+            screen: [0; 128 * 64],
+            screen_changed: true,
+            hires: false,
+            quirks: Quirks::default(),
+            planes: 1,
+            halted: false,
             delay_timer: 0,
             sound_timer: 0,
             stack: [0; 16],
             sp: 0,
             keys: [false; 16],
+            polled_keys: [false; 16],
             waiting_for_key: false,
             key_reg: 0,
+            waiting_for_display: false,
+            rpl_flags: [0; 8],
+            audio_pattern: [0; 16],
+            pitch: 64,
+            rom: Vec::new(),
+            rng: SplitMix64(rand::random()),
+            rng_seed: None,
+            executed: 0,
+            cycles: 0,
+            cycle_table: None,
+            observers: Observers::default(),
         }
     }
 
     /// Initialize memory and registers
     pub fn initialize(&mut self) {
-        self.pc = 0x200;
+        let was_beeping = self.is_beeping();
+        self.pc = self.load_address;
         self.I = 0;
         self.sp = 0;
 
-        self.screen = [false; 64 * 32];
+        self.screen = [0; 128 * 64];
+        self.hires = false;
+        self.planes = 1;
+        self.audio_pattern = [0; 16];
+        self.pitch = 64;
+        self.halted = false;
         self.stack = [0; 16];
         self.V = [0; 16];
-        self.memory = [0; 4096];
+        let memory_size = if self.variant == Variant::XoChip {
+            0x10000
+        } else {
+            0x1000
+        };
+        match self.memory_fill {
+            MemoryFill::Byte(value) => self.memory = vec![value; memory_size],
+            MemoryFill::Random(seed) => {
+                info!("filling memory with random bytes from seed {}", seed);
+                self.memory = vec![0; memory_size];
+                StdRng::seed_from_u64(seed).fill(&mut self.memory[..]);
+            }
+        }
 
         // Load fontset
         for (i, byte) in CHIP_8_FONTSET.iter().enumerate() {
             self.memory[i] = *byte;
         }
+        self.memory[BIG_FONT_ADDRESS..BIG_FONT_ADDRESS + SCHIP_BIG_FONTSET.len()]
+            .copy_from_slice(&SCHIP_BIG_FONTSET);
 
         // Reset timers
         self.delay_timer = 0;
         self.sound_timer = 0;
 
-        // Reset boolean flag
+        // Reset boolean flags
         self.waiting_for_key = false;
+        self.waiting_for_display = false;
+
+        if let Some(seed) = self.rng_seed {
+            self.rng = SplitMix64(seed);
+        }
+        self.screen_updated();
+        self.sound_changed(was_beeping);
+    }
+
+    /// Load ROMs at `address` and start running them there, instead of at 0x200. ETI-660
+    /// programs expect 0x600. Takes effect on the next initialize() and load.
+    pub fn set_load_address(&mut self, address: usize) {
+        self.load_address = address;
+    }
+
+    pub fn load_address(&self) -> usize {
+        self.load_address
+    }
+
+    /// Make CXNN's random numbers repeat from run to run, for replays and tests. The sequence
+    /// starts again from the seed whenever the machine is initialized or reset.
+    pub fn set_rng_seed(&mut self, seed: u64) {
+        self.rng_seed = Some(seed);
+        self.rng = SplitMix64(seed);
+    }
+
+    /// Read a ROM file and load it. Not available in the browser, which has no files; use
+    /// load_rom_bytes() there.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_game(&mut self, game_name: &str) -> Result<(), Chip8Error> {
+        let rom = fs::read(game_name)?;
+        self.load_rom_bytes(&rom)
     }
 
-    pub fn load_game(&mut self, game_name: &str) -> std::io::Result<()> {
-        let file = File::open(game_name)?;
-        for (i, byte) in file.bytes().enumerate() {
-            self.memory[i + 0x200] = byte.unwrap();
+    /// Load a ROM image that has already been read, e.g. after patching it.
+    pub fn load_rom_bytes(&mut self, rom: &[u8]) -> Result<(), Chip8Error> {
+        let program = strip_hp48_header(rom);
+        let start = self.load_address;
+        if start + program.len() > self.memory.len() {
+            return Err(Chip8Error::RomTooLarge {
+                size: program.len(),
+                available: self.memory.len().saturating_sub(start),
+            });
         }
+        self.memory[start..start + program.len()].copy_from_slice(program);
+        self.rom = rom.to_vec();
         Ok(())
     }
 
-    pub fn draw_screen(&self, screen: &mut [u8]) {
-        debug_assert_eq!(screen.len(), 4 * self.screen.len());
-        for (p, pix) in self.screen.iter().zip(screen.chunks_exact_mut(4)) {
-            let color = if *p {
-                [0xff, 0xff, 0xff, 0xff]
-            } else {
-                [0, 0, 0, 0]
-            };
-            pix.copy_from_slice(&color);
-        }
-    }
-
-    fn split_opcode(value: u16) -> (u8, u8, u8, u8) {
-        (
-            ((value >> 12) & 0xF).try_into().unwrap(),
-            ((value >> 8) & 0xF).try_into().unwrap(),
-            ((value >> 4) & 0xF).try_into().unwrap(),
-            ((value >> 0) & 0xF).try_into().unwrap(),
-        )
-    }
-
-    fn handle_opcode(&mut self, opcode_value: u16) {
-        match Self::split_opcode(opcode_value) {
-            (0x0, 0x0, 0xE, 0x0) => self.clear_screen(),
-            (0x0, 0x0, 0xE, 0xE) => self.return_subroutine(),
-            (0x0, r2, r1, r0) => self.machine_code_routine(join_nibbles!(r2, r1, r0)),
-            (0x1, r2, r1, r0) => self.goto(join_nibbles!(r2, r1, r0)),
-            (0x2, r2, r1, r0) => self.call_subroutine(join_nibbles!(r2, r1, r0)),
-            (0x3, x, c1, c0) => self.skip_const_eq(x, join_nibbles!(c1, c0)),
-            (0x4, x, c1, c0) => self.skip_const_neq(x, join_nibbles!(c1, c0)),
-            (0x5, x, y, 0x0) => self.skip_reg_eq(x, y),
-            (0x6, x, c1, c0) => self.set_const(x, join_nibbles!(c1, c0)),
-            (0x7, x, c1, c0) => self.add_const(x, join_nibbles!(c1, c0)),
-            (0x8, x, y, 0x0) => self.set(x, y),
-            (0x8, x, y, 0x1) => self.or(x, y),
-            (0x8, x, y, 0x2) => self.and(x, y),
-            (0x8, x, y, 0x3) => self.xor(x, y),
-            (0x8, x, y, 0x4) => self.add(x, y),
-            (0x8, x, y, 0x5) => self.sub(x, y),
-            (0x8, x, y, 0x6) => self.div_2(x, y),
-            (0x8, x, y, 0x7) => self.diff(x, y),
-            (0x8, x, y, 0xE) => self.mul_2(x, y),
-            (0x9, x, y, 0x0) => self.skip_reg_neq(x, y),
-            (0xA, r2, r1, r0) => self.set_i(join_nibbles!(r2, r1, r0)),
-            (0xB, r2, r1, r0) => self.jump_offset(join_nibbles!(r2, r1, r0)),
-            (0xC, x, c1, c0) => self.rand(x, join_nibbles!(c1, c0)),
-            (0xD, x, y, c) => self.draw(x, y, c),
-            (0xE, x, 0x9, 0xE) => self.skip_if_key(x),
-            (0xE, x, 0xA, 0x1) => self.skip_if_nkey(x),
-            (0xF, x, 0x0, 0x7) => self.get_delay(x),
-            (0xF, x, 0x0, 0xA) => self.get_key(x),
-            (0xF, x, 0x1, 0x5) => self.set_delay(x),
-            (0xF, x, 0x1, 0x8) => self.set_sound(x),
-            (0xF, x, 0x1, 0xE) => self.inc_i(x),
-            (0xF, x, 0x2, 0x9) => self.set_i_sprite(x),
-            (0xF, x, 0x3, 0x3) => self.bcd(x),
-            (0xF, x, 0x5, 0x5) => self.reg_dump(x),
-            (0xF, x, 0x6, 0x5) => self.reg_load(x),
-            _ => panic!(
-                "{:#06X} is not a recognized opcode (pc: {:#X})",
-                opcode_value, self.pc
-            ),
-        }
-    }
-
-    pub fn emulate_cycle(&mut self) {
-        // Don't do anything if waiting for a key
-        if self.waiting_for_key {
+    /// Restart the loaded ROM from the beginning, as if the machine had been switched off and
+    /// on. The variant, quirks and memory fill stay as they are. Fails if the ROM no longer
+    /// fits, e.g. after switching to a variant with less memory or moving the load address.
+    pub fn reset(&mut self) -> Result<(), Chip8Error> {
+        self.initialize();
+        let rom = std::mem::take(&mut self.rom);
+        let loaded = self.load_rom_bytes(&rom);
+        self.rom = rom;
+        loaded
+    }
+
+    /// Load a file into memory at `address`, on top of whatever is already there.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_segment(&mut self, path: &str, address: usize) -> Result<(), Chip8Error> {
+        let data = fs::read(path)?;
+        self.load_segment_bytes(&data, address)
+    }
+
+    /// Copy data into memory at `address`, on top of whatever is already there.
+    pub fn load_segment_bytes(&mut self, data: &[u8], address: usize) -> Result<(), Chip8Error> {
+        self.write_memory(address, data)
+    }
+
+    /// Memory in `range`, cut short at the end of memory.
+    pub fn read_memory(&self, range: Range<usize>) -> &[u8] {
+        let end = range.end.min(self.memory.len());
+        &self.memory[range.start.min(end)..end]
+    }
+
+    /// Overwrite memory at `address`, e.g. to poke a cheat or patch code from a debugger. Fails
+    /// without writing anything if `data` runs past the end of memory.
+    pub fn write_memory(&mut self, address: usize, data: &[u8]) -> Result<(), Chip8Error> {
+        let end = address + data.len();
+        if end > self.memory.len() {
+            return Err(Chip8Error::RomTooLarge {
+                size: data.len(),
+                available: self.memory.len().saturating_sub(address),
+            });
+        }
+        self.memory[address..end].copy_from_slice(data);
+        Ok(())
+    }
+
+    /// Where the loaded ROM sits in memory.
+    pub fn rom_range(&self) -> Range<usize> {
+        self.load_address..self.load_address + strip_hp48_header(&self.rom).len()
+    }
+
+    /// Snapshot the whole machine, to be restored later with load_state().
+    pub fn save_state(&self) -> Vec<u8> {
+        let state = SavedState {
+            version: STATE_VERSION,
+            memory: StateBytes::pack(&self.memory),
+            V: self.V,
+            I: self.I,
+            pc: self.pc,
+            variant: self.variant,
+            quirks: self.quirks,
+            screen: StateBytes::pack(&self.screen),
+            hires: self.hires,
+            planes: self.planes,
+            halted: self.halted,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+            stack: self.stack,
+            sp: self.sp,
+            keys: self.keys,
+            waiting_for_key: self.waiting_for_key,
+            key_reg: self.key_reg,
+            rpl_flags: self.rpl_flags,
+            audio_pattern: self.audio_pattern,
+            pitch: self.pitch,
+            rng: self.rng,
+        };
+        serde_json::to_vec(&state).unwrap()
+    }
+
+    /// A fingerprint of the memory, registers, timers, stack and screen, for telling whether two
+    /// runs have reached the same state, e.g. when playing back a movie.
+    pub fn state_hash(&self) -> u64 {
+        let mut hasher = Sha1::new();
+        hasher.update(&self.memory);
+        hasher.update(self.V);
+        hasher.update((self.I as u64).to_le_bytes());
+        hasher.update((self.pc as u64).to_le_bytes());
+        hasher.update([self.delay_timer, self.sound_timer, self.sp as u8]);
+        for entry in self.stack {
+            hasher.update(entry.to_le_bytes());
+        }
+        hasher.update(self.screen);
+        let digest = hasher.finalize();
+        u64::from_le_bytes(digest[..8].try_into().unwrap())
+    }
+
+    /// Restore a snapshot taken by save_state(). The machine is left untouched if the snapshot
+    /// is damaged or from an incompatible version.
+    pub fn load_state(&mut self, data: &[u8]) -> std::io::Result<()> {
+        let invalid =
+            |message: String| std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+        let state: SavedState = serde_json::from_slice(data).map_err(|e| invalid(e.to_string()))?;
+        if !(OLDEST_STATE_VERSION..=STATE_VERSION).contains(&state.version) {
+            return Err(invalid(format!(
+                "save state version {} is not supported, expected {} to {}",
+                state.version, OLDEST_STATE_VERSION, STATE_VERSION
+            )));
+        }
+        let damaged = || invalid("save state has damaged memory or screen".to_string());
+        let memory = state.memory.unpack().ok_or_else(damaged)?;
+        let screen = state
+            .screen
+            .unpack()
+            .ok_or_else(damaged)?
+            .try_into()
+            .map_err(|_| invalid("save state has the wrong screen size".to_string()))?;
+        if ![0x1000, 0x10000].contains(&memory.len())
+            || state.pc >= memory.len()
+            || state.sp > state.stack.len()
+            || state.key_reg > 0xF
+            || state.planes > 3
+        {
+            return Err(invalid("save state is inconsistent".to_string()));
+        }
+
+        let was_beeping = self.is_beeping();
+        self.memory = memory;
+        self.V = state.V;
+        self.I = state.I;
+        self.pc = state.pc;
+        self.variant = state.variant;
+        self.quirks = state.quirks;
+        self.screen = screen;
+        self.hires = state.hires;
+        self.planes = state.planes;
+        self.halted = state.halted;
+        self.delay_timer = state.delay_timer;
+        self.sound_timer = state.sound_timer;
+        self.stack = state.stack;
+        self.sp = state.sp;
+        self.keys = state.keys;
+        self.waiting_for_key = state.waiting_for_key;
+        self.waiting_for_display = false;
+        self.key_reg = state.key_reg;
+        self.rpl_flags = state.rpl_flags;
+        self.audio_pattern = state.audio_pattern;
+        self.pitch = state.pitch;
+        self.rng = state.rng;
+        self.screen_updated();
+        self.sound_changed(was_beeping);
+        Ok(())
+    }
+
+    /// Count instructions in the table's cycles instead of the COSMAC VIP's, for clocks that
+    /// keep to another interpreter's timing.
+    pub fn set_cycle_table(&mut self, table: Option<Arc<CycleTable>>) {
+        self.cycle_table = table;
+    }
+
+    /// Choose the dialect to emulate. Takes effect on the next initialize().
+    pub fn set_variant(&mut self, variant: Variant) {
+        self.variant = variant;
+        self.quirks = Quirks::for_variant(variant);
+    }
+
+    /// Override the quirks that came with the variant. Call after set_variant().
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
+
+    /// Width of the display in its current resolution.
+    pub fn screen_width(&self) -> usize {
+        if self.hires {
+            128
+        } else {
+            64
+        }
+    }
+
+    pub fn screen_height(&self) -> usize {
+        self.screen_width() / 2
+    }
+
+    /// The display, row by row. Each pixel is a bit mask of the planes lit there, so 0 is unlit
+    /// and anything else is lit; only XO-CHIP ROMs use the second plane.
+    pub fn screen(&self) -> &[u8] {
+        &self.screen[..self.screen_width() * self.screen_height()]
+    }
+
+    /// Whether the screen has been drawn to, cleared, scrolled or resized since the last call,
+    /// so frontends can skip redrawing an unchanged picture.
+    pub fn take_screen_changed(&mut self) -> bool {
+        std::mem::take(&mut self.screen_changed)
+    }
+
+    /// Call `observer`'s hooks from now on, after any registered before it.
+    pub fn add_observer(&mut self, observer: Box<dyn Chip8Observer>) {
+        self.observers.0.push(observer);
+    }
+
+    // Call a hook on every observer, each given the machine as it is now.
+    fn notify(&mut self, hook: impl Fn(&mut dyn Chip8Observer, &Self)) {
+        if self.observers.0.is_empty() {
+            return;
+        }
+        // Taken out while they run, so that they can look at the rest of the machine.
+        let mut observers = std::mem::take(&mut self.observers.0);
+        for observer in &mut observers {
+            hook(observer.as_mut(), self);
+        }
+        self.observers.0 = observers;
+    }
+
+    fn screen_updated(&mut self) {
+        self.screen_changed = true;
+        self.notify(|observer, emu| observer.on_draw(emu));
+    }
+
+    fn update_sound_timer(&mut self, value: u8) {
+        let was_beeping = self.is_beeping();
+        self.sound_timer = value;
+        self.sound_changed(was_beeping);
+    }
+
+    // Tell the observers if the buzzer started or stopped.
+    fn sound_changed(&mut self, was_beeping: bool) {
+        match (was_beeping, self.is_beeping()) {
+            (false, true) => self.notify(|observer, emu| observer.on_sound_start(emu)),
+            (true, false) => self.notify(|observer, emu| observer.on_sound_stop(emu)),
+            _ => {}
+        }
+    }
+
+    // Tell the observers about `len` bytes an instruction stored from `address` on, wrapping
+    // around the end of memory.
+    fn memory_written(&mut self, address: usize, len: usize) {
+        if self.observers.0.is_empty() {
             return;
         }
+        for offset in 0..len {
+            let address = (address + offset) % self.memory.len();
+            let value = self.memory[address];
+            self.notify(|observer, emu| observer.on_memory_write(emu, address, value));
+        }
+    }
+
+    /// Whether the ROM has exited with the SUPER-CHIP exit instruction.
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    /// Set what memory outside the font and ROM holds. Takes effect on the next initialize().
+    pub fn set_memory_fill(&mut self, fill: MemoryFill) {
+        self.memory_fill = fill;
+    }
+
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn registers(&self) -> &[u8; 16] {
+        &self.V
+    }
+
+    pub fn i(&self) -> usize {
+        self.I
+    }
+
+    pub fn sp(&self) -> usize {
+        self.sp
+    }
+
+    /// Return addresses of the subroutines currently running, outermost first.
+    pub fn stack(&self) -> &[u16] {
+        &self.stack[..self.sp]
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    /// Set VX, e.g. from a debugger.
+    pub fn set_register(&mut self, x: usize, value: u8) {
+        self.V[x & 0xF] = value;
+    }
+
+    /// Set I, e.g. from a debugger.
+    pub fn set_index(&mut self, value: usize) {
+        self.I = value;
+    }
+
+    /// Continue execution from `address`, wrapped around the end of memory.
+    pub fn set_pc(&mut self, address: usize) {
+        self.pc = address & self.address_mask();
+    }
+
+    pub fn set_delay_timer(&mut self, value: u8) {
+        self.delay_timer = value;
+    }
+
+    pub fn set_sound_timer(&mut self, value: u8) {
+        self.update_sound_timer(value);
+    }
+
+    /// XO-CHIP's 128 1 bit samples, most significant bit first.
+    pub fn audio_pattern(&self) -> &[u8; 16] {
+        &self.audio_pattern
+    }
+
+    /// Rate the audio pattern is played back at, in samples per second.
+    pub fn audio_sample_rate(&self) -> f64 {
+        4000.0 * 2f64.powf((f64::from(self.pitch) - 64.0) / 48.0)
+    }
+
+    /// The audio pattern and its rate, for XO-CHIP ROMs that have loaded one, to be played
+    /// while the buzzer sounds in place of the usual tone.
+    pub fn sound_pattern(&self) -> Option<([u8; 16], f64)> {
+        // A silent pattern is what every machine starts with, not something a ROM would load.
+        let loaded = self.variant == Variant::XoChip && self.audio_pattern != [0; 16];
+        loaded.then(|| (self.audio_pattern, self.audio_sample_rate()))
+    }
+
+    /// Whether the buzzer should be sounding.
+    pub fn is_beeping(&self) -> bool {
+        self.sound_timer > 0
+    }
+
+    pub fn is_waiting_for_key(&self) -> bool {
+        self.waiting_for_key
+    }
+
+    /// Whether a draw is waiting for the display refresh, with the display wait quirk. Nothing
+    /// runs until the next `tick_timers()`, so frontends can end the frame early.
+    pub fn is_waiting_for_display(&self) -> bool {
+        self.waiting_for_display
+    }
+
+    /// The ROM last loaded, as it was given to load_rom_bytes().
+    pub fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    /// The whole address space, 4K or 64K for XO-CHIP, e.g. for tools that watch game variables.
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    /// Draw the screen into an RGBA frame, using the given colors for lit and unlit pixels.
+    /// XO-CHIP's second plane is drawn in Octo's default colors.
+    pub fn draw_screen(&self, screen: &mut [u8], foreground: [u8; 4], background: [u8; 4]) {
+        self.draw_screen_with_palette(screen, &Palette::new(foreground, background));
+    }
+
+    /// Draw the screen into an RGBA frame, with the palette's color for each combination of lit
+    /// planes.
+    pub fn draw_screen_with_palette(&self, screen: &mut [u8], palette: &Palette) {
+        debug_assert_eq!(screen.len(), 4 * self.screen().len());
+        let colors = palette.colors();
+        for (p, pix) in self.screen().iter().zip(screen.chunks_exact_mut(4)) {
+            pix.copy_from_slice(&colors[usize::from(*p & 3)]);
+        }
+    }
 
-        // Fetch and execute opcode
-        let opcode_value =
-            u16::from(self.memory[self.pc]) << 8 | u16::from(self.memory[self.pc + 1]);
+    /// The big-endian word at `address`, wrapping around the end of memory.
+    fn word_at(&self, address: usize) -> u16 {
+        let mask = self.address_mask();
+        u16::from(self.memory[address & mask]) << 8 | u16::from(self.memory[(address + 1) & mask])
+    }
+
+    /// Memory is 4K or 64K, so addresses wrap around its end with a mask rather than a
+    /// division, which matters on the path every instruction takes.
+    fn address_mask(&self) -> usize {
+        self.memory.len() - 1
+    }
+
+    /// Move the program counter on by `bytes`, wrapping around the end of memory like every
+    /// other address.
+    fn advance(&mut self, bytes: usize) {
+        self.pc = (self.pc + bytes) & self.address_mask();
+    }
 
-        self.handle_opcode(opcode_value);
+    /// Skip the next instruction, which is 4 bytes long if it is XO-CHIP's `i := long NNNN`.
+    fn skip_next(&mut self) {
+        if self.variant == Variant::XoChip && self.word_at(self.pc + 2) == 0xF000 {
+            self.advance(6);
+        } else {
+            self.advance(4);
+        }
+    }
+
+    /// Faults an instruction would cause, checked before it runs so that it either completes
+    /// or leaves the machine untouched.
+    fn check(&self, op: Opcode) -> Result<(), Chip8Error> {
+        let pc = self.pc;
+        // Instructions that access `len` bytes from I.
+        let memory_from_i = |len: usize| {
+            let end = self.I + len;
+            if end > self.memory.len() {
+                Err(Chip8Error::MemoryOutOfBounds {
+                    address: end - 1,
+                    pc,
+                })
+            } else {
+                Ok(())
+            }
+        };
+        match op {
+            Opcode::CallSub(_) if self.sp >= self.stack.len() => {
+                Err(Chip8Error::StackOverflow { pc })
+            }
+            Opcode::ReturnFromSub if self.sp == 0 => Err(Chip8Error::StackUnderflow { pc }),
+            Opcode::BCD(_) => memory_from_i(3),
+            Opcode::RegDump(x) | Opcode::RegLoad(x) => memory_from_i(usize::from(x) + 1),
+            _ => Ok(()),
+        }
+    }
+
+    /// Carry out a single decoded instruction.
+    pub fn execute(&mut self, op: Opcode) -> Result<(), Chip8Error> {
+        self.check(op)?;
+        match op {
+            Opcode::MachineCode(address) => self.machine_code_routine(address),
+            Opcode::ClearScreen => self.clear_screen(),
+            Opcode::ReturnFromSub => self.return_subroutine(),
+            Opcode::Goto(address) => self.goto(address),
+            Opcode::CallSub(address) => self.call_subroutine(address),
+            Opcode::SkipEQ(x, c) => self.skip_const_eq(x, c),
+            Opcode::SkipNEQ(x, c) => self.skip_const_neq(x, c),
+            Opcode::SkipRegEQ(x, y) => self.skip_reg_eq(x, y),
+            Opcode::SetConst(x, c) => self.set_const(x, c),
+            Opcode::AddConst(x, c) => self.add_const(x, c),
+            Opcode::SetReg(x, y) => self.set(x, y),
+            Opcode::Or(x, y) => self.or(x, y),
+            Opcode::And(x, y) => self.and(x, y),
+            Opcode::Xor(x, y) => self.xor(x, y),
+            Opcode::AddReg(x, y) => self.add(x, y),
+            Opcode::SubReg(x, y) => self.sub(x, y),
+            Opcode::Div2(x, y) => self.div_2(x, y),
+            Opcode::DiffReg(x, y) => self.diff(x, y),
+            Opcode::Mul2(x, y) => self.mul_2(x, y),
+            Opcode::SkipRegNEQ(x, y) => self.skip_reg_neq(x, y),
+            Opcode::SetAR(address) => self.set_i(address),
+            Opcode::Jump(address) => self.jump_offset(address),
+            Opcode::Rand(x, c) => self.rand(x, c),
+            Opcode::Draw(x, y, c) => self.draw(x, y, c),
+            Opcode::KeyEQ(x) => self.skip_if_key(x),
+            Opcode::KeyNEQ(x) => self.skip_if_nkey(x),
+            Opcode::GetDelayTimer(x) => self.get_delay(x),
+            Opcode::GetKey(x) => self.get_key(x),
+            Opcode::SetDelayTimer(x) => self.set_delay(x),
+            Opcode::SetSoundTimer(x) => self.set_sound(x),
+            Opcode::AddToI(x) => self.inc_i(x),
+            Opcode::SetISprite(x) => self.set_i_sprite(x),
+            Opcode::BCD(x) => self.bcd(x),
+            Opcode::RegDump(x) => self.reg_dump(x),
+            Opcode::RegLoad(x) => self.reg_load(x),
+            Opcode::ScrollDown(n) => self.scroll(0, isize::from(n)),
+            Opcode::ScrollRight => self.scroll(4, 0),
+            Opcode::ScrollLeft => self.scroll(-4, 0),
+            Opcode::Exit => self.exit(),
+            Opcode::LowRes => self.set_hires(false),
+            Opcode::HighRes => self.set_hires(true),
+            Opcode::SetIBigSprite(x) => self.set_i_big_sprite(x),
+            Opcode::SaveFlags(x) => self.save_flags(x),
+            Opcode::LoadFlags(x) => self.load_flags(x),
+            Opcode::ScrollUp(n) => self.scroll(0, -isize::from(n)),
+            Opcode::SaveRange(x, y) => self.save_range(x, y),
+            Opcode::LoadRange(x, y) => self.load_range(x, y),
+            Opcode::SetILong => self.set_i_long(),
+            Opcode::SelectPlanes(n) => self.select_planes(n),
+            Opcode::LoadAudio => self.load_audio(),
+            Opcode::SetPitch(x) => self.set_pitch(x),
+        }
+        Ok(())
+    }
+
+    /// Run the next instruction. On a fault, nothing is changed and the same fault happens
+    /// again if it is retried.
+    pub fn emulate_cycle(&mut self) -> Result<(), Chip8Error> {
+        // Don't do anything if halted or waiting
+        if self.blocked().is_some() {
+            return Ok(());
+        }
+
+        // Fetch, decode and execute opcode
+        let opcode_value = self.word_at(self.pc);
+        match opcode::from_value(opcode_value) {
+            Ok(op) if op.variant() <= self.variant => {
+                trace!(
+                    "{:#05X}: {:04X}  {:<20} V={:02X?} I={:#05X} sp={}",
+                    self.pc,
+                    opcode_value,
+                    op,
+                    self.V,
+                    self.I,
+                    self.sp
+                );
+                self.execute(op)?;
+                self.executed += 1;
+                self.cycles += u64::from(match &self.cycle_table {
+                    Some(table) => table.cycles(opcode_value),
+                    None => op.vip_cycles(),
+                });
+                Ok(())
+            }
+            Ok(op) => {
+                self.notify(|observer, emu| observer.on_unknown_opcode(emu, opcode_value));
+                Err(Chip8Error::UnsupportedOpcode {
+                    opcode: opcode_value,
+                    pc: self.pc,
+                    variant: op.variant(),
+                })
+            }
+            Err(e) => {
+                self.notify(|observer, emu| observer.on_unknown_opcode(emu, opcode_value));
+                Err(Chip8Error::InvalidOpcode {
+                    opcode: e.0,
+                    pc: self.pc,
+                })
+            }
+        }
+    }
+
+    /// Run up to `instructions` instructions, stopping early if the machine has to wait for a
+    /// key or the display, or the ROM exits.
+    pub fn run_for(&mut self, instructions: u32) -> Result<StepOutcome, Chip8Error> {
+        self.run(Some(instructions), |_| false)
+    }
+
+    /// Run until `stop` holds before an instruction, or the machine has to wait for a key or
+    /// the display, or the ROM exits.
+    pub fn run_until(
+        &mut self,
+        stop: impl FnMut(&Self) -> bool,
+    ) -> Result<StepOutcome, Chip8Error> {
+        self.run(None, stop)
+    }
 
-        // Update timers
+    fn run(
+        &mut self,
+        limit: Option<u32>,
+        mut stop: impl FnMut(&Self) -> bool,
+    ) -> Result<StepOutcome, Chip8Error> {
+        let mut executed = 0;
+        loop {
+            if limit.is_some_and(|limit| executed >= limit) {
+                return Ok(StepOutcome::Completed);
+            }
+            if let Some(outcome) = self.blocked() {
+                return Ok(outcome);
+            }
+            if stop(self) {
+                return Ok(StepOutcome::HitBreakpoint);
+            }
+            self.emulate_cycle()?;
+            executed += 1;
+        }
+    }
+
+    /// Why the next instruction can't run yet, if it can't.
+    fn blocked(&mut self) -> Option<StepOutcome> {
+        if self.halted {
+            Some(StepOutcome::Halted)
+        } else if self.waiting_for_key {
+            // FX0A accepts any key, so the ROM is effectively polling all of them.
+            self.polled_keys = [true; 16];
+            Some(StepOutcome::WaitingForKey)
+        } else if self.waiting_for_display {
+            Some(StepOutcome::DisplayUpdated)
+        } else {
+            None
+        }
+    }
+
+    /// Instructions run since the machine was created, not counting time spent waiting.
+    pub fn executed(&self) -> u64 {
+        self.executed
+    }
+
+    /// How long the instructions run since the machine was created would have taken on a
+    /// COSMAC VIP, in machine cycles, for clocks that keep to its timing. With a cycle table,
+    /// the cycles are the table's instead.
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Count the delay and sound timers down. Call this at 60 Hz, independently of how many
+    /// instructions are executed.
+    pub fn tick_timers(&mut self) {
+        // Timer ticks and display refreshes both come from the VIP's vertical blank.
+        self.waiting_for_display = false;
         if self.delay_timer > 0 {
             self.delay_timer -= 1;
         }
         if self.sound_timer > 0 {
             self.sound_timer -= 1;
-            if self.sound_timer != 0 {
-                println!("BEEP");
-            }
+            self.sound_changed(true);
         }
     }
 
@@ -222,34 +1159,84 @@ impl Chip8Emulator {
         self.keys.copy_from_slice(keys);
     }
 
+    pub fn keys(&self) -> &[bool; 16] {
+        &self.keys
+    }
+
+    /// Return which keys the ROM has polled since the last call, and clear them.
+    pub fn take_polled_keys(&mut self) -> [bool; 16] {
+        std::mem::take(&mut self.polled_keys)
+    }
+
     fn wait_for_keypress(&mut self, reg: usize) {
         self.waiting_for_key = true;
         self.key_reg = reg;
+        self.notify(|observer, emu| observer.on_key_wait(emu));
     }
 
     fn handle_keypress(&mut self, key: u8) {
         if self.waiting_for_key {
             self.waiting_for_key = false;
             self.V[self.key_reg] = key;
-            self.pc += 2;
+            self.advance(2);
         }
     }
 
     fn clear_screen(&mut self) {
         for b in self.screen.as_mut_slice().iter_mut() {
-            *b = false;
+            *b &= !self.planes;
         }
-        self.pc += 2;
+        self.advance(2);
+        self.screen_updated();
+    }
+
+    /// Move the selected planes right by `dx` and down by `dy` pixels.
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        let width = self.screen_width() as isize;
+        let height = self.screen_height() as isize;
+        let old = self.screen;
+        for y in 0..height {
+            for x in 0..width {
+                let (from_x, from_y) = (x - dx, y - dy);
+                let moved = if (0..width).contains(&from_x) && (0..height).contains(&from_y) {
+                    old[(from_y * width + from_x) as usize]
+                } else {
+                    0
+                };
+                let idx = (y * width + x) as usize;
+                self.screen[idx] = (old[idx] & !self.planes) | (moved & self.planes);
+            }
+        }
+        self.advance(2);
+        self.screen_updated();
+    }
+
+    fn exit(&mut self) {
+        self.halted = true;
+    }
+
+    fn set_hires(&mut self, hires: bool) {
+        // The old contents would be laid out for the wrong row width.
+        self.hires = hires;
+        self.screen = [0; 128 * 64];
+        self.advance(2);
+        self.screen_updated();
     }
 
     fn return_subroutine(&mut self) {
         self.sp -= 1;
         self.pc = self.stack[self.sp].into();
-        self.pc += 2;
+        self.advance(2);
     }
 
+    /// Calls into COSMAC VIP machine code can't be emulated, and are mostly left over in ROMs
+    /// that never reach them, so they are skipped.
     fn machine_code_routine(&mut self, address: u16) {
-        todo!()
+        warn!(
+            "skipping machine code routine at {:#05X} (pc: {:#X})",
+            address, self.pc
+        );
+        self.advance(2);
     }
 
     fn goto(&mut self, address: u16) {
@@ -257,65 +1244,84 @@ impl Chip8Emulator {
     }
 
     fn call_subroutine(&mut self, address: u16) {
-        self.stack[self.sp] = self.pc.try_into().unwrap();
+        // The program counter stays within memory, which is at most 64K.
+        self.stack[self.sp] = self.pc as u16;
         self.sp += 1;
         self.pc = address.into();
     }
 
     fn skip_const_eq(&mut self, reg: u8, c: u8) {
         if self.V[usize::from(reg)] == c {
-            self.pc += 4;
+            self.skip_next();
         } else {
-            self.pc += 2;
+            self.advance(2);
         }
     }
 
     fn skip_const_neq(&mut self, reg: u8, c: u8) {
         if self.V[usize::from(reg)] != c {
-            self.pc += 4;
+            self.skip_next();
         } else {
-            self.pc += 2;
+            self.advance(2);
         }
     }
 
     fn skip_reg_eq(&mut self, reg: u8, reg2: u8) {
-        todo!()
+        let reg = usize::from(reg);
+        let reg2 = usize::from(reg2);
+        if self.V[reg] == self.V[reg2] {
+            self.skip_next();
+        } else {
+            self.advance(2);
+        }
     }
 
     fn set_const(&mut self, reg: u8, c: u8) {
         self.V[usize::from(reg)] = c;
-        self.pc += 2;
+        self.advance(2);
     }
 
     fn add_const(&mut self, reg: u8, c: u8) {
         let reg = usize::from(reg);
         self.V[reg] = self.V[reg].overflowing_add(c).0;
-        self.pc += 2;
+        self.advance(2);
     }
 
     fn set(&mut self, reg: u8, reg2: u8) {
         let reg = usize::from(reg);
         let reg2 = usize::from(reg2);
         self.V[reg] = self.V[reg2];
-        self.pc += 2;
+        self.advance(2);
     }
 
     fn or(&mut self, reg: u8, reg2: u8) {
         let reg = usize::from(reg);
         let reg2 = usize::from(reg2);
         self.V[reg] |= self.V[reg2];
-        self.pc += 2;
+        if self.quirks.vf_reset {
+            self.V[0xF] = 0;
+        }
+        self.advance(2);
     }
 
     fn and(&mut self, reg: u8, reg2: u8) {
         let reg = usize::from(reg);
         let reg2 = usize::from(reg2);
         self.V[reg] &= self.V[reg2];
-        self.pc += 2;
+        if self.quirks.vf_reset {
+            self.V[0xF] = 0;
+        }
+        self.advance(2);
     }
 
     fn xor(&mut self, reg: u8, reg2: u8) {
-        todo!()
+        let reg = usize::from(reg);
+        let reg2 = usize::from(reg2);
+        self.V[reg] ^= self.V[reg2];
+        if self.quirks.vf_reset {
+            self.V[0xF] = 0;
+        }
+        self.advance(2);
     }
 
     fn add(&mut self, reg: u8, reg2: u8) {
@@ -331,7 +1337,7 @@ impl Chip8Emulator {
                 self.V[0xF] = 1;
             }
         }
-        self.pc += 2;
+        self.advance(2);
     }
 
     fn sub(&mut self, reg: u8, reg2: u8) {
@@ -347,82 +1353,148 @@ impl Chip8Emulator {
                 self.V[0xF] = 0;
             }
         }
-        self.pc += 2;
+        self.advance(2);
     }
 
-    fn div_2(&mut self, reg: u8, _reg2: u8) {
-        todo!()
+    /// The value 8XY6 and 8XYE shift, which depends on the shift quirk.
+    fn shift_source(&self, reg: usize, reg2: usize) -> u8 {
+        if self.quirks.shift_vy {
+            self.V[reg2]
+        } else {
+            self.V[reg]
+        }
+    }
+
+    fn div_2(&mut self, reg: u8, reg2: u8) {
+        let reg = usize::from(reg);
+        let reg2 = usize::from(reg2);
+        let value = self.shift_source(reg, reg2);
+        self.V[reg] = value >> 1;
+        self.V[0xF] = value & 1;
+        self.advance(2);
     }
 
     fn diff(&mut self, reg: u8, reg2: u8) {
-        todo!()
+        let reg = usize::from(reg);
+        let reg2 = usize::from(reg2);
+        let (val, borrow) = self.V[reg2].overflowing_sub(self.V[reg]);
+        self.V[reg] = val;
+        self.V[0xF] = u8::from(!borrow);
+        self.advance(2);
     }
 
-    fn mul_2(&mut self, reg: u8, _reg2: u8) {
-        todo!()
+    fn mul_2(&mut self, reg: u8, reg2: u8) {
+        let reg = usize::from(reg);
+        let reg2 = usize::from(reg2);
+        let value = self.shift_source(reg, reg2);
+        self.V[reg] = value << 1;
+        self.V[0xF] = value >> 7;
+        self.advance(2);
     }
 
     fn skip_reg_neq(&mut self, reg: u8, reg2: u8) {
         let reg = usize::from(reg);
         let reg2 = usize::from(reg2);
         if self.V[reg] != self.V[reg2] {
-            self.pc += 4;
+            self.skip_next();
         } else {
-            self.pc += 2;
+            self.advance(2);
         }
     }
 
     fn set_i(&mut self, address: u16) {
         self.I = address.into();
-        self.pc += 2;
+        self.advance(2);
     }
 
     fn jump_offset(&mut self, address: u16) {
-        todo!()
+        let reg = if self.quirks.jump_vx {
+            usize::from(address >> 8)
+        } else {
+            0
+        };
+        self.pc = (usize::from(address) + usize::from(self.V[reg])) & self.address_mask();
     }
 
     fn rand(&mut self, reg: u8, c: u8) {
-        self.V[usize::from(reg)] = rand::random::<u8>() & c;
-        self.pc += 2;
+        self.V[usize::from(reg)] = self.rng.next() as u8 & c;
+        self.advance(2);
     }
 
     fn draw(&mut self, x: u8, y: u8, height: u8) {
-        let x = usize::from(self.V[usize::from(x)]);
-        let y = usize::from(self.V[usize::from(y)]);
-        let height = usize::from(height);
+        let width = self.screen_width();
+        let screen_height = self.screen_height();
+        // Sprites start at a position wrapped onto the screen. Depending on the clip quirk,
+        // they keep wrapping around the edges or are cut off there.
+        let wrap = !self.quirks.clip_sprites;
+        let x = usize::from(self.V[usize::from(x)]) % width;
+        let y = usize::from(self.V[usize::from(y)]) % screen_height;
+        // SUPER-CHIP draws a 16x16 sprite, 2 bytes per row, when the height is 0.
+        let (height, row_bytes) = if height == 0 && self.variant >= Variant::SuperChip {
+            (16, 2)
+        } else {
+            (usize::from(height), 1)
+        };
 
         self.V[0xF] = 0;
-        for yline in 0..height {
-            let pixel = self.memory[self.I + yline];
-            for xline in 0..8 {
-                if (pixel & (0x80 >> xline)) != 0 {
-                    let idx = x + xline + ((y + yline) * 64);
-                    if self.screen[idx] {
-                        self.V[0xF] = 1;
+        let mask = self.address_mask();
+        // With both XO-CHIP planes selected, the sprite for the second follows the first.
+        let mut sprite = self.I;
+        for plane in [1, 2] {
+            if self.planes & plane == 0 {
+                continue;
+            }
+            for yline in 0..height {
+                let py = y + yline;
+                if py >= screen_height && !wrap {
+                    break;
+                }
+                for byte in 0..row_bytes {
+                    let pixel = self.memory[(sprite + yline * row_bytes + byte) & mask];
+                    for xline in 0..8 {
+                        let px = x + byte * 8 + xline;
+                        if (pixel & (0x80 >> xline)) != 0 && (px < width || wrap) {
+                            let idx = (px % width) + ((py % screen_height) * width);
+                            if self.screen[idx] & plane != 0 {
+                                self.V[0xF] = 1;
+                            }
+                            self.screen[idx] ^= plane;
+                        }
                     }
-                    self.screen[idx] ^= true;
                 }
             }
+            sprite += height * row_bytes;
         }
 
-        self.pc += 2;
+        self.waiting_for_display = self.quirks.display_wait;
+        self.advance(2);
+        self.screen_updated();
     }
 
     fn skip_if_key(&mut self, reg: u8) {
-        todo!()
+        // The VIP's keypad latch only took the low nibble.
+        let key = usize::from(self.V[usize::from(reg)] & 0xF);
+        self.polled_keys[key] = true;
+        if self.keys[key] {
+            self.skip_next();
+        } else {
+            self.advance(2);
+        }
     }
 
     fn skip_if_nkey(&mut self, reg: u8) {
-        if !self.keys[usize::from(self.V[usize::from(reg)])] {
-            self.pc += 4;
+        let key = usize::from(self.V[usize::from(reg)] & 0xF);
+        self.polled_keys[key] = true;
+        if !self.keys[key] {
+            self.skip_next();
         } else {
-            self.pc += 2;
+            self.advance(2);
         }
     }
 
     fn get_delay(&mut self, reg: u8) {
         self.V[usize::from(reg)] = self.delay_timer;
-        self.pc += 2;
+        self.advance(2);
     }
 
     fn get_key(&mut self, reg: u8) {
@@ -433,23 +1505,30 @@ impl Chip8Emulator {
     fn set_delay(&mut self, reg: u8) {
         let reg = usize::from(reg);
         self.delay_timer = self.V[reg];
-        self.pc += 2;
+        self.advance(2);
     }
 
     fn set_sound(&mut self, reg: u8) {
         let reg = usize::from(reg);
-        self.sound_timer = self.V[reg];
-        self.pc += 2;
+        self.advance(2);
+        self.update_sound_timer(self.V[reg]);
     }
 
     fn inc_i(&mut self, reg: u8) {
-        todo!()
+        self.I += usize::from(self.V[usize::from(reg)]);
+        self.advance(2);
     }
 
     fn set_i_sprite(&mut self, reg: u8) {
         let reg = usize::from(reg);
-        self.I = (self.V[reg] * 5).into();
-        self.pc += 2;
+        self.I = usize::from(self.V[reg] & 0xF) * 5;
+        self.advance(2);
+    }
+
+    fn set_i_big_sprite(&mut self, reg: u8) {
+        let reg = usize::from(reg);
+        self.I = BIG_FONT_ADDRESS + usize::from(self.V[reg]) * 10;
+        self.advance(2);
     }
 
     fn bcd(&mut self, reg: u8) {
@@ -457,11 +1536,21 @@ impl Chip8Emulator {
         self.memory[self.I] = self.V[reg] / 100;
         self.memory[self.I + 1] = (self.V[reg] / 10) % 10;
         self.memory[self.I + 2] = (self.V[reg] % 100) % 10;
-        self.pc += 2;
+        self.advance(2);
+        self.memory_written(self.I, 3);
     }
 
     fn reg_dump(&mut self, reg: u8) {
-        todo!()
+        let reg = usize::from(reg);
+        let address = self.I;
+        for i in 0..=reg {
+            self.memory[address + i] = self.V[i];
+        }
+        if self.quirks.increment_i {
+            self.I += reg + 1;
+        }
+        self.advance(2);
+        self.memory_written(address, reg + 1);
     }
 
     fn reg_load(&mut self, reg: u8) {
@@ -469,6 +1558,66 @@ impl Chip8Emulator {
         for i in 0..=reg {
             self.V[i] = self.memory[self.I + i];
         }
-        self.pc += 2;
+        if self.quirks.increment_i {
+            self.I += reg + 1;
+        }
+        self.advance(2);
+    }
+
+    /// Registers X to Y inclusive, in that order even when Y is lower.
+    fn register_range(x: u8, y: u8) -> impl Iterator<Item = usize> {
+        let (x, y) = (usize::from(x), usize::from(y));
+        (0..=x.abs_diff(y)).map(move |i| if x <= y { x + i } else { x - i })
+    }
+
+    fn save_range(&mut self, x: u8, y: u8) {
+        let len = self.memory.len();
+        for (offset, reg) in Self::register_range(x, y).enumerate() {
+            self.memory[(self.I + offset) % len] = self.V[reg];
+        }
+        self.advance(2);
+        self.memory_written(self.I, usize::from(x.abs_diff(y)) + 1);
+    }
+
+    fn load_range(&mut self, x: u8, y: u8) {
+        let len = self.memory.len();
+        for (offset, reg) in Self::register_range(x, y).enumerate() {
+            self.V[reg] = self.memory[(self.I + offset) % len];
+        }
+        self.advance(2);
+    }
+
+    fn set_i_long(&mut self) {
+        self.I = usize::from(self.word_at(self.pc + 2));
+        self.advance(4);
+    }
+
+    fn select_planes(&mut self, planes: u8) {
+        self.planes = planes & 3;
+        self.advance(2);
+    }
+
+    fn load_audio(&mut self) {
+        for (offset, sample) in self.audio_pattern.iter_mut().enumerate() {
+            *sample = self.memory[(self.I + offset) % self.memory.len()];
+        }
+        self.advance(2);
+    }
+
+    fn set_pitch(&mut self, reg: u8) {
+        self.pitch = self.V[usize::from(reg)];
+        self.advance(2);
+    }
+
+    fn save_flags(&mut self, reg: u8) {
+        let count = usize::from(reg).min(7) + 1;
+        self.rpl_flags[..count].copy_from_slice(&self.V[..count]);
+        self.advance(2);
+    }
+
+    fn load_flags(&mut self, reg: u8) {
+        let count = usize::from(reg).min(7) + 1;
+        self.V[..count].copy_from_slice(&self.rpl_flags[..count]);
+        self.advance(2);
     }
 }
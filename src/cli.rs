@@ -0,0 +1,384 @@
+use clap::{Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use chip8emu::chip8::{MemoryFill, Variant};
+use chip8emu::clock::{Clock, CycleTable};
+use chip8emu::debugger::Watchpoint;
+use chip8emu::palette::Palette;
+use chip8emu::quirks::Quirks;
+
+use crate::display::DisplayOptions;
+use crate::keymap::KeyMap;
+
+/// A CHIP-8, SUPER-CHIP and XO-CHIP emulator.
+///
+/// Runs the ROM in a window when given one without a subcommand.
+#[derive(Parser)]
+#[command(version, args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    #[command(flatten)]
+    pub run: Option<Options>,
+}
+
+impl Cli {
+    /// Parse the command line, exiting with usage on errors. Clap only fills in a flattened
+    /// `Option` through its argument group, which stays empty for `Options` because it nests
+    /// `DisplayOptions`, so `run` is read from the matches here instead.
+    pub fn parse_args() -> Self {
+        let matches = Self::command().get_matches();
+        let mut cli = Self::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+        if cli.command.is_none() {
+            cli.run = Some(Options::from_arg_matches(&matches).unwrap_or_else(|e| e.exit()));
+        }
+        cli
+    }
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Run a ROM in a window.
+    Run(Box<Options>),
+    /// Print an annotated listing of a ROM.
+    Disasm { rom: String },
+    /// Assemble a source file in the disassembler's syntax into a ROM.
+    Assemble {
+        source: PathBuf,
+        /// Where to write the ROM, the source path with a `.ch8` extension by default.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Summarize the platform, keys and sprite data a ROM uses.
+    Inspect { rom: String },
+    /// Print the ROM's static call graph in Graphviz dot format.
+    Callgraph { rom: String },
+    /// Run ROMs for external frontends connecting over TCP.
+    Serve {
+        rom: String,
+        #[arg(default_value = "127.0.0.1:48769")]
+        address: String,
+    },
+    /// Print a hex dump of memory with a ROM loaded.
+    Dump {
+        rom: String,
+        /// Only the part of memory the ROM was loaded into.
+        #[arg(long)]
+        rom_only: bool,
+        /// How many 60 Hz frames to run before dumping.
+        #[arg(long, default_value_t = 0)]
+        frames: u32,
+    },
+    /// Run a ROM without a window and print the screen it ends up showing.
+    Test {
+        rom: String,
+        /// How many 60 Hz frames to run for.
+        #[arg(long, default_value_t = 600)]
+        frames: u32,
+        /// The dialect to emulate, guessed from the ROM by default.
+        #[arg(long)]
+        variant: Option<Variant>,
+        /// The quirks of a dialect (chip8, schip or xochip), those of the variant by default.
+        #[arg(long)]
+        quirks: Option<Quirks>,
+    },
+    /// Run the ROMs listed in a TOML manifest without a window, and check the screens they end
+    /// up showing against golden images or hashes.
+    TestRoms {
+        manifest: PathBuf,
+        /// Write the golden images from the screens instead of checking them.
+        #[arg(long)]
+        update: bool,
+    },
+}
+
+/// Options for running a ROM in a window.
+#[derive(Args)]
+pub struct Options {
+    /// The ROM to run, or a playlist of ROMs with --kiosk. Without one, a launcher lists the
+    /// recently played ROMs and those in the config's `rom_dir`.
+    #[arg(value_name = "ROM")]
+    pub rom_path: Option<String>,
+    /// Initial window size, in screen pixels per CHIP-8 pixel, 10 by default.
+    #[arg(long)]
+    pub scale: Option<u32>,
+    /// Start in a borderless fullscreen window.
+    #[arg(long)]
+    pub fullscreen: bool,
+    /// Colors instead of the ROM's or the config's: a theme (white, green, amber, high-contrast
+    /// or octo), or colors like `#FFCC00,#996600`, optionally followed by two more for XO-CHIP's
+    /// second plane and both planes.
+    #[arg(long)]
+    pub palette: Option<Palette>,
+    /// Run without a window for --cycles instructions, then print the registers and a hash of
+    /// the screen as JSON. The config file isn't read, so runs are reproducible.
+    #[arg(long, requires = "rom_path")]
+    pub headless: bool,
+    /// How many instructions to execute with --headless.
+    #[arg(long, default_value_t = 1_000_000, requires = "headless")]
+    pub cycles: u64,
+    /// With --headless, also write the final screen here: a PNG for `.png` paths, otherwise a
+    /// plain PBM.
+    #[arg(long, value_name = "PATH", requires = "headless")]
+    pub screenshot: Option<PathBuf>,
+    /// Where to show the screen: `winit` for a window, or `tui` to draw it in the terminal,
+    /// which needs the `tui` feature. The terminal doesn't read the config file.
+    #[arg(long, value_enum, default_value_t = Frontend::Winit, requires_if("tui", "rom_path"))]
+    pub frontend: Frontend,
+    /// Run this many million instructions without a window, as fast as possible, then print
+    /// how many ran per second.
+    #[arg(
+        long,
+        value_name = "MILLIONS",
+        requires = "rom_path",
+        conflicts_with = "headless"
+    )]
+    pub bench: Option<u64>,
+    /// Log every executed instruction with the registers before it runs.
+    #[arg(long)]
+    pub trace: bool,
+    /// Record the calls made while running and write the call graph here on exit.
+    #[arg(long = "callgraph", value_name = "PATH", requires = "rom_path")]
+    pub callgraph_path: Option<String>,
+    /// Record an execution timeline and write it here on exit as a speedscope profile.
+    #[arg(long = "profile", value_name = "PATH", requires = "rom_path")]
+    pub profile_path: Option<String>,
+    /// Write every executed instruction here as JSON Lines.
+    #[arg(long = "trace-json", value_name = "PATH")]
+    pub trace_json_path: Option<String>,
+    /// Open the ROM in an already running instance instead of starting a new one, or become
+    /// that instance if none is running. They find each other through a socket in the user's
+    /// runtime directory, so this only works on Unix.
+    #[arg(long)]
+    pub single_instance: bool,
+    /// Treat the path as a playlist of ROMs and cycle through them.
+    #[arg(long, requires = "rom_path")]
+    pub kiosk: bool,
+    /// How long each ROM is shown in kiosk mode, in seconds.
+    #[arg(long, default_value_t = 60)]
+    pub kiosk_seconds: u64,
+    /// Seconds without input before kiosk mode resumes cycling.
+    #[arg(long, default_value_t = 30)]
+    pub kiosk_idle: u64,
+    /// Instructions executed per second.
+    #[arg(long, conflicts_with = "ipf")]
+    pub clock_hz: Option<u32>,
+    /// Instructions executed per 60 Hz frame.
+    #[arg(long)]
+    pub ipf: Option<u32>,
+    /// Give each instruction as long as it took on the COSMAC VIP, instead of running them at
+    /// a fixed rate, for ROMs that depend on its timing.
+    #[arg(long, conflicts_with_all = ["clock_hz", "ipf"])]
+    pub vip_timing: bool,
+    /// Like --vip-timing, but with the cost of each instruction, and the cycles in a frame, from
+    /// a TOML file, to run at the pace of another interpreter.
+    #[arg(
+        long,
+        value_name = "PATH",
+        value_parser = load_cycle_table,
+        conflicts_with_all = ["clock_hz", "ipf", "vip_timing"]
+    )]
+    pub cycle_table: Option<Arc<CycleTable>>,
+    /// The dialect to emulate (chip8, schip or xochip), guessed from the ROM by default.
+    #[arg(long)]
+    pub variant: Option<Variant>,
+    /// The quirks of a dialect (chip8, schip or xochip), those of the variant by default.
+    #[arg(long)]
+    pub quirks: Option<Quirks>,
+    /// Turn a single quirk on or off, e.g. `clip=off`. Can be repeated.
+    #[arg(long = "quirk", value_name = "NAME=on|off", value_parser = parse_quirk_toggle)]
+    pub quirk_toggles: Vec<(String, bool)>,
+    /// Also run a second machine with other quirks, drawn to the right of the first, and pause
+    /// at the first frame where the two differ. Either a preset (chip8, schip or xochip) or a
+    /// change like `clip=off` to the first machine's quirks. Can be repeated.
+    #[arg(long, value_name = "QUIRKS", value_parser = parse_quirk_change)]
+    pub compare: Vec<QuirkChange>,
+    /// Buzzer volume in percent, 25 by default.
+    #[arg(long)]
+    pub volume: Option<u8>,
+    /// No buzzer sound.
+    #[arg(long)]
+    pub mute: bool,
+    /// Buzzer pitch in Hz, 440 by default.
+    #[arg(long)]
+    pub tone: Option<f32>,
+    /// Shape of the buzzer's tone, square by default.
+    #[arg(long, value_enum)]
+    pub waveform: Option<Waveform>,
+    /// What memory outside the ROM holds at start: a byte like `0xFF`, `random`, or
+    /// `random:<seed>`.
+    #[arg(long, default_value = "0")]
+    pub memory_fill: MemoryFill,
+    /// Record the keys held each frame to a movie file, written on exit.
+    #[arg(long, value_name = "PATH", conflicts_with = "playback")]
+    pub record: Option<PathBuf>,
+    /// Play back a movie file recorded with --record instead of taking input. With --headless,
+    /// run for the length of the movie instead of --cycles.
+    #[arg(long, value_name = "PATH")]
+    pub playback: Option<PathBuf>,
+    /// Open the --playback movie paused at its first frame with the debugger showing, to step
+    /// or scrub through it with the Movie window's timeline.
+    #[arg(long, requires = "playback")]
+    pub view: bool,
+    /// Address the ROM is loaded at and starts running from, like `0x600` for ETI-660 programs.
+    #[arg(long, value_name = "ADDRESS", default_value = "0x200", value_parser = parse_address)]
+    pub load_addr: usize,
+    /// Seed for the random numbers ROMs draw, so that runs can be reproduced. Random by default.
+    #[arg(long)]
+    pub seed: Option<u64>,
+    /// A shared library to load as an input or output plugin. Can be repeated.
+    #[arg(long = "plugin", value_name = "PATH")]
+    pub plugins: Vec<String>,
+    /// A Rhai script to run alongside the ROM, with callbacks on every frame, at addresses and
+    /// on memory writes. Needs the `scripting` feature.
+    #[arg(long, value_name = "PATH")]
+    pub script: Option<String>,
+    #[command(flatten)]
+    pub display: DisplayOptions,
+    /// Also load a file at an address, like `font.bin@0x50`. Can be repeated.
+    #[arg(long = "load", value_name = "FILE@ADDRESS", value_parser = parse_segment)]
+    pub segments: Vec<(String, usize)>,
+    /// Apply an IPS or BPS patch to the ROM in memory. Can be repeated.
+    #[arg(long = "patch", value_name = "PATH")]
+    pub patches: Vec<String>,
+    /// Accept debugger commands as JSON lines on a TCP address like `127.0.0.1:6510`, to pause,
+    /// step, set breakpoints and read or write registers and memory while the window runs.
+    #[arg(long, value_name = "ADDRESS")]
+    pub debug_server: Option<String>,
+    /// Let a second player join over TCP on an address like `0.0.0.0:48770`. They see the
+    /// screen, hear the buzzer, and their keys are held along with the ones pressed here.
+    #[arg(long, value_name = "ADDRESS")]
+    pub host: Option<String>,
+    /// Join a game started with --host at an address like `192.168.1.5:48770` instead of
+    /// running a ROM, showing its screen and sending it the keys pressed here.
+    #[arg(long, value_name = "ADDRESS", conflicts_with_all = ["rom_path", "host"])]
+    pub join: Option<String>,
+    /// Pause before executing the instruction at an address. Can be repeated.
+    #[arg(long = "break", value_name = "ADDRESS", value_parser = parse_address)]
+    pub breakpoints: Vec<usize>,
+    /// Pause when a register like `v3` or a memory address changes. Can be repeated.
+    #[arg(long = "watch", value_name = "TARGET")]
+    pub watchpoints: Vec<Watchpoint>,
+    /// Keyboard layout for the keypad: `cosmac` (1234/QWER/ASDF/ZXCV), `hex`, or a TOML file
+    /// of bindings. The config's bindings, or `cosmac`, by default.
+    #[arg(long, value_parser = KeyMap::from_arg)]
+    pub keymap: Option<KeyMap>,
+    /// Settings file to use instead of `~/.config/chip8-emulator/config.toml`.
+    #[arg(long = "config", value_name = "PATH")]
+    pub config_path: Option<PathBuf>,
+}
+
+/// What shows the screen and takes input.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Frontend {
+    Winit,
+    Tui,
+}
+
+/// Shape of the buzzer's tone.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Waveform {
+    #[default]
+    Square,
+    Triangle,
+    /// White noise, changing at the tone's frequency.
+    Noise,
+}
+
+impl Options {
+    /// The clock asked for with --clock-hz, --ipf, --vip-timing or --cycle-table.
+    pub fn clock(&self) -> Option<Clock> {
+        if self.vip_timing {
+            return Some(Clock::vip());
+        }
+        if let Some(table) = &self.cycle_table {
+            return Some(Clock::from_cycle_table(table));
+        }
+        self.clock_hz
+            .map(Clock::from_hz)
+            .or_else(|| self.ipf.map(Clock::from_ipf))
+    }
+}
+
+/// A `--cycle-table` file, like:
+///
+/// ```toml
+/// cycles_per_frame = 3000
+///
+/// [cycles]
+/// DXYN = 1200
+/// 00E0 = 800
+/// ```
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CycleTableFile {
+    cycles_per_frame: Option<u64>,
+    #[serde(default)]
+    cycles: BTreeMap<String, u32>,
+}
+
+/// Read a `--cycle-table` file.
+fn load_cycle_table(path: &str) -> Result<Arc<CycleTable>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let file: CycleTableFile = toml::from_str(&text).map_err(|e| format!("{}: {}", path, e))?;
+    let table = CycleTable::new(&file.cycles, file.cycles_per_frame)
+        .map_err(|e| format!("{}: {}", path, e))?;
+    Ok(Arc::new(table))
+}
+
+/// Parse an address like `0x200` or `512`.
+fn parse_address(address: &str) -> Result<usize, std::num::ParseIntError> {
+    match address.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => address.parse(),
+    }
+}
+
+/// Parse a `--quirk` argument like `vf-reset=on`.
+fn parse_quirk_toggle(arg: &str) -> Result<(String, bool), String> {
+    let (name, setting) = arg.split_once('=').ok_or("expected <name>=<on|off>")?;
+    let enabled = match setting {
+        "on" => true,
+        "off" => false,
+        _ => return Err(format!("{} should be on or off", name)),
+    };
+    Quirks::default().set(name, enabled)?;
+    Ok((name.to_string(), enabled))
+}
+
+/// A change `--compare` makes to the quirks of the second machine.
+#[derive(Clone)]
+pub enum QuirkChange {
+    Preset(Quirks),
+    Toggle(String, bool),
+}
+
+impl QuirkChange {
+    pub fn apply(&self, quirks: &mut Quirks) {
+        match self {
+            QuirkChange::Preset(preset) => *quirks = *preset,
+            QuirkChange::Toggle(name, enabled) => quirks.set(name, *enabled).unwrap(),
+        }
+    }
+}
+
+/// Parse a `--compare` argument, a preset like `schip` or a change like `clip=off`.
+fn parse_quirk_change(arg: &str) -> Result<QuirkChange, String> {
+    if arg.contains('=') {
+        let (name, enabled) = parse_quirk_toggle(arg)?;
+        Ok(QuirkChange::Toggle(name, enabled))
+    } else {
+        arg.parse().map(QuirkChange::Preset)
+    }
+}
+
+/// Parse a `--load` argument like `font.bin@0x50`.
+fn parse_segment(arg: &str) -> Result<(String, usize), String> {
+    let (path, address) = arg.rsplit_once('@').ok_or("expected <file>@<address>")?;
+    let address = parse_address(address).map_err(|e| format!("invalid address: {}", e))?;
+    Ok((path.to_string(), address))
+}
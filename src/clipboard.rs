@@ -0,0 +1,175 @@
+use arboard::{Clipboard, ImageData};
+use std::borrow::Cow;
+use std::error::Error;
+
+use chip8emu::chip8::Chip8Emulator;
+use chip8emu::palette::Palette;
+
+// Each CHIP-8 pixel becomes an 8x8 block, so the image is readable when pasted.
+const SCALE: usize = 8;
+
+// Starts copied save states, so that pasting something else fails with a clear message.
+const STATE_PREFIX: &str = "chip8emu-state:";
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Scale an RGBA frame by an integer factor with nearest-neighbour sampling.
+pub fn scale_frame(frame: &[u8], width: usize, factor: usize) -> Vec<u8> {
+    let mut scaled = Vec::with_capacity(frame.len() * factor * factor);
+    for row in frame.chunks_exact(4 * width) {
+        let mut scaled_row = Vec::with_capacity(row.len() * factor);
+        for pixel in row.chunks_exact(4) {
+            for _ in 0..factor {
+                scaled_row.extend_from_slice(pixel);
+            }
+        }
+        for _ in 0..factor {
+            scaled.extend_from_slice(&scaled_row);
+        }
+    }
+    scaled
+}
+
+/// Standard base64 with padding.
+fn to_base64(data: &[u8]) -> String {
+    let mut text = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, byte)| {
+            bits | u32::from(*byte) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                text.push(char::from(BASE64[(bits >> (18 - 6 * i)) as usize & 0x3F]));
+            } else {
+                text.push('=');
+            }
+        }
+    }
+    text
+}
+
+/// The bytes of base64 text, ignoring whitespace in case it was wrapped on the way.
+fn from_base64(text: &str) -> Option<Vec<u8>> {
+    let digits: Vec<u8> = text.bytes().filter(|c| !c.is_ascii_whitespace()).collect();
+    if !digits.len().is_multiple_of(4) {
+        return None;
+    }
+    let mut data = Vec::with_capacity(digits.len() / 4 * 3);
+    for (n, chunk) in digits.chunks(4).enumerate() {
+        let last = n == digits.len() / 4 - 1;
+        let padding = chunk.iter().rev().take_while(|c| **c == b'=').count();
+        if padding > 2 || (padding > 0 && !last) {
+            return None;
+        }
+        let mut bits = 0u32;
+        for (i, c) in chunk[..4 - padding].iter().enumerate() {
+            let value = BASE64.iter().position(|digit| digit == c)? as u32;
+            bits |= value << (18 - 6 * i);
+        }
+        data.extend_from_slice(&bits.to_be_bytes()[1..4 - padding]);
+    }
+    Some(data)
+}
+
+/// The save state in text copied with `copy_state`, if it is one.
+fn state_from_text(text: &str) -> Option<Vec<u8>> {
+    text.trim().strip_prefix(STATE_PREFIX).and_then(from_base64)
+}
+
+/// Puts screenshots of the display and save states on the system clipboard, and takes save
+/// states back from it.
+pub struct SystemClipboard {
+    // Created on first use. On X11 the copied image is only available while this is alive.
+    clipboard: Option<Clipboard>,
+}
+
+impl SystemClipboard {
+    pub fn new() -> Self {
+        Self { clipboard: None }
+    }
+
+    fn clipboard(&mut self) -> Result<&mut Clipboard, arboard::Error> {
+        if self.clipboard.is_none() {
+            self.clipboard = Some(Clipboard::new()?);
+        }
+        Ok(self.clipboard.as_mut().unwrap())
+    }
+
+    /// Copy the emulator's screen, without any debug overlays, to the clipboard.
+    pub fn copy_screen(
+        &mut self,
+        emu: &Chip8Emulator,
+        palette: &Palette,
+    ) -> Result<(), arboard::Error> {
+        let mut frame = vec![0; 4 * emu.screen().len()];
+        emu.draw_screen_with_palette(&mut frame, palette);
+        // Pasted images shouldn't have see-through pixels.
+        for pixel in frame.chunks_exact_mut(4) {
+            pixel[3] = 0xff;
+        }
+
+        let (width, height) = (emu.screen_width(), emu.screen_height());
+        let image = ImageData {
+            width: width * SCALE,
+            height: height * SCALE,
+            bytes: Cow::Owned(scale_frame(&frame, width, SCALE)),
+        };
+
+        self.clipboard()?.set_image(image)
+    }
+
+    /// Copy a save state of the machine to the clipboard as text, to paste into a bug report
+    /// or a message.
+    pub fn copy_state(&mut self, emu: &Chip8Emulator) -> Result<(), arboard::Error> {
+        let text = format!("{}{}", STATE_PREFIX, to_base64(&emu.save_state()));
+        self.clipboard()?.set_text(text)
+    }
+
+    /// Restore the machine from a save state copied with `copy_state`, here or elsewhere.
+    pub fn paste_state(&mut self, emu: &mut Chip8Emulator) -> Result<(), Box<dyn Error>> {
+        let text = self.clipboard()?.get_text()?;
+        let state = state_from_text(&text).ok_or("the clipboard doesn't hold a save state")?;
+        emu.load_state(&state)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_survives_a_round_trip() {
+        let encoded = ["", "Zg==", "Zm8=", "Zm9v", "Zm9vYg==", "Zm9vYmE="];
+        for (len, text) in encoded.iter().enumerate() {
+            let data = &b"fooba"[..len];
+            assert_eq!(to_base64(data), *text);
+            assert_eq!(from_base64(text).as_deref(), Some(data));
+        }
+        let bytes: Vec<u8> = (0..=255).collect();
+        assert_eq!(from_base64(&to_base64(&bytes)), Some(bytes));
+        assert_eq!(from_base64("Zm9v\r\nYmE=").as_deref(), Some(&b"fooba"[..]));
+    }
+
+    #[test]
+    fn misplaced_padding_is_rejected() {
+        for text in ["Zg==Zm9v", "Z=g=", "=Zm9", "Zm=v", "Z==="] {
+            assert_eq!(from_base64(text), None, "{}", text);
+        }
+    }
+
+    #[test]
+    fn bad_lengths_are_rejected() {
+        for text in ["Z", "Zg", "Zg=", "Zm9vY", "Zm9vYmE"] {
+            assert_eq!(from_base64(text), None, "{}", text);
+        }
+    }
+
+    #[test]
+    fn pasted_states_need_the_prefix() {
+        let text = format!("  {}{}\n", STATE_PREFIX, to_base64(b"state"));
+        assert_eq!(state_from_text(&text).as_deref(), Some(&b"state"[..]));
+        assert_eq!(state_from_text(&to_base64(b"state")), None);
+        assert_eq!(state_from_text("chip8emu-state:not base64!"), None);
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use crate::chip8::{Chip8Emulator, StepOutcome};
+use crate::disasm;
+use crate::error::Chip8Error;
+use crate::opcode;
+
+/// Rate of the delay and sound timers, and of display refreshes.
+pub const TIMER_HZ: u32 = 60;
+
+/// Machine cycles the COSMAC VIP has for running CHIP-8 instructions in each frame: the 3,668
+/// its 1.76 MHz clock makes, less the 1,024 the display's DMA steals and roughly 40 for its
+/// interrupt routine.
+pub const VIP_CYCLES_PER_FRAME: u64 = 2604;
+
+/// CPU clock, run in batches of instructions between 60 Hz timer ticks. Rates that aren't a
+/// multiple of 60 carry the remainder over, so they still average out over a second.
+///
+/// With VIP timing, a frame instead runs as many instructions as fit in the VIP's machine
+/// cycles, each taking as long as it did there, so timing-sensitive ROMs run at its pace.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Clock {
+    instructions_per_second: u32,
+    remainder: u32,
+    // With VIP timing, the machine cycle count the current frame runs until.
+    vip_frame_end: Option<u64>,
+    // With VIP timing, the machine cycles in each frame.
+    cycles_per_frame: u64,
+}
+
+impl Clock {
+    pub fn from_hz(instructions_per_second: u32) -> Self {
+        Self {
+            instructions_per_second,
+            remainder: 0,
+            vip_frame_end: None,
+            cycles_per_frame: VIP_CYCLES_PER_FRAME,
+        }
+    }
+
+    /// A clock running a fixed number of instructions per frame, like Octo's tickrate.
+    pub fn from_ipf(instructions_per_frame: u32) -> Self {
+        Self::from_hz(instructions_per_frame * TIMER_HZ)
+    }
+
+    /// A clock keeping to the COSMAC VIP's timing, see `Opcode::vip_cycles`.
+    pub fn vip() -> Self {
+        Self {
+            vip_frame_end: Some(0),
+            ..Self::from_hz(0)
+        }
+    }
+
+    /// A clock that gives each instruction its cost in a `CycleTable`, like the VIP's timing
+    /// but with another interpreter's.
+    pub fn from_cycle_table(table: &CycleTable) -> Self {
+        Self {
+            cycles_per_frame: table.cycles_per_frame,
+            ..Self::vip()
+        }
+    }
+
+    pub fn is_vip(&self) -> bool {
+        self.vip_frame_end.is_some()
+    }
+
+    /// The fixed rate, or 0 with VIP timing.
+    pub fn instructions_per_second(&self) -> u32 {
+        self.instructions_per_second
+    }
+
+    /// How many instructions to execute before the next timer tick.
+    pub fn instructions_this_frame(&mut self) -> u32 {
+        let total = self.instructions_per_second + self.remainder;
+        self.remainder = total % TIMER_HZ;
+        total / TIMER_HZ
+    }
+
+    /// With VIP timing, the machine cycle count (see `Chip8Emulator::cycles`) to run the next
+    /// frame until, given the count so far. An instruction that ran past the end of the last
+    /// frame takes its time from this one, but time the machine spent waiting isn't made up.
+    pub fn vip_frame_end(&mut self, cycles: u64) -> u64 {
+        let end = self.vip_frame_end.unwrap_or(cycles).min(cycles) + self.cycles_per_frame;
+        self.vip_frame_end = Some(end);
+        end
+    }
+
+    /// Run a frame's worth of instructions, stopping early where `run_for` would.
+    pub fn run_frame(&mut self, emu: &mut Chip8Emulator) -> Result<StepOutcome, Chip8Error> {
+        if !self.is_vip() {
+            return emu.run_for(self.instructions_this_frame());
+        }
+        let end = self.vip_frame_end(emu.cycles());
+        match emu.run_until(|emu| emu.cycles() >= end)? {
+            StepOutcome::HitBreakpoint => Ok(StepOutcome::Completed),
+            outcome => Ok(outcome),
+        }
+    }
+}
+
+impl fmt::Display for Clock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_vip() && self.cycles_per_frame != VIP_CYCLES_PER_FRAME {
+            write!(f, "{} cycles per frame", self.cycles_per_frame)
+        } else if self.is_vip() {
+            write!(f, "VIP timing")
+        } else {
+            write!(f, "{} Hz", self.instructions_per_second)
+        }
+    }
+}
+
+/// What each instruction costs in machine cycles on an interpreter other than the VIP's, like
+/// the HP48's SUPER-CHIP or the DREAM 6800's, for running ROMs at its pace.
+#[derive(Debug, PartialEq, Eq)]
+pub struct CycleTable {
+    // The cost of every instruction word, so that looking one up is as cheap as for the VIP.
+    cycles: Vec<u32>,
+    cycles_per_frame: u64,
+}
+
+impl CycleTable {
+    /// Costs keyed by instruction pattern as `disasm::classify` names them, like `DXYN` or
+    /// `8XY4`, with the VIP's for instructions left out, and the machine cycles in a frame,
+    /// the VIP's by default.
+    pub fn new(
+        costs: &BTreeMap<String, u32>,
+        cycles_per_frame: Option<u64>,
+    ) -> Result<Self, String> {
+        let mut cycles: Vec<u32> = (0..=u16::MAX)
+            .map(|word| opcode::from_value(word).map_or(0, |op| op.vip_cycles()))
+            .collect();
+        let mut patterns = BTreeSet::new();
+        for (word, cost) in (0..=u16::MAX).zip(&mut cycles) {
+            if let Some((pattern, _)) = disasm::classify(word) {
+                patterns.insert(pattern);
+                if let Some(table_cost) = costs.get(pattern) {
+                    *cost = *table_cost;
+                }
+            }
+        }
+        if let Some(name) = costs.keys().find(|name| !patterns.contains(name.as_str())) {
+            return Err(format!(
+                "{} is not an instruction, expected one like DXYN",
+                name
+            ));
+        }
+        let cycles_per_frame = cycles_per_frame.unwrap_or(VIP_CYCLES_PER_FRAME);
+        if cycles_per_frame == 0 {
+            return Err("a frame needs at least one cycle".to_string());
+        }
+        Ok(Self {
+            cycles,
+            cycles_per_frame,
+        })
+    }
+
+    /// The cost of the instruction `word`.
+    pub fn cycles(&self, word: u16) -> u32 {
+        self.cycles[usize::from(word)]
+    }
+}
@@ -0,0 +1,113 @@
+use std::fmt;
+
+use crate::chip8::Chip8Emulator;
+use crate::error::Chip8Error;
+use crate::quirks::Quirks;
+
+/// The first frame two machines running the same ROM no longer agreed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    /// Frames completed before the difference showed, starting from 0.
+    pub frame: u64,
+    /// What differed, like `screen`, `V3 05/07` or `pc 0x214/0x216`, with the first machine's
+    /// value before the second's.
+    pub differences: Vec<String>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "frame {}: {}", self.frame, self.differences.join(", "))
+    }
+}
+
+/// Runs a second machine in lockstep with a first one, from a copy of it with other quirks, so
+/// that quirk-related bugs show up as the frame where the two stop matching.
+///
+/// Call `step` after each instruction the first machine runs and `end_frame` after its timers
+/// tick. The copy draws the same random numbers and sees the same keys.
+pub struct Comparison {
+    other: Chip8Emulator,
+    frame: u64,
+    // Why the copy stopped running, if it faulted.
+    fault: Option<Chip8Error>,
+    divergence: Option<Divergence>,
+}
+
+impl Comparison {
+    pub fn new(emu: &Chip8Emulator, quirks: Quirks) -> Self {
+        let mut other = emu.clone();
+        other.set_quirks(quirks);
+        Self {
+            other,
+            frame: 0,
+            fault: None,
+            divergence: None,
+        }
+    }
+
+    /// The second machine.
+    pub fn other(&self) -> &Chip8Emulator {
+        &self.other
+    }
+
+    /// Run the second machine's next instruction, with the keys the first one has.
+    pub fn step(&mut self, emu: &Chip8Emulator) {
+        if self.fault.is_some() {
+            return;
+        }
+        self.other.set_keys(emu.keys());
+        if let Err(e) = self.other.emulate_cycle() {
+            self.fault = Some(e);
+        }
+    }
+
+    /// Tick the second machine's timers, and compare the two. Returns the divergence the first
+    /// time they differ.
+    pub fn end_frame(&mut self, emu: &Chip8Emulator) -> Option<&Divergence> {
+        self.other.tick_timers();
+        let frame = self.frame;
+        self.frame += 1;
+        if self.divergence.is_some() {
+            return None;
+        }
+        let differences = differences(emu, &self.other, self.fault.as_ref());
+        if differences.is_empty() {
+            return None;
+        }
+        self.divergence = Some(Divergence { frame, differences });
+        self.divergence.as_ref()
+    }
+
+    /// Where the machines first differed, if they have.
+    pub fn divergence(&self) -> Option<&Divergence> {
+        self.divergence.as_ref()
+    }
+}
+
+fn differences(a: &Chip8Emulator, b: &Chip8Emulator, fault: Option<&Chip8Error>) -> Vec<String> {
+    let mut differences = Vec::new();
+    if let Some(fault) = fault {
+        differences.push(format!("second machine stopped: {}", fault));
+    }
+    let (width, height) = (a.screen_width(), a.screen_height());
+    if (width, height) != (b.screen_width(), b.screen_height())
+        || a.screen()[..width * height] != b.screen()[..width * height]
+    {
+        differences.push("screen".to_string());
+    }
+    for (x, (va, vb)) in a.registers().iter().zip(b.registers()).enumerate() {
+        if va != vb {
+            differences.push(format!("V{:X} {:02X}/{:02X}", x, va, vb));
+        }
+    }
+    if a.i() != b.i() {
+        differences.push(format!("I {:#05X}/{:#05X}", a.i(), b.i()));
+    }
+    if a.pc() != b.pc() {
+        differences.push(format!("pc {:#05X}/{:#05X}", a.pc(), b.pc()));
+    }
+    if a.stack() != b.stack() {
+        differences.push("stack".to_string());
+    }
+    differences
+}
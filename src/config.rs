@@ -0,0 +1,362 @@
+use log::{info, warn};
+use serde::de::{self, Deserializer};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use chip8emu::chip8::Variant;
+use chip8emu::palette::{parse_color, Palette};
+use chip8emu::quirks::Quirks;
+
+use crate::cli::Waveform;
+use crate::gamepad::GamepadMap;
+use crate::keymap::{Binding, KeyMap};
+use crate::romdb;
+
+// Written out the first time the emulator runs, so there's something to edit.
+const DEFAULT_CONFIG: &str = r##"# Settings for chip8-emulator. Command line options take priority over these.
+
+# Initial window size, in screen pixels per CHIP-8 pixel.
+scale = 10
+
+# Buzzer volume in percent.
+volume = 25
+
+# Buzzer tone: its shape (square, triangle or noise), pitch in Hz, and how many milliseconds it
+# takes to fade in and out, which softens the clicks as it starts and stops. XO-CHIP ROMs that
+# load their own audio pattern play that instead of the tone.
+# waveform = "square"
+# tone = 440
+# attack_ms = 2
+# release_ms = 10
+
+# Quirks preset (chip8, schip or xochip). Those of the ROM's variant by default.
+# quirks = "chip8"
+
+# Colors of lit and unlit pixels, unless the ROM comes with its own: a theme (white, green,
+# amber, high-contrast or octo), colors like "#FFCC00,#996600", or a table with `foreground`,
+# `background` and, for XO-CHIP's second plane, `plane_2` and `both_planes`.
+palette = "white"
+
+# Directory the launcher lists ROMs from when the emulator is started without one. The working
+# directory by default.
+# rom_dir = "/home/me/roms"
+
+# How far from the middle, from 0 to 1, a gamepad's analog sticks can move without pressing
+# anything, and whether pushing one diagonally presses the keys of both directions or only the
+# key of the nearer one.
+# stick_deadzone = 0.3
+# stick_diagonals = true
+
+# Keyboard keys for each CHIP-8 key, by winit key name. A key can have several, like
+# `0 = ["X", "Numpad0"]`.
+[keys]
+1 = "Key1"
+2 = "Key2"
+3 = "Key3"
+C = "Key4"
+4 = "Q"
+5 = "W"
+6 = "E"
+D = "R"
+7 = "A"
+8 = "S"
+9 = "D"
+E = "F"
+A = "Z"
+0 = "X"
+B = "C"
+F = "V"
+
+# Gamepad buttons for each CHIP-8 key, by gilrs button name, for builds with the gamepad
+# feature. The analog sticks' directions are LeftStickUp, RightStickLeft and so on. Without
+# this table the D-pad and left stick are on 2/4/6/8, South on 5, East on A, North on B, West
+# on 0, Select on E and Start on F.
+# [gamepad]
+# 2 = ["DPadUp", "LeftStickUp"]
+# 8 = ["DPadDown", "LeftStickDown"]
+# 5 = ["South", "RightTrigger"]
+
+# Buttons on the second gamepad to be connected, for two-player ROMs, in the same form. Without
+# this table it presses the same keys as the first.
+# [gamepad2]
+# C = "DPadUp"
+# D = "DPadDown"
+
+# Settings for a single ROM, keyed by the SHA-1 of the file (see `chip8emu inspect`). These add
+# to or override the built-in database of known ROMs.
+# [roms.0123456789abcdef0123456789abcdef01234567]
+# title = "Pong"
+# variant = "schip"
+# quirks = "schip"
+# clock_hz = 1000
+# palette = "green"   # or { foreground = "#FFCC00", background = "#996600" }
+# keymap = "hex"      # or { 1 = "W", 4 = "S" }
+# gamepad = { 1 = "DPadUp", 4 = "DPadDown" }
+# gamepad2 = { C = "DPadUp", D = "DPadDown" }
+"##;
+
+// How many ROMs the recently played list keeps.
+const MAX_RECENT: usize = 10;
+
+/// A palette as written in the config: a theme or colors as on the command line, or a table
+/// of colors.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PaletteSetting {
+    Parsed(String),
+    Table {
+        foreground: String,
+        background: String,
+        plane_2: Option<String>,
+        both_planes: Option<String>,
+    },
+}
+
+/// Key bindings as written in the config: `cosmac`, `hex` or the path of a bindings file, as on
+/// the command line, or a table of bindings.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum KeyMapSetting {
+    Parsed(String),
+    Table(BTreeMap<String, Binding>),
+}
+
+/// Settings for one ROM, which take priority over the ROM's Octo settings and the global
+/// ones.
+#[derive(Deserialize, Default, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct RomConfig {
+    /// Name to show instead of the file's.
+    pub title: Option<String>,
+    #[serde(deserialize_with = "parsed")]
+    pub variant: Option<Variant>,
+    #[serde(deserialize_with = "parsed")]
+    pub quirks: Option<Quirks>,
+    pub clock_hz: Option<u32>,
+    #[serde(deserialize_with = "palette")]
+    pub palette: Option<Palette>,
+    #[serde(deserialize_with = "keymap")]
+    pub keymap: Option<KeyMap>,
+    pub gamepad: Option<GamepadMap>,
+    pub gamepad2: Option<GamepadMap>,
+}
+
+impl RomConfig {
+    /// These settings, with the ones left out taken from `fallback`.
+    pub fn or(self, fallback: Self) -> Self {
+        Self {
+            title: self.title.or(fallback.title),
+            variant: self.variant.or(fallback.variant),
+            quirks: self.quirks.or(fallback.quirks),
+            clock_hz: self.clock_hz.or(fallback.clock_hz),
+            palette: self.palette.or(fallback.palette),
+            keymap: self.keymap.or(fallback.keymap),
+            gamepad: self.gamepad.or(fallback.gamepad),
+            gamepad2: self.gamepad2.or(fallback.gamepad2),
+        }
+    }
+}
+
+/// Persistent settings, read from `~/.config/chip8-emulator/config.toml`.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub scale: Option<u32>,
+    pub volume: Option<u8>,
+    pub waveform: Option<Waveform>,
+    pub tone: Option<f32>,
+    pub attack_ms: Option<u32>,
+    pub release_ms: Option<u32>,
+    #[serde(deserialize_with = "parsed")]
+    pub quirks: Option<Quirks>,
+    #[serde(deserialize_with = "palette")]
+    pub palette: Option<Palette>,
+    pub gamepad: Option<GamepadMap>,
+    pub gamepad2: Option<GamepadMap>,
+    pub stick_deadzone: Option<f32>,
+    pub stick_diagonals: Option<bool>,
+    pub rom_dir: Option<PathBuf>,
+    /// ROMs played lately, the last one first, kept up to date as ROMs are loaded.
+    pub recent: Vec<PathBuf>,
+    keys: BTreeMap<String, Binding>,
+    // Keyed by SHA-1, in hex of either case.
+    roms: BTreeMap<String, RomConfig>,
+    // Where the config was read from, so that `recent` can be written back.
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl Config {
+    /// Where the config lives: under `$XDG_CONFIG_HOME`, or `~/.config` without it.
+    pub fn default_path() -> Option<PathBuf> {
+        let base = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+        Some(base.join("chip8-emulator").join("config.toml"))
+    }
+
+    /// Read the config, writing out the defaults first if there isn't one yet. Problems are
+    /// logged and leave the built-in defaults in place.
+    pub fn load(path: &Path) -> Self {
+        if !path.exists() {
+            match write_default(path) {
+                Ok(()) => info!("wrote default settings to {}", path.display()),
+                Err(e) => warn!("failed to write {}: {}", path.display(), e),
+            }
+        }
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) => {
+                warn!("failed to read {}: {}", path.display(), e);
+                return Self::default();
+            }
+        };
+        match toml::from_str::<Self>(&text) {
+            Ok(config) => Self {
+                path: Some(path.to_path_buf()),
+                ..config
+            },
+            Err(e) => {
+                warn!("ignoring {}: {}", path.display(), e);
+                Self::default()
+            }
+        }
+    }
+
+    /// Move a ROM to the front of the recently played list and save the list to the config
+    /// file, leaving the rest of the file as it is.
+    pub fn remember_rom(&mut self, rom: &Path) {
+        let rom = fs::canonicalize(rom).unwrap_or_else(|_| rom.to_path_buf());
+        self.recent.retain(|path| *path != rom);
+        self.recent.insert(0, rom);
+        self.recent.truncate(MAX_RECENT);
+
+        let Some(path) = &self.path else {
+            return;
+        };
+        if let Err(e) = write_recent(path, &self.recent) {
+            warn!("failed to update {}: {}", path.display(), e);
+        }
+    }
+
+    /// The key bindings in the config, if it has any.
+    pub fn keymap(&self) -> Option<KeyMap> {
+        if self.keys.is_empty() {
+            return None;
+        }
+        let keys = self
+            .keys
+            .iter()
+            .map(|(key, binding)| (key.clone(), binding.clone()))
+            .collect();
+        match KeyMap::from_table(keys) {
+            Ok(map) => Some(map.with_name("config")),
+            Err(e) => {
+                warn!("ignoring key bindings in config: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Settings for the ROM with this SHA-1: those in the config, on top of any the built-in
+    /// ROM database has.
+    pub fn rom(&self, sha1: &str) -> RomConfig {
+        let own = self
+            .roms
+            .iter()
+            .find(|(hash, _)| hash.eq_ignore_ascii_case(sha1))
+            .map(|(_, rom)| rom.clone())
+            .unwrap_or_default();
+        own.or(romdb::lookup(sha1).unwrap_or_default())
+    }
+}
+
+fn write_recent(path: &Path, recent: &[PathBuf]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut document: toml_edit::DocumentMut = fs::read_to_string(path)?.parse()?;
+    let list: toml_edit::Array = recent
+        .iter()
+        .map(|rom| rom.to_string_lossy().into_owned())
+        .collect();
+    let is_new = !document.contains_key("recent");
+    document["recent"] = toml_edit::value(list);
+    if is_new {
+        if let Some(mut key) = document.as_table_mut().key_mut("recent") {
+            key.leaf_decor_mut().set_prefix(
+                "\n# ROMs played lately, the last one first. Kept up to date by the emulator.\n",
+            );
+        }
+    }
+    fs::write(path, document.to_string())?;
+    Ok(())
+}
+
+fn write_default(path: &Path) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, DEFAULT_CONFIG)
+}
+
+/// Deserialize a string with the type's `FromStr`, like the command line does.
+pub fn parsed<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|s| s.parse().map_err(de::Error::custom))
+        .transpose()
+}
+
+fn keymap<'de, D>(deserializer: D) -> Result<Option<KeyMap>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<KeyMapSetting>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(KeyMapSetting::Parsed(keymap)) => KeyMap::from_arg(&keymap)
+            .map(Some)
+            .map_err(de::Error::custom),
+        Some(KeyMapSetting::Table(table)) => KeyMap::from_table(table)
+            .map(|keymap| Some(keymap.with_name("rom")))
+            .map_err(de::Error::custom),
+    }
+}
+
+fn palette<'de, D>(deserializer: D) -> Result<Option<Palette>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let color = |color: &str| {
+        parse_color(color)
+            .ok_or_else(|| de::Error::custom(format!("{} is not a #RRGGBB color", color)))
+    };
+    match Option::<PaletteSetting>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(PaletteSetting::Parsed(palette)) => {
+            palette.parse().map(Some).map_err(de::Error::custom)
+        }
+        Some(PaletteSetting::Table {
+            foreground,
+            background,
+            plane_2,
+            both_planes,
+        }) => {
+            let mut palette = Palette::new(color(&foreground)?, color(&background)?);
+            if let Some(plane_2) = plane_2 {
+                palette.plane_2 = color(&plane_2)?;
+            }
+            if let Some(both_planes) = both_planes {
+                palette.both_planes = color(&both_planes)?;
+            }
+            Ok(Some(palette))
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use log::{info, warn};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+
+use chip8emu::chip8::Chip8Emulator;
+use chip8emu::debugger::Debugger;
+
+// Clients send one JSON object per line, named by its "cmd":
+//   {"cmd": "state"}                                     the registers, timers and stack
+//   {"cmd": "pause"}                                     pause, replying with the state
+//   {"cmd": "continue"}, {"cmd": "step"}, {"cmd": "step_over"}
+//   {"cmd": "set_register", "register": "v3", "value": 7}    also "i", "pc", "dt" and "st"
+//   {"cmd": "read_memory", "address": 512, "length": 16}     replies {"bytes": [...]}
+//   {"cmd": "write_memory", "address": 768, "bytes": [1, 2]}
+//   {"cmd": "add_breakpoint", "address": 522}, {"cmd": "remove_breakpoint", "address": 522}
+// Each gets a one line reply, an object with "error" if it failed. Once execution stops after
+// continuing or stepping, at a breakpoint, a watchpoint or the end of a step, the server also
+// sends {"event": "stopped"} with the state.
+
+/// A debugger command, in the JSON a client sends.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+pub enum Command {
+    State,
+    Pause,
+    Continue,
+    Step,
+    StepOver,
+    SetRegister { register: String, value: usize },
+    ReadMemory { address: usize, length: usize },
+    WriteMemory { address: usize, bytes: Vec<u8> },
+    AddBreakpoint { address: usize },
+    RemoveBreakpoint { address: usize },
+}
+
+/// A command from a client, to be carried out on the thread that runs the machine.
+#[derive(Debug)]
+pub struct Request {
+    pub command: Command,
+    /// Lines to send to the client, for the reply and later events.
+    pub client: Sender<String>,
+}
+
+/// Listen for debugger clients on `address`, one at a time, calling `send` with each command.
+pub fn listen<F>(address: &str, send: F) -> io::Result<()>
+where
+    F: Fn(Request) + Send + 'static,
+{
+    let listener = TcpListener::bind(address)?;
+    info!("debug server listening on {}", listener.local_addr()?);
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    warn!("failed to accept debugger client: {}", e);
+                    continue;
+                }
+            };
+            let peer = stream.peer_addr().map(|peer| peer.to_string());
+            info!(
+                "debugger client connected from {}",
+                peer.as_deref().unwrap_or("?")
+            );
+
+            // Replies and events are written on their own thread, so events can be sent while
+            // this one waits for the next command.
+            let (client, lines) = mpsc::channel::<String>();
+            let mut writer = match stream.try_clone() {
+                Ok(writer) => writer,
+                Err(e) => {
+                    warn!("failed to set up debugger client: {}", e);
+                    continue;
+                }
+            };
+            thread::spawn(move || {
+                for line in lines {
+                    if writeln!(writer, "{}", line).is_err() {
+                        break;
+                    }
+                }
+            });
+
+            for line in BufReader::new(stream).lines() {
+                let Ok(line) = line else { break };
+                if line.trim().is_empty() {
+                    continue;
+                }
+                match serde_json::from_str(&line) {
+                    Ok(command) => send(Request {
+                        command,
+                        client: client.clone(),
+                    }),
+                    Err(e) => {
+                        let _ = client.send(json!({ "error": e.to_string() }).to_string());
+                    }
+                }
+            }
+            info!("debugger client disconnected");
+        }
+    });
+    Ok(())
+}
+
+/// The registers, timers and stack, and whether execution is stopped.
+pub fn state(emu: &Chip8Emulator, debugger: &Debugger) -> Value {
+    json!({
+        "pc": emu.pc(),
+        "i": emu.i(),
+        "v": emu.registers(),
+        "dt": emu.delay_timer(),
+        "st": emu.sound_timer(),
+        "stack": emu.stack(),
+        "stopped": debugger.is_stopped(),
+    })
+}
+
+/// Carry out a command, returning the reply.
+pub fn handle(command: &Command, emu: &mut Chip8Emulator, debugger: &mut Debugger) -> Value {
+    match command {
+        Command::State => state(emu, debugger),
+        Command::Pause => {
+            debugger.pause();
+            state(emu, debugger)
+        }
+        Command::Continue => {
+            debugger.resume();
+            json!({})
+        }
+        Command::Step => {
+            debugger.step();
+            json!({})
+        }
+        Command::StepOver => {
+            debugger.step_over(emu);
+            json!({})
+        }
+        Command::SetRegister { register, value } => match set_register(emu, register, *value) {
+            Ok(()) => state(emu, debugger),
+            Err(e) => json!({ "error": e }),
+        },
+        Command::ReadMemory { address, length } => {
+            json!({ "bytes": emu.read_memory(*address..address.saturating_add(*length)) })
+        }
+        Command::WriteMemory { address, bytes } => match emu.write_memory(*address, bytes) {
+            Ok(()) => json!({}),
+            Err(e) => json!({ "error": e.to_string() }),
+        },
+        Command::AddBreakpoint { address } => {
+            debugger.add_breakpoint(*address);
+            json!({ "breakpoints": debugger.breakpoints().collect::<Vec<_>>() })
+        }
+        Command::RemoveBreakpoint { address } => {
+            debugger.remove_breakpoint(*address);
+            json!({ "breakpoints": debugger.breakpoints().collect::<Vec<_>>() })
+        }
+    }
+}
+
+fn set_register(emu: &mut Chip8Emulator, register: &str, value: usize) -> Result<(), String> {
+    let byte = || u8::try_from(value).map_err(|_| format!("{} doesn't fit in a byte", value));
+    match register.to_ascii_lowercase().as_str() {
+        "i" => emu.set_index(value),
+        "pc" => emu.set_pc(value),
+        "dt" => emu.set_delay_timer(byte()?),
+        "st" => emu.set_sound_timer(byte()?),
+        name => match name.strip_prefix('v').map(|x| usize::from_str_radix(x, 16)) {
+            Some(Ok(x)) if x < 16 => emu.set_register(x, byte()?),
+            _ => return Err(format!("unknown register {:?}", register)),
+        },
+    }
+    Ok(())
+}
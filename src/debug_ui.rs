@@ -0,0 +1,701 @@
+use egui::{Color32, Pos2, RawInput, Rect, RichText, Sense, Vec2};
+use egui_wgpu::renderer::{RenderPass, ScreenDescriptor};
+use log::error;
+use pixels::{wgpu, Pixels, PixelsContext};
+use winit::dpi::PhysicalSize;
+use winit::event::{
+    ElementState, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent,
+};
+
+use chip8emu::chip8::Chip8Emulator;
+use chip8emu::debugger::Debugger;
+use chip8emu::palette::Palette;
+use chip8emu::quirks::QUIRK_NAMES;
+use chip8emu::timeline::Timeline;
+
+// Points scrolled per line of a mouse wheel.
+const SCROLL_LINE: f32 = 24.0;
+// Bytes per row of the memory viewer.
+const ROW_BYTES: usize = 16;
+// Rows of the sprite at I shown, enough for a SUPER-CHIP 16x16 sprite of 8 pixel wide halves.
+const SPRITE_ROWS: usize = 16;
+
+const PC_HIGHLIGHT: Color32 = Color32::from_rgb(0x40, 0xc0, 0xff);
+const I_HIGHLIGHT: Color32 = Color32::from_rgb(0xff, 0xc0, 0x00);
+const HELD: Color32 = Color32::from_rgb(0x40, 0xff, 0x40);
+
+// Keys in the order they appear on the COSMAC VIP hex keypad.
+const LAYOUT: [[usize; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+
+/// Which debugger windows are open.
+struct Panels {
+    memory: bool,
+    screen: bool,
+    stack: bool,
+    registers: bool,
+    keypad: bool,
+    breakpoints: bool,
+    quirks: bool,
+    movie: bool,
+}
+
+/// Debugger windows drawn with egui over the screen, toggled with F12: a memory viewer that can
+/// be edited in place, the screen and the sprite at I, the stack, the registers, the keypad,
+/// breakpoints, the quirks and a timeline of the movie being played back with its inputs.
+pub struct DebugUi {
+    pub visible: bool,
+    context: egui::Context,
+    render_pass: RenderPass,
+    screen: ScreenDescriptor,
+    // Input received since the last frame.
+    input: RawInput,
+    // Pointer position in points, which button events don't carry.
+    pointer: Pos2,
+    modifiers: egui::Modifiers,
+    // The last frame, waiting to be drawn.
+    paint_jobs: Vec<egui::ClippedPrimitive>,
+    textures: egui::TexturesDelta,
+    panels: Panels,
+    // Address being edited in the memory viewer, and the text typed so far.
+    editing: Option<(usize, String)>,
+    // Address typed into the breakpoint list.
+    new_breakpoint: String,
+    // Set when a quirk is flipped, until the ROM is restarted or the prompt is dismissed.
+    quirks_changed: bool,
+    // Set when the restart button is clicked, until collected by `take_restart`.
+    restart: bool,
+    // Frame of the movie to go to, until collected by `take_seek`.
+    seek: Option<usize>,
+    // Set when the movie's save button is clicked, until collected by `take_save_movie`.
+    save_movie: bool,
+    // Name typed in for a new branch of the movie.
+    new_branch: String,
+}
+
+impl DebugUi {
+    pub fn new(pixels: &Pixels, size: PhysicalSize<u32>, scale_factor: f64) -> Self {
+        Self {
+            visible: false,
+            context: egui::Context::default(),
+            render_pass: RenderPass::new(pixels.device(), pixels.render_texture_format(), 1),
+            screen: ScreenDescriptor {
+                size_in_pixels: [size.width, size.height],
+                pixels_per_point: scale_factor as f32,
+            },
+            input: RawInput::default(),
+            pointer: Pos2::ZERO,
+            modifiers: egui::Modifiers::default(),
+            paint_jobs: Vec::new(),
+            textures: egui::TexturesDelta::default(),
+            panels: Panels {
+                memory: true,
+                screen: true,
+                stack: true,
+                registers: true,
+                keypad: true,
+                breakpoints: true,
+                quirks: true,
+                movie: true,
+            },
+            editing: None,
+            new_breakpoint: String::new(),
+            quirks_changed: false,
+            restart: false,
+            seek: None,
+            save_movie: false,
+            new_branch: String::new(),
+        }
+    }
+
+    /// Whether the ROM should be restarted, after its quirks were changed.
+    pub fn take_restart(&mut self) -> bool {
+        std::mem::take(&mut self.restart)
+    }
+
+    /// The frame of the movie being played back to go to, after it was edited.
+    pub fn take_seek(&mut self) -> Option<usize> {
+        self.seek.take()
+    }
+
+    /// Whether the movie being played back should be saved.
+    pub fn take_save_movie(&mut self) -> bool {
+        std::mem::take(&mut self.save_movie)
+    }
+
+    /// Whether a window is being typed into, so keys shouldn't also go to the emulator.
+    pub fn wants_keyboard(&self) -> bool {
+        self.visible && self.context.wants_keyboard_input()
+    }
+
+    /// Pass a window event on to egui.
+    pub fn handle_event(&mut self, event: &WindowEvent) {
+        let points = self.screen.pixels_per_point;
+        let event = match event {
+            WindowEvent::Resized(size) => {
+                self.screen.size_in_pixels = [size.width, size.height];
+                return;
+            }
+            WindowEvent::ScaleFactorChanged {
+                scale_factor,
+                new_inner_size,
+            } => {
+                self.screen.pixels_per_point = *scale_factor as f32;
+                self.screen.size_in_pixels = [new_inner_size.width, new_inner_size.height];
+                return;
+            }
+            WindowEvent::ModifiersChanged(state) => {
+                self.modifiers = egui::Modifiers {
+                    alt: state.alt(),
+                    ctrl: state.ctrl(),
+                    shift: state.shift(),
+                    mac_cmd: cfg!(target_os = "macos") && state.logo(),
+                    command: if cfg!(target_os = "macos") {
+                        state.logo()
+                    } else {
+                        state.ctrl()
+                    },
+                };
+                return;
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                self.pointer = Pos2::new(position.x as f32 / points, position.y as f32 / points);
+                egui::Event::PointerMoved(self.pointer)
+            }
+            WindowEvent::CursorLeft { .. } => egui::Event::PointerGone,
+            WindowEvent::MouseInput { state, button, .. } => {
+                let button = match button {
+                    MouseButton::Left => egui::PointerButton::Primary,
+                    MouseButton::Right => egui::PointerButton::Secondary,
+                    MouseButton::Middle => egui::PointerButton::Middle,
+                    MouseButton::Other(_) => return,
+                };
+                egui::Event::PointerButton {
+                    pos: self.pointer,
+                    button,
+                    pressed: *state == ElementState::Pressed,
+                    modifiers: self.modifiers,
+                }
+            }
+            WindowEvent::MouseWheel { delta, .. } => egui::Event::Scroll(match delta {
+                MouseScrollDelta::LineDelta(x, y) => Vec2::new(*x, *y) * SCROLL_LINE,
+                MouseScrollDelta::PixelDelta(delta) => {
+                    Vec2::new(delta.x as f32, delta.y as f32) / points
+                }
+            }),
+            WindowEvent::ReceivedCharacter(c) if !c.is_control() => {
+                egui::Event::Text(c.to_string())
+            }
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        virtual_keycode: Some(key),
+                        state,
+                        ..
+                    },
+                ..
+            } => match egui_key(*key) {
+                Some(key) => egui::Event::Key {
+                    key,
+                    pressed: *state == ElementState::Pressed,
+                    modifiers: self.modifiers,
+                },
+                None => return,
+            },
+            _ => return,
+        };
+        self.input.events.push(event);
+    }
+
+    /// Lay out the windows for this frame, applying any edits made in them.
+    /// Lay out the windows, given the timeline of the movie being played back, if any, and the
+    /// next frame it plays.
+    pub fn run(
+        &mut self,
+        emu: &mut Chip8Emulator,
+        debugger: &mut Debugger,
+        palette: &Palette,
+        playback: Option<(&mut Timeline, usize)>,
+    ) {
+        if !self.visible {
+            self.input.events.clear();
+            return;
+        }
+        let mut input = std::mem::take(&mut self.input);
+        let [width, height] = self.screen.size_in_pixels;
+        let points = self.screen.pixels_per_point;
+        input.screen_rect = Some(Rect::from_min_size(
+            Pos2::ZERO,
+            Vec2::new(width as f32, height as f32) / points,
+        ));
+        input.pixels_per_point = Some(points);
+        input.modifiers = self.modifiers;
+
+        let context = self.context.clone();
+        let output = context.run(input, |ctx| self.ui(ctx, emu, debugger, palette, playback));
+        self.paint_jobs = context.tessellate(output.shapes);
+        self.textures.append(output.textures_delta);
+    }
+
+    /// Draw the last frame laid out by `run` over `target`.
+    pub fn render(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        context: &PixelsContext,
+    ) {
+        if !self.visible {
+            return;
+        }
+        for (id, delta) in &self.textures.set {
+            self.render_pass
+                .update_texture(&context.device, &context.queue, *id, delta);
+        }
+        self.render_pass.update_buffers(
+            &context.device,
+            &context.queue,
+            &self.paint_jobs,
+            &self.screen,
+        );
+        self.render_pass
+            .execute(encoder, target, &self.paint_jobs, &self.screen, None);
+        for id in &self.textures.free {
+            self.render_pass.free_texture(id);
+        }
+        self.textures = egui::TexturesDelta::default();
+    }
+
+    fn ui(
+        &mut self,
+        ctx: &egui::Context,
+        emu: &mut Chip8Emulator,
+        debugger: &mut Debugger,
+        palette: &Palette,
+        playback: Option<(&mut Timeline, usize)>,
+    ) {
+        egui::TopBottomPanel::top("panels").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.panels.memory, "Memory");
+                ui.checkbox(&mut self.panels.screen, "Screen");
+                ui.checkbox(&mut self.panels.stack, "Stack");
+                ui.checkbox(&mut self.panels.registers, "Registers");
+                ui.checkbox(&mut self.panels.keypad, "Keypad");
+                ui.checkbox(&mut self.panels.breakpoints, "Breakpoints");
+                ui.checkbox(&mut self.panels.quirks, "Quirks");
+                if playback.is_some() {
+                    ui.checkbox(&mut self.panels.movie, "Movie");
+                }
+            });
+        });
+
+        let mut open = self.panels.memory;
+        egui::Window::new("Memory")
+            .open(&mut open)
+            .default_pos([8.0, 40.0])
+            .show(ctx, |ui| self.memory_ui(ui, emu));
+        self.panels.memory = open;
+
+        egui::Window::new("Screen")
+            .open(&mut self.panels.screen)
+            .default_pos([420.0, 40.0])
+            .show(ctx, |ui| screen_ui(ui, emu, palette));
+
+        egui::Window::new("Stack")
+            .open(&mut self.panels.stack)
+            .default_pos([420.0, 260.0])
+            .show(ctx, |ui| {
+                ui.monospace(format!("SP {}", emu.sp()));
+                for (level, address) in emu.stack().iter().enumerate().rev() {
+                    ui.monospace(format!("{:2} {:#05X}", level, address));
+                }
+            });
+
+        egui::Window::new("Registers")
+            .open(&mut self.panels.registers)
+            .default_pos([420.0, 420.0])
+            .show(ctx, |ui| {
+                egui::Grid::new("registers").show(ui, |ui| {
+                    for (row, values) in emu.registers().chunks(4).enumerate() {
+                        for (column, value) in values.iter().enumerate() {
+                            ui.monospace(format!("V{:X} {:02X}", row * 4 + column, value));
+                        }
+                        ui.end_row();
+                    }
+                });
+                ui.monospace(format!("PC {:#05X}  I {:#05X}", emu.pc(), emu.i()));
+                ui.monospace(format!(
+                    "DT {:3}  ST {:3}",
+                    emu.delay_timer(),
+                    emu.sound_timer()
+                ));
+            });
+
+        egui::Window::new("Keypad")
+            .open(&mut self.panels.keypad)
+            .default_pos([560.0, 260.0])
+            .show(ctx, |ui| {
+                egui::Grid::new("keys").show(ui, |ui| {
+                    for row in LAYOUT {
+                        for key in row {
+                            let text = RichText::new(format!("{:X}", key)).monospace();
+                            ui.label(if emu.keys()[key] {
+                                text.color(HELD).strong()
+                            } else {
+                                text
+                            });
+                        }
+                        ui.end_row();
+                    }
+                });
+            });
+
+        let mut open = self.panels.breakpoints;
+        egui::Window::new("Breakpoints")
+            .open(&mut open)
+            .default_pos([660.0, 260.0])
+            .show(ctx, |ui| self.breakpoints_ui(ui, emu, debugger));
+        self.panels.breakpoints = open;
+
+        let mut open = self.panels.quirks;
+        egui::Window::new("Quirks")
+            .open(&mut open)
+            .default_pos([660.0, 420.0])
+            .show(ctx, |ui| self.quirks_ui(ui, emu));
+        self.panels.quirks = open;
+
+        if let Some((timeline, frame)) = playback {
+            let mut open = self.panels.movie;
+            egui::Window::new("Movie")
+                .open(&mut open)
+                .default_pos([8.0, 460.0])
+                .show(ctx, |ui| self.movie_ui(ui, timeline, frame));
+            self.panels.movie = open;
+        }
+    }
+
+    /// A slider over the movie's frames, going to the frame it is dragged to, then the movie's
+    /// inputs as a grid of a row per frame and a column per key, which can be clicked to change
+    /// them. Clicking a frame's number goes there. The next frame to play is highlighted, and
+    /// frames can be inserted or dropped there. Changing a frame already played goes back to
+    /// the nearest snapshot before it and plays on to the current frame again. Below it, the
+    /// frames so far can be saved as a named branch, and the movie switched to one.
+    fn movie_ui(&mut self, ui: &mut egui::Ui, timeline: &mut Timeline, frame: usize) {
+        let len = timeline.movie().len();
+        let mut target = frame;
+        ui.horizontal(|ui| {
+            if ui.add_enabled(frame > 0, egui::Button::new("<")).clicked() {
+                target = frame - 1;
+            }
+            ui.add(egui::Slider::new(&mut target, 0..=len).text(format!("of {}", len)));
+            if ui
+                .add_enabled(frame < len, egui::Button::new(">"))
+                .clicked()
+            {
+                target = frame + 1;
+            }
+        });
+        if target != frame {
+            self.seek = Some(target);
+        }
+        ui.horizontal(|ui| {
+            if ui.button("Insert").clicked() {
+                timeline.insert_frame(frame);
+            }
+            if ui
+                .add_enabled(frame < len, egui::Button::new("Delete"))
+                .clicked()
+            {
+                timeline.remove_frame(frame);
+            }
+            if ui.button("Truncate").clicked() {
+                timeline.truncate(frame);
+            }
+            if ui.button("Save").clicked() {
+                self.save_movie = true;
+            }
+        });
+        ui.separator();
+
+        let row_height = ui.spacing().interact_size.y;
+        let mut edit = None;
+        egui::ScrollArea::vertical().max_height(240.0).show_rows(
+            ui,
+            row_height,
+            timeline.movie().len(),
+            |ui, range| {
+                for n in range {
+                    let keys = timeline.movie().frame(n).unwrap();
+                    ui.horizontal(|ui| {
+                        ui.spacing_mut().item_spacing.x = 2.0;
+                        let mut label = RichText::new(format!("{:6}", n)).monospace();
+                        if n == frame {
+                            label = label.color(PC_HIGHLIGHT);
+                        }
+                        if ui
+                            .add(egui::Label::new(label).sense(Sense::click()))
+                            .clicked()
+                        {
+                            self.seek = Some(n);
+                        }
+                        for key in 0..16 {
+                            let text = RichText::new(format!("{:X}", key)).monospace();
+                            if ui.selectable_label(keys[key], text).clicked() {
+                                let mut keys = keys;
+                                keys[key] = !keys[key];
+                                edit = Some((n, keys));
+                            }
+                        }
+                    });
+                }
+            },
+        );
+
+        if let Some((n, keys)) = edit {
+            timeline.set_keys(n, &keys);
+            if n < frame {
+                self.seek = Some(frame);
+            }
+        }
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.new_branch)
+                    .hint_text("branch name")
+                    .desired_width(120.0),
+            );
+            let name = self.new_branch.trim();
+            if ui
+                .add_enabled(!name.is_empty(), egui::Button::new("Save branch"))
+                .clicked()
+            {
+                match timeline.save_branch(name, frame) {
+                    Ok(()) => self.new_branch.clear(),
+                    Err(e) => error!("failed to save branch {}: {}", name, e),
+                }
+            }
+        });
+        let mut switch = None;
+        let mut remove = None;
+        for branch in timeline.movie().branches() {
+            ui.horizontal(|ui| {
+                ui.label(format!("{} ({} frames)", branch.name, branch.len()));
+                if ui.small_button("Switch").clicked() {
+                    switch = Some(branch.name.clone());
+                }
+                if ui.small_button("x").clicked() {
+                    remove = Some(branch.name.clone());
+                }
+            });
+        }
+        if let Some(name) = switch {
+            self.seek = timeline.switch_branch(&name);
+        }
+        if let Some(name) = remove {
+            timeline.remove_branch(&name);
+        }
+    }
+
+    /// A checkbox for each quirk, taking effect at once. Since a ROM may already have acted on
+    /// the old setting, flipping one offers to restart it.
+    fn quirks_ui(&mut self, ui: &mut egui::Ui, emu: &mut Chip8Emulator) {
+        let mut quirks = emu.quirks();
+        for name in QUIRK_NAMES {
+            let mut enabled = quirks.is_set(name);
+            if ui.checkbox(&mut enabled, name).changed() {
+                quirks.set(name, enabled).unwrap();
+                emu.set_quirks(quirks);
+                self.quirks_changed = true;
+            }
+        }
+        if self.quirks_changed {
+            ui.separator();
+            ui.label("Restart the ROM with these quirks?");
+            ui.horizontal(|ui| {
+                if ui.button("Restart").clicked() {
+                    self.restart = true;
+                    self.quirks_changed = false;
+                }
+                if ui.button("Keep running").clicked() {
+                    self.quirks_changed = false;
+                }
+            });
+        }
+    }
+
+    /// Rows of 16 bytes. Clicking a byte edits it, and Enter writes the new value.
+    fn memory_ui(&mut self, ui: &mut egui::Ui, emu: &mut Chip8Emulator) {
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+        let rows = emu.memory().len() / ROW_BYTES;
+        let mut write = None;
+        egui::ScrollArea::vertical().max_height(360.0).show_rows(
+            ui,
+            row_height,
+            rows,
+            |ui, range| {
+                for row in range {
+                    ui.horizontal(|ui| {
+                        ui.spacing_mut().item_spacing.x = 4.0;
+                        ui.monospace(format!("{:03X}", row * ROW_BYTES));
+                        for address in row * ROW_BYTES..(row + 1) * ROW_BYTES {
+                            if let Some((editing, text)) = &mut self.editing {
+                                if *editing == address {
+                                    let edit = ui.add(
+                                        egui::TextEdit::singleline(text)
+                                            .font(egui::TextStyle::Monospace)
+                                            .desired_width(row_height),
+                                    );
+                                    if edit.lost_focus() {
+                                        if ui.input().key_pressed(egui::Key::Enter) {
+                                            write = Some((address, text.clone()));
+                                        }
+                                        self.editing = None;
+                                    } else if !edit.has_focus() {
+                                        edit.request_focus();
+                                    }
+                                    continue;
+                                }
+                            }
+                            let value = emu.memory()[address];
+                            let mut text = RichText::new(format!("{:02X}", value)).monospace();
+                            if address == emu.pc() || address == emu.pc() + 1 {
+                                text = text.color(PC_HIGHLIGHT);
+                            } else if address == emu.i() {
+                                text = text.color(I_HIGHLIGHT);
+                            }
+                            if ui
+                                .add(egui::Label::new(text).sense(Sense::click()))
+                                .clicked()
+                            {
+                                self.editing = Some((address, format!("{:02X}", value)));
+                            }
+                        }
+                    });
+                }
+            },
+        );
+
+        if let Some((address, text)) = write {
+            match u8::from_str_radix(text.trim(), 16) {
+                Ok(value) => {
+                    if let Err(e) = emu.write_memory(address, &[value]) {
+                        error!("failed to write memory: {}", e);
+                    }
+                }
+                Err(_) => error!("{} is not a hex byte", text),
+            }
+        }
+    }
+
+    fn breakpoints_ui(&mut self, ui: &mut egui::Ui, emu: &Chip8Emulator, debugger: &mut Debugger) {
+        ui.horizontal(|ui| {
+            let label = if debugger.is_paused() {
+                "Resume"
+            } else {
+                "Pause"
+            };
+            if ui.button(label).clicked() {
+                debugger.toggle_pause();
+            }
+            if ui.button("Step").clicked() {
+                debugger.step();
+            }
+            if ui.button("Step over").clicked() {
+                debugger.step_over(emu);
+            }
+        });
+        ui.separator();
+
+        let mut remove = None;
+        for address in debugger.breakpoints() {
+            ui.horizontal(|ui| {
+                ui.monospace(format!("{:#05X}", address));
+                if ui.small_button("x").clicked() {
+                    remove = Some(address);
+                }
+            });
+        }
+        if let Some(address) = remove {
+            debugger.remove_breakpoint(address);
+        }
+
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.new_breakpoint)
+                    .hint_text("0x200")
+                    .desired_width(60.0),
+            );
+            if ui.button("Add").clicked() {
+                let text = self.new_breakpoint.trim();
+                let hex = text.strip_prefix("0x").unwrap_or(text);
+                match usize::from_str_radix(hex, 16) {
+                    Ok(address) => {
+                        debugger.add_breakpoint(address);
+                        self.new_breakpoint.clear();
+                    }
+                    Err(_) => error!("{} is not a hex address", text),
+                }
+            }
+        });
+    }
+}
+
+/// The screen, and the sprite at I as 8 pixel wide rows.
+fn screen_ui(ui: &mut egui::Ui, emu: &Chip8Emulator, palette: &Palette) {
+    let colors = palette
+        .colors()
+        .map(|[r, g, b, _]| Color32::from_rgb(r, g, b));
+    let (width, height) = (emu.screen_width(), emu.screen_height());
+    let size = 256.0 / width as f32;
+    let (response, painter) = ui.allocate_painter(
+        Vec2::new(width as f32, height as f32) * size,
+        Sense::hover(),
+    );
+    let origin = response.rect.min;
+    painter.rect_filled(response.rect, 0.0, colors[0]);
+    for (i, planes) in emu.screen().iter().enumerate() {
+        if *planes == 0 {
+            continue;
+        }
+        let min = origin + Vec2::new((i % width) as f32, (i / width) as f32) * size;
+        painter.rect_filled(
+            Rect::from_min_size(min, Vec2::splat(size)),
+            0.0,
+            colors[usize::from(*planes & 3)],
+        );
+    }
+
+    ui.separator();
+    ui.monospace(format!("Sprite at I = {:#05X}", emu.i()));
+    let memory = emu.memory();
+    for row in 0..SPRITE_ROWS {
+        let byte = memory[(emu.i() + row) % memory.len()];
+        let bits: String = (0..8)
+            .map(|bit| if byte & (0x80 >> bit) != 0 { '#' } else { '.' })
+            .collect();
+        ui.monospace(format!("{:02X} {}", byte, bits));
+    }
+}
+
+/// The egui key for keys used to edit text.
+fn egui_key(key: VirtualKeyCode) -> Option<egui::Key> {
+    Some(match key {
+        VirtualKeyCode::Left => egui::Key::ArrowLeft,
+        VirtualKeyCode::Right => egui::Key::ArrowRight,
+        VirtualKeyCode::Up => egui::Key::ArrowUp,
+        VirtualKeyCode::Down => egui::Key::ArrowDown,
+        VirtualKeyCode::Escape => egui::Key::Escape,
+        VirtualKeyCode::Tab => egui::Key::Tab,
+        VirtualKeyCode::Back => egui::Key::Backspace,
+        VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter => egui::Key::Enter,
+        VirtualKeyCode::Delete => egui::Key::Delete,
+        VirtualKeyCode::Home => egui::Key::Home,
+        VirtualKeyCode::End => egui::Key::End,
+        VirtualKeyCode::A => egui::Key::A,
+        _ => return None,
+    })
+}
@@ -0,0 +1,213 @@
+use log::info;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+use crate::chip8::Chip8Emulator;
+use crate::opcode::{self, Opcode};
+
+/// A register or memory byte whose changes pause execution.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Watchpoint {
+    Register(usize),
+    Memory(usize),
+}
+
+impl Watchpoint {
+    fn read(&self, emu: &Chip8Emulator) -> u8 {
+        match *self {
+            Watchpoint::Register(reg) => emu.registers()[reg],
+            Watchpoint::Memory(address) => emu.memory()[address % emu.memory().len()],
+        }
+    }
+}
+
+impl FromStr for Watchpoint {
+    type Err = ParseIntError;
+
+    /// Parses a register like `v3` or `VF`, or a memory address like `0x300` or `768`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(reg) = s.strip_prefix('v').or_else(|| s.strip_prefix('V')) {
+            return u8::from_str_radix(reg, 16)
+                .map(|reg| Watchpoint::Register(usize::from(reg & 0xF)));
+        }
+        match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+            Some(hex) => usize::from_str_radix(hex, 16).map(Watchpoint::Memory),
+            None => s.parse().map(Watchpoint::Memory),
+        }
+    }
+}
+
+impl fmt::Display for Watchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Watchpoint::Register(reg) => write!(f, "V{:X}", reg),
+            Watchpoint::Memory(address) => write!(f, "{:#05X}", address),
+        }
+    }
+}
+
+// What to run while paused.
+#[derive(Clone, Copy, Debug)]
+enum Step {
+    // A single instruction.
+    Into,
+    // Until a call returns to where it was made from.
+    Over { return_pc: usize, sp: usize },
+}
+
+/// Pauses the emulator at breakpoints and watchpoints, and runs it an instruction at a time.
+/// Call `before_cycle` before each `emulate_cycle`, which should only run if it returns `true`,
+/// and `after_cycle` after it.
+#[derive(Debug, Default)]
+pub struct Debugger {
+    paused: bool,
+    step: Option<Step>,
+    breakpoints: BTreeSet<usize>,
+    watchpoints: Vec<Watchpoint>,
+    // Values of the watchpoints before the current instruction.
+    watched: Vec<u8>,
+    // The breakpoint execution last stopped at, so resuming doesn't stop there again at once.
+    stopped_at: Option<usize>,
+}
+
+/// Registers, I and the stack pointer on one line, for logging where execution stopped.
+fn describe(emu: &Chip8Emulator) -> String {
+    let registers: Vec<String> = emu
+        .registers()
+        .iter()
+        .enumerate()
+        .map(|(reg, value)| format!("V{:X}={:02X}", reg, value))
+        .collect();
+    format!(
+        "pc={:#05X} I={:#05X} sp={} {}",
+        emu.pc(),
+        emu.i(),
+        emu.sp(),
+        registers.join(" ")
+    )
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Whether execution is paused with no step under way.
+    pub fn is_stopped(&self) -> bool {
+        self.paused && self.step.is_none()
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+        self.step = None;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.step = None;
+    }
+
+    pub fn toggle_pause(&mut self) {
+        if self.paused {
+            self.resume();
+        } else {
+            self.pause();
+        }
+    }
+
+    /// While paused, run the next instruction.
+    pub fn step(&mut self) {
+        self.paused = true;
+        self.step = Some(Step::Into);
+    }
+
+    /// While paused, run the next instruction, or the whole subroutine if it is a call.
+    pub fn step_over(&mut self, emu: &Chip8Emulator) {
+        self.paused = true;
+        let memory = emu.memory();
+        let word = u16::from(memory[emu.pc() % memory.len()]) << 8
+            | u16::from(memory[(emu.pc() + 1) % memory.len()]);
+        self.step = match opcode::from_value(word) {
+            Ok(Opcode::CallSub(_)) => Some(Step::Over {
+                return_pc: emu.pc() + 2,
+                sp: emu.sp(),
+            }),
+            _ => Some(Step::Into),
+        };
+    }
+
+    pub fn add_breakpoint(&mut self, address: usize) {
+        self.breakpoints.insert(address);
+    }
+
+    pub fn remove_breakpoint(&mut self, address: usize) {
+        self.breakpoints.remove(&address);
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = usize> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.push(watchpoint);
+    }
+
+    pub fn remove_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.retain(|watch| *watch != watchpoint);
+    }
+
+    /// Whether the next instruction should run. Stops at breakpoints.
+    pub fn before_cycle(&mut self, emu: &Chip8Emulator) -> bool {
+        if self.paused && self.step.is_none() {
+            return false;
+        }
+        let pc = emu.pc();
+        if self.breakpoints.contains(&pc) && self.stopped_at != Some(pc) {
+            info!("breakpoint: {}", describe(emu));
+            self.stopped_at = Some(pc);
+            self.pause();
+            return false;
+        }
+        self.watched = self
+            .watchpoints
+            .iter()
+            .map(|watch| watch.read(emu))
+            .collect();
+        true
+    }
+
+    /// Stops when a watched value changed or a step finished.
+    pub fn after_cycle(&mut self, emu: &Chip8Emulator) {
+        self.stopped_at = None;
+        for (watch, old) in self.watchpoints.iter().zip(&self.watched) {
+            let new = watch.read(emu);
+            if new != *old {
+                info!(
+                    "{} changed from {:#04X} to {:#04X}: {}",
+                    watch,
+                    old,
+                    new,
+                    describe(emu)
+                );
+                self.paused = true;
+                self.step = None;
+            }
+        }
+        match self.step {
+            Some(Step::Into) => self.step = None,
+            Some(Step::Over { return_pc, sp }) if emu.pc() == return_pc && emu.sp() == sp => {
+                self.step = None
+            }
+            _ => {}
+        }
+        if self.paused && self.step.is_none() {
+            info!("paused: {}", describe(emu));
+        }
+    }
+}
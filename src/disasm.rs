@@ -0,0 +1,324 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use crate::opcode;
+
+/// How an instruction refers to another address.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum RefKind {
+    Call,
+    Jump,
+    Data,
+}
+
+impl RefKind {
+    fn name(self) -> &'static str {
+        match self {
+            RefKind::Call => "call",
+            RefKind::Jump => "jump",
+            RefKind::Data => "data",
+        }
+    }
+
+    fn label_prefix(self) -> &'static str {
+        match self {
+            RefKind::Call => "sub",
+            RefKind::Jump => "label",
+            RefKind::Data => "data",
+        }
+    }
+}
+
+/// Where execution can go after an instruction.
+pub enum Flow {
+    Next,
+    Skip,
+    Stop,
+    Jump(u16),
+    Call(u16),
+}
+
+fn split(word: u16) -> (u8, u8, u8, u8) {
+    (
+        (word >> 12) as u8 & 0xF,
+        (word >> 8) as u8 & 0xF,
+        (word >> 4) as u8 & 0xF,
+        word as u8 & 0xF,
+    )
+}
+
+/// The address referenced by an instruction, if it has one.
+fn reference(word: u16) -> Option<(RefKind, u16)> {
+    let address = word & 0xFFF;
+    match word >> 12 {
+        0x1 | 0xB => Some((RefKind::Jump, address)),
+        0x2 => Some((RefKind::Call, address)),
+        0xA => Some((RefKind::Data, address)),
+        _ => None,
+    }
+}
+
+pub fn flow(word: u16) -> Flow {
+    match split(word) {
+        (0x0, 0x0, 0xE, 0xE) => Flow::Stop,
+        // SUPER-CHIP's EXIT.
+        (0x0, 0x0, 0xF, 0xD) => Flow::Stop,
+        (0x1, ..) => Flow::Jump(word & 0xFFF),
+        (0x2, ..) => Flow::Call(word & 0xFFF),
+        // The target of a computed jump can't be known statically.
+        (0xB, ..) => Flow::Stop,
+        (0x3, ..) | (0x4, ..) | (0x5, _, _, 0x0) | (0x9, ..) | (0xE, ..) => Flow::Skip,
+        _ => Flow::Next,
+    }
+}
+
+/// The instruction set that introduced an opcode.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Platform {
+    Chip8,
+    SuperChip,
+    XoChip,
+}
+
+impl Platform {
+    pub fn name(self) -> &'static str {
+        match self {
+            Platform::Chip8 => "CHIP-8",
+            Platform::SuperChip => "SUPER-CHIP",
+            Platform::XoChip => "XO-CHIP",
+        }
+    }
+}
+
+/// The opcode pattern a word matches (like `8XY4`) and the platform it belongs to, including
+/// the SUPER-CHIP and XO-CHIP extensions. Returns `None` for words that aren't instructions on
+/// any of them.
+pub fn classify(word: u16) -> Option<(&'static str, Platform)> {
+    use Platform::*;
+    let class = match split(word) {
+        (0x0, 0x0, 0xE, 0x0) => ("00E0", Chip8),
+        (0x0, 0x0, 0xE, 0xE) => ("00EE", Chip8),
+        (0x0, 0x0, 0xC, _) => ("00CN", SuperChip),
+        (0x0, 0x0, 0xD, _) => ("00DN", XoChip),
+        (0x0, 0x0, 0xF, 0xB) => ("00FB", SuperChip),
+        (0x0, 0x0, 0xF, 0xC) => ("00FC", SuperChip),
+        (0x0, 0x0, 0xF, 0xD) => ("00FD", SuperChip),
+        (0x0, 0x0, 0xF, 0xE) => ("00FE", SuperChip),
+        (0x0, 0x0, 0xF, 0xF) => ("00FF", SuperChip),
+        (0x0, ..) => ("0NNN", Chip8),
+        (0x1, ..) => ("1NNN", Chip8),
+        (0x2, ..) => ("2NNN", Chip8),
+        (0x3, ..) => ("3XNN", Chip8),
+        (0x4, ..) => ("4XNN", Chip8),
+        (0x5, _, _, 0x0) => ("5XY0", Chip8),
+        (0x5, _, _, 0x2) => ("5XY2", XoChip),
+        (0x5, _, _, 0x3) => ("5XY3", XoChip),
+        (0x6, ..) => ("6XNN", Chip8),
+        (0x7, ..) => ("7XNN", Chip8),
+        (0x8, _, _, 0x0) => ("8XY0", Chip8),
+        (0x8, _, _, 0x1) => ("8XY1", Chip8),
+        (0x8, _, _, 0x2) => ("8XY2", Chip8),
+        (0x8, _, _, 0x3) => ("8XY3", Chip8),
+        (0x8, _, _, 0x4) => ("8XY4", Chip8),
+        (0x8, _, _, 0x5) => ("8XY5", Chip8),
+        (0x8, _, _, 0x6) => ("8XY6", Chip8),
+        (0x8, _, _, 0x7) => ("8XY7", Chip8),
+        (0x8, _, _, 0xE) => ("8XYE", Chip8),
+        (0x9, _, _, 0x0) => ("9XY0", Chip8),
+        (0xA, ..) => ("ANNN", Chip8),
+        (0xB, ..) => ("BNNN", Chip8),
+        (0xC, ..) => ("CXNN", Chip8),
+        (0xD, _, _, 0x0) => ("DXY0", SuperChip),
+        (0xD, ..) => ("DXYN", Chip8),
+        (0xE, _, 0x9, 0xE) => ("EX9E", Chip8),
+        (0xE, _, 0xA, 0x1) => ("EXA1", Chip8),
+        (0xF, 0x0, 0x0, 0x0) => ("F000", XoChip),
+        (0xF, _, 0x0, 0x1) => ("FN01", XoChip),
+        (0xF, 0x0, 0x0, 0x2) => ("F002", XoChip),
+        (0xF, _, 0x0, 0x7) => ("FX07", Chip8),
+        (0xF, _, 0x0, 0xA) => ("FX0A", Chip8),
+        (0xF, _, 0x1, 0x5) => ("FX15", Chip8),
+        (0xF, _, 0x1, 0x8) => ("FX18", Chip8),
+        (0xF, _, 0x1, 0xE) => ("FX1E", Chip8),
+        (0xF, _, 0x2, 0x9) => ("FX29", Chip8),
+        (0xF, _, 0x3, 0x0) => ("FX30", SuperChip),
+        (0xF, _, 0x3, 0x3) => ("FX33", Chip8),
+        (0xF, _, 0x3, 0xA) => ("FX3A", XoChip),
+        (0xF, _, 0x5, 0x5) => ("FX55", Chip8),
+        (0xF, _, 0x6, 0x5) => ("FX65", Chip8),
+        (0xF, _, 0x7, 0x5) => ("FX75", SuperChip),
+        (0xF, _, 0x8, 0x5) => ("FX85", SuperChip),
+        _ => return None,
+    };
+    Some(class)
+}
+
+/// Rows of 16 bytes as hex, each led by its address, for `data` starting at `origin`.
+pub fn hex_dump(data: &[u8], origin: usize) -> String {
+    let mut out = String::new();
+    for (row, bytes) in data.chunks(16).enumerate() {
+        let hex: Vec<String> = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+        writeln!(out, "{:03X}: {}", origin + row * 16, hex.join(" ")).unwrap();
+    }
+    out
+}
+
+/// Format an instruction, using `target` for its address operand. Returns `None` for words
+/// that aren't valid instructions.
+fn mnemonic(word: u16, target: &str) -> Option<String> {
+    opcode::from_value(word).ok().map(|op| op.mnemonic(target))
+}
+
+pub fn is_instruction(word: u16) -> bool {
+    mnemonic(word, "").is_some()
+}
+
+/// Format a single instruction on its own, with its address operand as a plain number.
+pub fn format_instruction(word: u16) -> Option<String> {
+    opcode::from_value(word).ok().map(|op| op.to_string())
+}
+
+/// A ROM loaded at `origin`, with its code separated from its data and every referenced
+/// address collected.
+pub struct Analysis<'a> {
+    rom: &'a [u8],
+    origin: u16,
+    // Addresses where reachable instructions start.
+    code: BTreeSet<u16>,
+    // Referenced address -> (kind of reference, address of the referencing instruction).
+    xrefs: BTreeMap<u16, Vec<(RefKind, u16)>>,
+}
+
+impl<'a> Analysis<'a> {
+    pub fn new(rom: &'a [u8], origin: u16) -> Self {
+        let mut analysis = Self {
+            rom,
+            origin,
+            code: BTreeSet::new(),
+            xrefs: BTreeMap::new(),
+        };
+        analysis.trace_from(origin);
+        analysis
+    }
+
+    pub fn word_at(&self, address: u16) -> Option<u16> {
+        let offset = usize::from(address.checked_sub(self.origin)?);
+        let bytes = self.rom.get(offset..offset + 2)?;
+        Some(u16::from(bytes[0]) << 8 | u16::from(bytes[1]))
+    }
+
+    /// Follow every path execution can take from `entry`, marking the instructions reached.
+    fn trace_from(&mut self, entry: u16) {
+        let mut pending = vec![entry];
+        while let Some(address) = pending.pop() {
+            if self.code.contains(&address) {
+                continue;
+            }
+            let word = match self.word_at(address) {
+                Some(word) if is_instruction(word) => word,
+                _ => continue,
+            };
+            self.code.insert(address);
+            // XO-CHIP's `i := long NNNN` is followed by its 16 bit operand, which the listing
+            // shows as data.
+            if word == 0xF000 {
+                pending.push(address.wrapping_add(4));
+                continue;
+            }
+
+            if let Some((kind, target)) = reference(word) {
+                self.xrefs.entry(target).or_default().push((kind, address));
+            }
+            match flow(word) {
+                Flow::Next => pending.push(address.wrapping_add(2)),
+                Flow::Skip => pending.extend([address.wrapping_add(2), address.wrapping_add(4)]),
+                Flow::Stop => {}
+                Flow::Jump(target) => pending.push(target),
+                Flow::Call(target) => pending.extend([target, address.wrapping_add(2)]),
+            }
+        }
+    }
+
+    /// Every reachable instruction as `(address, word)`, in address order.
+    pub fn instructions(&self) -> Vec<(u16, u16)> {
+        self.code
+            .iter()
+            .map(|address| (*address, self.word_at(*address).unwrap()))
+            .collect()
+    }
+
+    /// Addresses loaded into I by the code, which is usually where sprites are stored.
+    pub fn data_references(&self) -> Vec<u16> {
+        self.xrefs
+            .iter()
+            .filter(|(_, refs)| refs.iter().any(|(kind, _)| *kind == RefKind::Data))
+            .map(|(address, _)| *address)
+            .collect()
+    }
+
+    /// Addresses that begin a line of the listing: each instruction, and each data byte that
+    /// isn't part of an instruction.
+    fn line_starts(&self) -> BTreeSet<u16> {
+        let mut starts = BTreeSet::new();
+        // Bytes past the end of the address space can't be reached, so the listing stops there.
+        let end = (u32::from(self.origin) + self.rom.len() as u32).min(0x10000);
+        let mut address = u32::from(self.origin);
+        while address < end {
+            let start = address as u16;
+            starts.insert(start);
+            address += if self.code.contains(&start) { 2 } else { 1 };
+        }
+        starts
+    }
+
+    fn label(&self, address: u16) -> String {
+        // Calls take precedence over jumps, and jumps over data.
+        let kind = self.xrefs[&address].iter().map(|(kind, _)| *kind).min();
+        format!("{}_{:03X}", kind.unwrap().label_prefix(), address)
+    }
+}
+
+/// Disassemble a ROM loaded at `origin` into a listing that can be re-assembled.
+///
+/// Code reachable from `origin` is decoded into mnemonics and everything else is emitted as
+/// `db` bytes, so the listing reproduces the ROM byte for byte. Every referenced address gets
+/// a label, annotated with the instructions that refer to it.
+pub fn disassemble(rom: &[u8], origin: u16) -> String {
+    let analysis = Analysis::new(rom, origin);
+    let line_starts = analysis.line_starts();
+
+    // Only addresses at the start of a line can be labelled. Anything else (e.g. a jump into
+    // the middle of an instruction, or outside the ROM) stays a plain number.
+    let target = |address: u16| {
+        if analysis.xrefs.contains_key(&address) && line_starts.contains(&address) {
+            analysis.label(address)
+        } else {
+            format!("{:#05X}", address)
+        }
+    };
+
+    let mut out = String::new();
+    for &address in &line_starts {
+        if let Some(refs) = analysis.xrefs.get(&address) {
+            let sources: Vec<String> = refs
+                .iter()
+                .map(|(kind, from)| format!("{:#05X} ({})", from, kind.name()))
+                .collect();
+            writeln!(out).unwrap();
+            writeln!(out, "; referenced from {}", sources.join(", ")).unwrap();
+            writeln!(out, "{}:", analysis.label(address)).unwrap();
+        }
+
+        if analysis.code.contains(&address) {
+            let word = analysis.word_at(address).unwrap();
+            let operand = target(word & 0xFFF);
+            let text = mnemonic(word, &operand).unwrap();
+            writeln!(out, "    {:<24}; {:#05X}: {:04X}", text, address, word).unwrap();
+        } else {
+            let byte = rom[usize::from(address - origin)];
+            let text = format!("db {:#04X}", byte);
+            writeln!(out, "    {:<24}; {:#05X}: {:08b}", text, address, byte).unwrap();
+        }
+    }
+    out
+}
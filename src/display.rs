@@ -0,0 +1,133 @@
+use clap::{Args, ValueEnum};
+
+use chip8emu::palette::Palette;
+
+/// Display settings for players who find the small 64x32 screen hard to read.
+#[derive(Args)]
+pub struct DisplayOptions {
+    /// Pure white on opaque black, whatever colors the ROM asks for.
+    #[arg(long)]
+    pub high_contrast: bool,
+    /// Brightness of lit pixels in percent, 100 for their full color.
+    #[arg(long, default_value_t = 100)]
+    pub on_brightness: u8,
+    /// How far unlit pixels are lifted towards white in percent, 0 for none.
+    #[arg(long, default_value_t = 0)]
+    pub off_brightness: u8,
+    /// Draw each lit pixel as a slightly larger block.
+    #[arg(long)]
+    pub thick_pixels: bool,
+    /// Let pixels fade out over a few frames like an old phosphor screen, which hides most
+    /// flicker. Toggled with F8.
+    #[arg(long)]
+    pub phosphor: bool,
+    /// Darken a line under each row of pixels like a CRT's scanlines. Toggled with F9.
+    #[arg(long)]
+    pub scanlines: bool,
+    /// Leave a thin gap between pixels like an LCD. Toggled with F11.
+    #[arg(long)]
+    pub pixel_grid: bool,
+    /// Bend the picture like the curved glass of an old CRT.
+    #[arg(long)]
+    pub curvature: bool,
+    /// Order to apply the effects that are on in, as a comma-separated list. Effects left out
+    /// are applied after the listed ones, in the default order.
+    #[arg(
+        long,
+        value_name = "LIST",
+        value_delimiter = ',',
+        default_value = "phosphor,colors,scanlines,grid,curvature"
+    )]
+    pub effects: Vec<Effect>,
+}
+
+/// A step of the chain of effects the screen is drawn through. `Colors` is the brightness and
+/// contrast settings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Effect {
+    Phosphor,
+    Colors,
+    Scanlines,
+    Grid,
+    Curvature,
+}
+
+const DEFAULT_ORDER: [Effect; 5] = [
+    Effect::Phosphor,
+    Effect::Colors,
+    Effect::Scanlines,
+    Effect::Grid,
+    Effect::Curvature,
+];
+
+impl Default for DisplayOptions {
+    fn default() -> Self {
+        Self {
+            high_contrast: false,
+            on_brightness: 100,
+            off_brightness: 0,
+            thick_pixels: false,
+            phosphor: false,
+            scanlines: false,
+            pixel_grid: false,
+            curvature: false,
+            effects: DEFAULT_ORDER.to_vec(),
+        }
+    }
+}
+
+impl DisplayOptions {
+    /// The effects that are on, in the order to apply them.
+    pub fn chain(&self) -> Vec<Effect> {
+        let mut chain: Vec<Effect> = Vec::new();
+        for effect in self.effects.iter().chain(&DEFAULT_ORDER) {
+            if !chain.contains(effect) {
+                chain.push(*effect);
+            }
+        }
+        chain.retain(|effect| match effect {
+            Effect::Phosphor => self.phosphor,
+            Effect::Colors => {
+                self.high_contrast || self.on_brightness != 100 || self.off_brightness > 0
+            }
+            Effect::Scanlines => self.scanlines,
+            Effect::Grid => self.pixel_grid,
+            Effect::Curvature => self.curvature,
+        });
+        chain
+    }
+
+    /// The colors the `Colors` effect turns the ROM's into, for things drawn straight to the
+    /// window rather than through the chain.
+    pub fn palette(&self, palette: Palette) -> Palette {
+        let mut palette = if self.high_contrast {
+            Palette {
+                foreground: [0xff; 4],
+                background: [0, 0, 0, 0xff],
+                plane_2: [0xff; 4],
+                both_planes: [0xff; 4],
+            }
+        } else {
+            palette
+        };
+
+        let on = u32::from(self.on_brightness.min(100));
+        for color in [
+            &mut palette.foreground,
+            &mut palette.plane_2,
+            &mut palette.both_planes,
+        ] {
+            for channel in &mut color[..3] {
+                *channel = (u32::from(*channel) * on / 100) as u8;
+            }
+        }
+        if self.off_brightness > 0 {
+            let off = u32::from(self.off_brightness.min(100));
+            for channel in &mut palette.background[..3] {
+                *channel += ((0xff - u32::from(*channel)) * off / 100) as u8;
+            }
+            palette.background[3] = 0xff;
+        }
+        palette
+    }
+}
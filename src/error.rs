@@ -0,0 +1,86 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use crate::chip8::Variant;
+
+/// Why a ROM couldn't be loaded or an instruction couldn't be executed. Faults leave the
+/// machine as it was before the instruction, so a frontend can stop and show them.
+#[derive(Debug)]
+pub enum Chip8Error {
+    /// The word at `pc` doesn't decode to any instruction.
+    InvalidOpcode { opcode: u16, pc: usize },
+    /// The instruction exists, but only in a later dialect than the one being run.
+    UnsupportedOpcode {
+        opcode: u16,
+        pc: usize,
+        variant: Variant,
+    },
+    /// A call with all 16 stack entries in use.
+    StackOverflow { pc: usize },
+    /// A return with nothing on the stack.
+    StackUnderflow { pc: usize },
+    /// An instruction at `pc` accessed memory past the end at `address`.
+    MemoryOutOfBounds { address: usize, pc: usize },
+    /// A ROM or file of `size` bytes doesn't fit in the `available` bytes of memory.
+    RomTooLarge { size: usize, available: usize },
+    /// A movie being played back reached a different state than when it was recorded, at the
+    /// end of `frame`, e.g. because it was recorded with other quirks or another version.
+    Desync { frame: usize },
+    /// The ROM or another file couldn't be read.
+    Io(io::Error),
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Chip8Error::InvalidOpcode { opcode, pc } => {
+                write!(
+                    f,
+                    "{:#06X} is not a recognized opcode (pc: {:#X})",
+                    opcode, pc
+                )
+            }
+            Chip8Error::UnsupportedOpcode {
+                opcode,
+                pc,
+                variant,
+            } => write!(
+                f,
+                "{:#06X} is a {:?} instruction (pc: {:#X})",
+                opcode, variant, pc
+            ),
+            Chip8Error::StackOverflow { pc } => write!(f, "stack overflow (pc: {:#X})", pc),
+            Chip8Error::StackUnderflow { pc } => {
+                write!(f, "return with an empty stack (pc: {:#X})", pc)
+            }
+            Chip8Error::MemoryOutOfBounds { address, pc } => write!(
+                f,
+                "memory access out of bounds at {:#X} (pc: {:#X})",
+                address, pc
+            ),
+            Chip8Error::RomTooLarge { size, available } => write!(
+                f,
+                "{} bytes do not fit in the {} bytes of memory available",
+                size, available
+            ),
+            Chip8Error::Desync { frame } => write!(f, "desync at frame {}", frame),
+            Chip8Error::Io(e) => e.fmt(f),
+        }
+    }
+}
+
+impl Error for Chip8Error {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Chip8Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Chip8Error {
+    fn from(e: io::Error) -> Self {
+        Chip8Error::Io(e)
+    }
+}
@@ -0,0 +1,46 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use chip8emu::{Chip8Emulator, Chip8Observer};
+
+/// What the machine has done that the window reacts to, collected through its observer hooks
+/// instead of checking the machine every frame.
+#[derive(Clone, Default)]
+pub struct MachineEvents {
+    // Set when the screen changes, until the window is drawn.
+    drawn: Arc<AtomicBool>,
+    // Between the buzzer starting and stopping.
+    beeping: Arc<AtomicBool>,
+}
+
+impl MachineEvents {
+    /// Start following a machine, in place of any followed before.
+    pub fn attach(&self, emu: &mut Chip8Emulator) {
+        self.drawn.store(true, Ordering::Relaxed);
+        self.beeping.store(emu.is_beeping(), Ordering::Relaxed);
+        emu.add_observer(Box::new(self.clone()));
+    }
+
+    /// Whether the screen has changed since the last call.
+    pub fn take_drawn(&self) -> bool {
+        self.drawn.swap(false, Ordering::Relaxed)
+    }
+
+    pub fn is_beeping(&self) -> bool {
+        self.beeping.load(Ordering::Relaxed)
+    }
+}
+
+impl Chip8Observer for MachineEvents {
+    fn on_draw(&mut self, _emu: &Chip8Emulator) {
+        self.drawn.store(true, Ordering::Relaxed);
+    }
+
+    fn on_sound_start(&mut self, _emu: &Chip8Emulator) {
+        self.beeping.store(true, Ordering::Relaxed);
+    }
+
+    fn on_sound_stop(&mut self, _emu: &Chip8Emulator) {
+        self.beeping.store(false, Ordering::Relaxed);
+    }
+}
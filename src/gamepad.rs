@@ -0,0 +1,240 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+use crate::keymap::parse_key;
+
+/// A gamepad button, by its gilrs name, or a direction of one of the analog sticks.
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Button {
+    South,
+    East,
+    North,
+    West,
+    LeftTrigger,
+    LeftTrigger2,
+    RightTrigger,
+    RightTrigger2,
+    Select,
+    Start,
+    Mode,
+    LeftThumb,
+    RightThumb,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+    LeftStickUp,
+    LeftStickDown,
+    LeftStickLeft,
+    LeftStickRight,
+    RightStickUp,
+    RightStickDown,
+    RightStickLeft,
+    RightStickRight,
+}
+
+/// How the analog sticks press the keys their directions are mapped to.
+#[derive(Clone, Copy, Debug)]
+pub struct Stick {
+    /// How far from the middle, from 0 to 1, a stick can move without pressing anything.
+    pub deadzone: f32,
+    /// Whether pushing a stick diagonally presses the keys of both directions, or only the key
+    /// of the nearer one.
+    pub diagonals: bool,
+}
+
+impl Default for Stick {
+    fn default() -> Self {
+        Self {
+            deadzone: 0.3,
+            diagonals: true,
+        }
+    }
+}
+
+impl Stick {
+    /// Whether a stick at `x`, `y` (each from -1 to 1, with up positive) is pushed up, down,
+    /// left and right.
+    #[cfg_attr(not(feature = "gamepad"), allow(dead_code))]
+    fn directions(&self, x: f32, y: f32) -> [bool; 4] {
+        if x.hypot(y) < self.deadzone {
+            return [false; 4];
+        }
+        let (x_held, y_held) = if self.diagonals {
+            // Within 22.5 degrees of a diagonal, both directions count, which splits the
+            // circle into eight equal parts.
+            let threshold = x.abs().max(y.abs()) * 22.5f32.to_radians().tan();
+            (x.abs() >= threshold, y.abs() >= threshold)
+        } else {
+            (x.abs() > y.abs(), y.abs() >= x.abs())
+        };
+        [
+            y_held && y > 0.0,
+            y_held && y < 0.0,
+            x_held && x < 0.0,
+            x_held && x > 0.0,
+        ]
+    }
+}
+
+/// One button, or several that all press the same CHIP-8 key.
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum ButtonBinding {
+    One(Button),
+    Many(Vec<Button>),
+}
+
+/// Which gamepad buttons press each CHIP-8 key. Read from a table like `2 = "DPadUp"` keyed by
+/// CHIP-8 key in hex; unlike keyboard bindings, a table replaces the whole default mapping so
+/// profiles don't end up with buttons pressing two keys.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(try_from = "BTreeMap<String, ButtonBinding>")]
+pub struct GamepadMap {
+    // Only read when polling gamepads, which builds without the feature don't do.
+    #[cfg_attr(not(feature = "gamepad"), allow(dead_code))]
+    bindings: [Vec<Button>; 16],
+}
+
+impl GamepadMap {
+    /// The D-pad and left stick on 2/4/6/8, the usual movement keys, and the face buttons on
+    /// keys nearby.
+    pub fn dpad() -> Self {
+        use Button::*;
+        let mut bindings: [Vec<Button>; 16] = Default::default();
+        for (key, button) in [
+            (0x2, DPadUp),
+            (0x4, DPadLeft),
+            (0x6, DPadRight),
+            (0x8, DPadDown),
+            (0x2, LeftStickUp),
+            (0x4, LeftStickLeft),
+            (0x6, LeftStickRight),
+            (0x8, LeftStickDown),
+            (0x5, South),
+            (0xA, East),
+            (0xB, North),
+            (0x0, West),
+            (0xE, Select),
+            (0xF, Start),
+        ] {
+            bindings[key].push(button);
+        }
+        Self { bindings }
+    }
+}
+
+impl Default for GamepadMap {
+    fn default() -> Self {
+        Self::dpad()
+    }
+}
+
+impl TryFrom<BTreeMap<String, ButtonBinding>> for GamepadMap {
+    type Error = String;
+
+    fn try_from(table: BTreeMap<String, ButtonBinding>) -> Result<Self, Self::Error> {
+        let mut bindings: [Vec<Button>; 16] = Default::default();
+        for (key, binding) in table {
+            bindings[parse_key(&key)?] = match binding {
+                ButtonBinding::One(button) => vec![button],
+                ButtonBinding::Many(buttons) => buttons,
+            };
+        }
+        Ok(Self { bindings })
+    }
+}
+
+/// Connected gamepads. Pads can be plugged in and out while running.
+pub struct Gamepads {
+    #[cfg(feature = "gamepad")]
+    gilrs: gilrs::Gilrs,
+}
+
+#[cfg(feature = "gamepad")]
+impl Gamepads {
+    pub fn open() -> Option<Self> {
+        match gilrs::Gilrs::new() {
+            Ok(gilrs) => {
+                for (_, pad) in gilrs.gamepads() {
+                    log::info!("gamepad connected: {}", pad.name());
+                }
+                Some(Self { gilrs })
+            }
+            Err(e) => {
+                log::error!("failed to open gamepads: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Press the CHIP-8 keys whose buttons are held, on top of `keys`. The second pad to be
+    /// connected goes by the second map, so that two players can each have their own keys, and
+    /// every other pad by the first.
+    pub fn poll(&mut self, maps: [&GamepadMap; 2], stick: &Stick, keys: &mut [bool; 16]) {
+        while let Some(gilrs::Event { id, event, .. }) = self.gilrs.next_event() {
+            let name = self.gilrs.gamepad(id).name().to_string();
+            match event {
+                gilrs::EventType::Connected => log::info!("gamepad connected: {}", name),
+                gilrs::EventType::Disconnected => log::info!("gamepad disconnected: {}", name),
+                _ => {}
+            }
+        }
+        for (n, (_, pad)) in self.gilrs.gamepads().enumerate() {
+            let map = maps[usize::from(n == 1)];
+            let sticks = [
+                (gilrs::Axis::LeftStickX, gilrs::Axis::LeftStickY),
+                (gilrs::Axis::RightStickX, gilrs::Axis::RightStickY),
+            ]
+            .map(|(x, y)| stick.directions(pad.value(x), pad.value(y)));
+            for (held, buttons) in keys.iter_mut().zip(&map.bindings) {
+                *held |= buttons.iter().any(|button| button.is_held(&pad, &sticks));
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "gamepad"))]
+impl Gamepads {
+    pub fn open() -> Option<Self> {
+        None
+    }
+
+    pub fn poll(&mut self, _maps: [&GamepadMap; 2], _stick: &Stick, _keys: &mut [bool; 16]) {}
+}
+
+#[cfg(feature = "gamepad")]
+impl Button {
+    /// Whether the button is held on `pad`, given which directions its left and right sticks
+    /// are pushed in.
+    fn is_held(self, pad: &gilrs::Gamepad, sticks: &[[bool; 4]; 2]) -> bool {
+        let button = match self {
+            Button::LeftStickUp => return sticks[0][0],
+            Button::LeftStickDown => return sticks[0][1],
+            Button::LeftStickLeft => return sticks[0][2],
+            Button::LeftStickRight => return sticks[0][3],
+            Button::RightStickUp => return sticks[1][0],
+            Button::RightStickDown => return sticks[1][1],
+            Button::RightStickLeft => return sticks[1][2],
+            Button::RightStickRight => return sticks[1][3],
+            Button::South => gilrs::Button::South,
+            Button::East => gilrs::Button::East,
+            Button::North => gilrs::Button::North,
+            Button::West => gilrs::Button::West,
+            Button::LeftTrigger => gilrs::Button::LeftTrigger,
+            Button::LeftTrigger2 => gilrs::Button::LeftTrigger2,
+            Button::RightTrigger => gilrs::Button::RightTrigger,
+            Button::RightTrigger2 => gilrs::Button::RightTrigger2,
+            Button::Select => gilrs::Button::Select,
+            Button::Start => gilrs::Button::Start,
+            Button::Mode => gilrs::Button::Mode,
+            Button::LeftThumb => gilrs::Button::LeftThumb,
+            Button::RightThumb => gilrs::Button::RightThumb,
+            Button::DPadUp => gilrs::Button::DPadUp,
+            Button::DPadDown => gilrs::Button::DPadDown,
+            Button::DPadLeft => gilrs::Button::DPadLeft,
+            Button::DPadRight => gilrs::Button::DPadRight,
+        };
+        pad.is_pressed(button)
+    }
+}
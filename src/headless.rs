@@ -0,0 +1,158 @@
+use serde::Serialize;
+use sha1::{Digest, Sha1};
+
+use crate::chip8::{Chip8Emulator, StepOutcome};
+use crate::clock::Clock;
+use crate::error::Chip8Error;
+use crate::movie::Movie;
+
+/// The state of the machine at the end of a headless run.
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct Report {
+    /// Instructions executed.
+    pub cycles: u64,
+    /// SHA-1 of the screen as PBM, see `screen_pbm()`.
+    pub screen_sha1: String,
+    pub pc: usize,
+    pub i: usize,
+    pub v: [u8; 16],
+    pub stack: Vec<u16>,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+}
+
+impl Report {
+    pub fn of(emu: &Chip8Emulator, cycles: u64) -> Self {
+        Self {
+            cycles,
+            screen_sha1: Sha1::digest(screen_pbm(emu))
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect(),
+            pc: emu.pc(),
+            i: emu.i(),
+            v: *emu.registers(),
+            stack: emu.stack().to_vec(),
+            delay_timer: emu.delay_timer(),
+            sound_timer: emu.sound_timer(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+}
+
+/// Execute `cycles` instructions of a loaded ROM at the clock's rate, ticking the timers
+/// between frames as a frontend would. Stops early if the ROM exits. With VIP timing the last
+/// frame runs whole, so a few more may run.
+pub fn run_headless(
+    emu: &mut Chip8Emulator,
+    cycles: u64,
+    mut clock: Clock,
+) -> Result<Report, Chip8Error> {
+    let mut executed = 0;
+    while executed < cycles && !emu.is_halted() {
+        let frame = u64::from(clock.instructions_this_frame().max(1)).min(cycles - executed);
+        executed += run_frame(emu, &mut clock, frame as u32)?;
+    }
+    Ok(Report::of(emu, executed))
+}
+
+/// Run a loaded ROM for `frames` 60 Hz frames at the clock's rate, ticking the timers after
+/// each. Frames go by even while the ROM waits for a key or has exited.
+pub fn run_frames(
+    emu: &mut Chip8Emulator,
+    frames: u32,
+    mut clock: Clock,
+) -> Result<(), Chip8Error> {
+    for _ in 0..frames {
+        clock.run_frame(emu)?;
+        emu.tick_timers();
+    }
+    Ok(())
+}
+
+/// Play a movie back on a loaded ROM with random numbers from its seed: each frame, hold its
+/// keys, run the clock's instructions and tick the timers. Stops early if the ROM exits, and
+/// fails with `Chip8Error::Desync` once the machine no longer matches the movie's state hashes.
+pub fn run_movie(
+    emu: &mut Chip8Emulator,
+    movie: &Movie,
+    mut clock: Clock,
+) -> Result<Report, Chip8Error> {
+    emu.set_rng_seed(movie.seed);
+    let mut executed = 0;
+    for (n, keys) in (0..movie.len()).map_while(|n| Some((n, movie.frame(n)?))) {
+        if emu.is_halted() {
+            break;
+        }
+        emu.set_keys(&keys);
+        let frame = clock.instructions_this_frame();
+        executed += run_frame(emu, &mut clock, frame)?;
+        movie.check_frame(n, emu)?;
+    }
+    Ok(Report::of(emu, executed))
+}
+
+/// Run a frame's instructions, or with VIP timing a frame's machine cycles, and tick the
+/// timers, returning how many instructions the frame took. Time spent waiting for a key counts
+/// as the whole frame, so runs without input still end.
+pub(crate) fn run_frame(
+    emu: &mut Chip8Emulator,
+    clock: &mut Clock,
+    instructions: u32,
+) -> Result<u64, Chip8Error> {
+    let before = emu.executed();
+    let outcome = if clock.is_vip() {
+        clock.run_frame(emu)?
+    } else {
+        emu.run_for(instructions)?
+    };
+    let executed = emu.executed() - before;
+    let executed = match outcome {
+        StepOutcome::WaitingForKey => executed.max(u64::from(instructions)),
+        _ => executed,
+    };
+    emu.tick_timers();
+    Ok(executed)
+}
+
+/// The screen as a plain (text) PBM image, one image pixel per CHIP-8 pixel, so that golden
+/// images can be diffed line by line.
+pub fn screen_pbm(emu: &Chip8Emulator) -> Vec<u8> {
+    let width = emu.screen_width();
+    let mut pbm = format!("P1\n{} {}\n", width, emu.screen_height());
+    for row in emu.screen().chunks(width) {
+        let bits: Vec<&str> = row
+            .iter()
+            .map(|planes| if *planes != 0 { "1" } else { "0" })
+            .collect();
+        pbm.push_str(&bits.join(" "));
+        pbm.push('\n');
+    }
+    pbm.into_bytes()
+}
+
+/// The screen as a grayscale PNG, one image pixel per CHIP-8 pixel.
+pub fn screen_png(emu: &Chip8Emulator) -> Vec<u8> {
+    let pixels: Vec<u8> = emu
+        .screen()
+        .iter()
+        .map(|planes| if *planes != 0 { 0xff } else { 0 })
+        .collect();
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(
+        &mut png,
+        emu.screen_width() as u32,
+        emu.screen_height() as u32,
+    );
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    // Writing to a Vec can't fail.
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&pixels))
+        .unwrap();
+    png
+}
@@ -0,0 +1,192 @@
+use log::{info, warn};
+use serde::Deserialize;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use chip8emu::chip8::Chip8Emulator;
+
+// How many scores are kept in a ROM's leaderboard.
+const LEADERBOARD_SIZE: usize = 10;
+// XO-CHIP's address space, the largest of any variant.
+const MEMORY_SIZE: usize = 0x10000;
+
+/// How the score bytes are laid out in memory.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum Encoding {
+    /// One decimal digit per byte, most significant first, as written by FX33.
+    Bcd,
+    /// A big-endian unsigned integer.
+    Binary,
+}
+
+/// Per-ROM score declaration, read from `<rom>.score.toml`.
+///
+/// ```toml
+/// address = 0x2F0
+/// length = 3
+/// encoding = "bcd"
+/// ```
+#[derive(Deserialize)]
+struct ScoreRegion {
+    address: usize,
+    length: usize,
+    encoding: Encoding,
+}
+
+impl ScoreRegion {
+    /// Why the declaration can't describe a score, if it can't.
+    fn check(&self) -> Result<(), String> {
+        let digits = match self.encoding {
+            Encoding::Bcd => 9,
+            Encoding::Binary => 4,
+        };
+        if self.length == 0 {
+            Err("the score needs a length of at least 1".to_string())
+        } else if self.length > digits {
+            Err(format!(
+                "a score {} bytes long doesn't fit in 32 bits",
+                self.length
+            ))
+        } else if self
+            .address
+            .checked_add(self.length)
+            .is_none_or(|end| end > MEMORY_SIZE)
+        {
+            Err(format!(
+                "the score at {:#X} runs past the end of memory",
+                self.address
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn read(&self, memory: &[u8]) -> Option<u32> {
+        let bytes = memory.get(self.address..self.address.checked_add(self.length)?)?;
+        let base = match self.encoding {
+            Encoding::Bcd => 10,
+            Encoding::Binary => 256,
+        };
+        bytes.iter().try_fold(0u32, |acc, b| {
+            acc.checked_mul(base)?.checked_add(u32::from(*b))
+        })
+    }
+}
+
+/// Watches a ROM's score variable while it runs and keeps a local leaderboard of the best
+/// values across sessions, since CHIP-8 games have no way of saving on their own.
+pub struct ScoreWatch {
+    region: ScoreRegion,
+    leaderboard_path: PathBuf,
+    // Highest score seen this session. The game usually resets the score at game over, so
+    // sampling every frame catches it before it disappears.
+    session_best: Option<u32>,
+}
+
+impl ScoreWatch {
+    /// Load the score declaration that sits next to the ROM, if there is one.
+    pub fn for_rom(rom_path: &str) -> Option<Self> {
+        let config_path = PathBuf::from(format!("{}.score.toml", rom_path));
+        let text = fs::read_to_string(&config_path).ok()?;
+        let region = toml::from_str::<ScoreRegion>(&text)
+            .map_err(|e| e.to_string())
+            .and_then(|region| region.check().map(|()| region));
+        let region = match region {
+            Ok(region) => region,
+            Err(e) => {
+                warn!("ignoring {}: {}", config_path.display(), e);
+                return None;
+            }
+        };
+
+        let watch = Self {
+            region,
+            leaderboard_path: PathBuf::from(format!("{}.hiscores", rom_path)),
+            session_best: None,
+        };
+        if let Some(best) = watch.load_leaderboard().first() {
+            info!("high score to beat: {}", best);
+        }
+        Some(watch)
+    }
+
+    /// Record the score currently held in memory.
+    pub fn sample(&mut self, emu: &Chip8Emulator) {
+        if let Some(score) = self.region.read(emu.memory()) {
+            self.session_best = self.session_best.max(Some(score));
+        }
+    }
+
+    /// Persist this session's best score and print the leaderboard.
+    pub fn finish(&self) -> io::Result<()> {
+        let mut scores = self.load_leaderboard();
+        if let Some(best) = self.session_best.filter(|score| *score > 0) {
+            scores.push(best);
+            scores.sort_unstable_by(|a, b| b.cmp(a));
+            scores.truncate(LEADERBOARD_SIZE);
+
+            let text: String = scores.iter().map(|score| format!("{}\n", score)).collect();
+            fs::write(&self.leaderboard_path, text)?;
+        }
+
+        println!("High scores:");
+        for (rank, score) in scores.iter().enumerate() {
+            let marker = if Some(*score) == self.session_best {
+                " <- this session"
+            } else {
+                ""
+            };
+            println!("{:>3}. {}{}", rank + 1, score, marker);
+        }
+        Ok(())
+    }
+
+    fn load_leaderboard(&self) -> Vec<u32> {
+        fs::read_to_string(&self.leaderboard_path)
+            .map(|text| {
+                text.lines()
+                    .filter_map(|line| line.trim().parse().ok())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region(address: usize, length: usize, encoding: Encoding) -> ScoreRegion {
+        ScoreRegion {
+            address,
+            length,
+            encoding,
+        }
+    }
+
+    #[test]
+    fn scores_are_read_from_memory() {
+        let mut memory = vec![0; 0x1000];
+        memory[0x2F0..0x2F3].copy_from_slice(&[1, 2, 3]);
+        assert_eq!(region(0x2F0, 3, Encoding::Bcd).read(&memory), Some(123));
+        assert_eq!(
+            region(0x2F0, 2, Encoding::Binary).read(&memory),
+            Some(0x102)
+        );
+        assert_eq!(region(0xFFF, 2, Encoding::Bcd).read(&memory), None);
+        assert_eq!(region(usize::MAX, 2, Encoding::Bcd).read(&memory), None);
+    }
+
+    #[test]
+    fn bad_declarations_are_rejected() {
+        assert!(region(0x2F0, 3, Encoding::Bcd).check().is_ok());
+        assert!(region(0xFFFC, 4, Encoding::Binary).check().is_ok());
+        assert!(region(0x2F0, 0, Encoding::Bcd).check().is_err());
+        assert!(region(0x2F0, 5, Encoding::Binary).check().is_err());
+        assert!(region(0x2F0, 10, Encoding::Bcd).check().is_err());
+        assert!(region(0xFFFF, 2, Encoding::Bcd).check().is_err());
+        assert!(region(usize::MAX, 1, Encoding::Bcd).check().is_err());
+    }
+}
@@ -0,0 +1,154 @@
+use sha1::{Digest, Sha1};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use chip8emu::chip8::Variant;
+use chip8emu::disasm::{self, Analysis, Platform};
+
+/// Keys a ROM checks with EX9E/EXA1, found by looking for the `LD VX, NN` that sets up the
+/// key register just before the check. Returns the keys found and whether any check used a
+/// register set some other way.
+fn referenced_keys(instructions: &[(u16, u16)]) -> (BTreeSet<u8>, bool) {
+    let mut keys = BTreeSet::new();
+    let mut computed = false;
+    for (i, (address, word)) in instructions.iter().enumerate() {
+        if !matches!(word & 0xF0FF, 0xE09E | 0xE0A1) {
+            continue;
+        }
+        let reg = (word >> 8) & 0xF;
+        let setup = instructions[i.saturating_sub(3)..i]
+            .iter()
+            .rev()
+            .take_while(|(prev, _)| address - prev <= 6)
+            .find(|(_, prev)| prev & 0xFF00 == 0x6000 | reg << 8);
+        match setup {
+            Some((_, prev)) if prev & 0xFF <= 0xF => {
+                keys.insert((prev & 0xF) as u8);
+            }
+            _ => computed = true,
+        }
+    }
+    (keys, computed)
+}
+
+/// SHA-1 of a ROM as lowercase hex, as used by ROM databases.
+pub fn sha1_hex(rom: &[u8]) -> String {
+    Sha1::digest(rom)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// The most capable platform whose instructions the ROM uses.
+pub fn detected_platform(rom: &[u8], origin: u16) -> Platform {
+    Analysis::new(rom, origin)
+        .instructions()
+        .iter()
+        .filter_map(|(_, word)| disasm::classify(*word))
+        .map(|(_, platform)| platform)
+        .max()
+        .unwrap_or(Platform::Chip8)
+}
+
+/// The dialect to run a ROM as. ROMs using extensions other than XO-CHIP's get SUPER-CHIP,
+/// which covers most of them.
+pub fn guess_variant(rom: &[u8], origin: u16) -> Variant {
+    match detected_platform(rom, origin) {
+        Platform::Chip8 => Variant::Chip8,
+        Platform::XoChip => Variant::XoChip,
+        _ => Variant::SuperChip,
+    }
+}
+
+/// Summarize what a ROM loaded at `origin` contains and needs from the emulator.
+pub fn inspect(name: &str, rom: &[u8], origin: u16) -> String {
+    let analysis = Analysis::new(rom, origin);
+    let instructions = analysis.instructions();
+    let end = usize::from(origin) + rom.len();
+
+    let mut histogram: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut extensions: BTreeMap<Platform, BTreeSet<&str>> = BTreeMap::new();
+    for (_, word) in &instructions {
+        let (pattern, platform) = disasm::classify(*word).unwrap();
+        *histogram.entry(pattern).or_default() += 1;
+        if platform != Platform::Chip8 {
+            extensions.entry(platform).or_default().insert(pattern);
+        }
+    }
+
+    let mut out = String::new();
+    writeln!(out, "File:     {}", name).unwrap();
+    writeln!(
+        out,
+        "Size:     {} bytes ({:#05X}-{:#05X})",
+        rom.len(),
+        origin,
+        end.saturating_sub(1)
+    )
+    .unwrap();
+    writeln!(out, "SHA-1:    {}", sha1_hex(rom)).unwrap();
+
+    if extensions.is_empty() {
+        writeln!(out, "Platform: CHIP-8 (no extension instructions found)").unwrap();
+    } else {
+        for (platform, patterns) in &extensions {
+            let patterns: Vec<&str> = patterns.iter().copied().collect();
+            writeln!(
+                out,
+                "Platform: {} ({})",
+                platform.name(),
+                patterns.join(", ")
+            )
+            .unwrap();
+        }
+    }
+
+    let (keys, computed) = referenced_keys(&instructions);
+    let mut key_names: Vec<String> = keys.iter().map(|key| format!("{:X}", key)).collect();
+    if computed {
+        key_names.push("computed at runtime".to_string());
+    }
+    if histogram.contains_key("FX0A") {
+        key_names.push("any key (FX0A)".to_string());
+    }
+    if key_names.is_empty() {
+        key_names.push("none".to_string());
+    }
+    writeln!(out, "Keys:     {}", key_names.join(", ")).unwrap();
+
+    // A sprite region runs from where I points to the next instruction or sprite.
+    writeln!(out, "Sprite data:").unwrap();
+    let code: BTreeSet<u16> = instructions.iter().map(|(address, _)| *address).collect();
+    let sprites: BTreeSet<u16> = analysis
+        .data_references()
+        .into_iter()
+        .filter(|address| (usize::from(origin)..end).contains(&usize::from(*address)))
+        .collect();
+    if sprites.is_empty() {
+        writeln!(out, "    none").unwrap();
+    }
+    for start in &sprites {
+        let next_boundary = code
+            .range(start + 1..)
+            .chain(sprites.range(start + 1..))
+            .min()
+            .map_or(end, |address| usize::from(*address));
+        let len = next_boundary - usize::from(*start);
+        writeln!(
+            out,
+            "    {:#05X}-{:#05X} ({} bytes)",
+            start,
+            next_boundary - 1,
+            len
+        )
+        .unwrap();
+    }
+
+    writeln!(out, "Opcodes:").unwrap();
+    let mut counts: Vec<_> = histogram.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    for (pattern, count) in counts {
+        writeln!(out, "    {}  {:>4}", pattern, count).unwrap();
+    }
+    out
+}
@@ -0,0 +1,197 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use winit::event::VirtualKeyCode;
+use winit_input_helper::WinitInputHelper;
+
+/// One key, or several that all press the same CHIP-8 key.
+#[derive(Deserialize, Clone)]
+#[serde(untagged)]
+pub enum Binding {
+    One(VirtualKeyCode),
+    Many(Vec<VirtualKeyCode>),
+}
+
+/// Which keyboard keys press each of the 16 CHIP-8 keys.
+#[derive(Clone)]
+pub struct KeyMap {
+    // How the map was chosen, for the window title.
+    name: String,
+    bindings: [Vec<VirtualKeyCode>; 16],
+}
+
+impl KeyMap {
+    /// The COSMAC VIP keypad laid over the left of a QWERTY keyboard:
+    ///
+    /// ```text
+    /// 1 2 3 C      1 2 3 4
+    /// 4 5 6 D  ->  Q W E R
+    /// 7 8 9 E      A S D F
+    /// A 0 B F      Z X C V
+    /// ```
+    pub fn cosmac() -> Self {
+        use VirtualKeyCode::*;
+        let keys = [X, Key1, Key2, Key3, Q, W, E, A, S, D, Z, C, Key4, R, F, V];
+        Self {
+            name: "cosmac".to_string(),
+            bindings: keys.map(|key| vec![key]),
+        }
+    }
+
+    /// Each CHIP-8 key on the keyboard key with the same label, 0-9 and A-F.
+    pub fn hex() -> Self {
+        use VirtualKeyCode::*;
+        let keys = [
+            Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9, A, B, C, D, E, F,
+        ];
+        Self {
+            name: "hex".to_string(),
+            bindings: keys.map(|key| vec![key]),
+        }
+    }
+
+    /// Load bindings from a TOML file, e.g. `5 = "Space"` or `0 = ["X", "Numpad0"]`, keyed by
+    /// CHIP-8 key in hex. Key names are winit's `VirtualKeyCode` names. Keys that aren't listed
+    /// keep their COSMAC layout binding.
+    pub fn load(path: &str) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let table: BTreeMap<String, Binding> =
+            toml::from_str(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let map =
+            Self::from_table(table).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(map.with_name(path))
+    }
+
+    /// Bindings keyed by CHIP-8 key in hex, on top of the COSMAC layout.
+    pub fn from_table(table: BTreeMap<String, Binding>) -> Result<Self, String> {
+        let mut map = Self::cosmac();
+        for (key, binding) in table {
+            map.bindings[parse_key(&key)?] = match binding {
+                Binding::One(code) => vec![code],
+                Binding::Many(codes) => codes,
+            };
+        }
+        Ok(map)
+    }
+
+    /// Bindings named by the ASCII letter or digit on each keyboard key, as `.c8b` files store
+    /// them, on top of the COSMAC layout. `None` if no key is bound.
+    pub fn from_ascii(keys: [Option<char>; 16]) -> Option<Self> {
+        if keys.iter().all(Option::is_none) {
+            return None;
+        }
+        let mut map = Self::cosmac().with_name("c8b");
+        for (binding, ascii) in map.bindings.iter_mut().zip(keys) {
+            if let Some(code) = ascii.and_then(code_for_ascii) {
+                *binding = vec![code];
+            }
+        }
+        Some(map)
+    }
+
+    /// The first key bound to each CHIP-8 key that has an ASCII letter or digit on it.
+    pub fn to_ascii(&self) -> [Option<char>; 16] {
+        self.bindings
+            .each_ref()
+            .map(|codes| codes.iter().find_map(|code| ascii_for_code(*code)))
+    }
+
+    /// Parse a `--keymap` argument: `cosmac`, `hex`, or the path of a bindings file.
+    pub fn from_arg(arg: &str) -> io::Result<Self> {
+        match arg {
+            "cosmac" => Ok(Self::cosmac()),
+            "hex" => Ok(Self::hex()),
+            path => Self::load(path),
+        }
+    }
+
+    /// Rename the map, as shown in the window title.
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The CHIP-8 keys currently held down.
+    pub fn held(&self, input: &WinitInputHelper) -> [bool; 16] {
+        self.bindings
+            .each_ref()
+            .map(|codes| codes.iter().any(|code| input.key_held(*code)))
+    }
+}
+
+// Keyboard keys labelled with an ASCII letter or digit.
+const ASCII_KEYS: [(char, VirtualKeyCode); 36] = {
+    use VirtualKeyCode::*;
+    [
+        ('0', Key0),
+        ('1', Key1),
+        ('2', Key2),
+        ('3', Key3),
+        ('4', Key4),
+        ('5', Key5),
+        ('6', Key6),
+        ('7', Key7),
+        ('8', Key8),
+        ('9', Key9),
+        ('A', A),
+        ('B', B),
+        ('C', C),
+        ('D', D),
+        ('E', E),
+        ('F', F),
+        ('G', G),
+        ('H', H),
+        ('I', I),
+        ('J', J),
+        ('K', K),
+        ('L', L),
+        ('M', M),
+        ('N', N),
+        ('O', O),
+        ('P', P),
+        ('Q', Q),
+        ('R', R),
+        ('S', S),
+        ('T', T),
+        ('U', U),
+        ('V', V),
+        ('W', W),
+        ('X', X),
+        ('Y', Y),
+        ('Z', Z),
+    ]
+};
+
+fn code_for_ascii(ascii: char) -> Option<VirtualKeyCode> {
+    let ascii = ascii.to_ascii_uppercase();
+    ASCII_KEYS
+        .iter()
+        .find(|(label, _)| *label == ascii)
+        .map(|(_, code)| *code)
+}
+
+fn ascii_for_code(code: VirtualKeyCode) -> Option<char> {
+    ASCII_KEYS
+        .iter()
+        .find(|(_, key)| *key == code)
+        .map(|(label, _)| *label)
+}
+
+/// Parse a CHIP-8 key written in hex, `0` to `F`.
+pub fn parse_key(key: &str) -> Result<usize, String> {
+    usize::from_str_radix(key, 16)
+        .ok()
+        .filter(|key| *key <= 0xF)
+        .ok_or_else(|| format!("{} is not a CHIP-8 key, expected 0-F", key))
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        Self::cosmac()
+    }
+}
@@ -0,0 +1,88 @@
+use std::time::{Duration, Instant};
+
+use chip8emu::chip8::Chip8Emulator;
+
+// How long a key stays highlighted after the ROM last polled it.
+const POLL_HIGHLIGHT: Duration = Duration::from_secs(1);
+
+// Keys in the order they appear on the COSMAC VIP hex keypad.
+const LAYOUT: [[usize; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+
+// Each key is drawn as a 3x3 block with a 1 pixel border around it.
+const CELL: usize = 3;
+const PANEL_SIZE: usize = 4 * (CELL + 1) + 1;
+
+const BACKGROUND: [u8; 4] = [0x20, 0x20, 0x20, 0xff];
+const IDLE: [u8; 4] = [0x50, 0x50, 0x50, 0xff];
+const HELD: [u8; 4] = [0xff, 0xff, 0xff, 0xff];
+const POLLED: [u8; 4] = [0xff, 0xc0, 0x00, 0xff];
+const HELD_AND_POLLED: [u8; 4] = [0x40, 0xff, 0x40, 0xff];
+
+/// Debug panel showing the keypad: which keys are held and which ones the running ROM has
+/// recently polled, so it is easy to see which controls a game actually uses.
+pub struct KeypadPanel {
+    pub visible: bool,
+    last_polled: [Option<Instant>; 16],
+}
+
+impl KeypadPanel {
+    pub fn new() -> Self {
+        Self {
+            visible: false,
+            last_polled: [None; 16],
+        }
+    }
+
+    /// Collect the keys polled by the ROM since the last update.
+    pub fn update(&mut self, emu: &mut Chip8Emulator) {
+        let now = Instant::now();
+        for (polled, last) in emu
+            .take_polled_keys()
+            .iter()
+            .zip(self.last_polled.iter_mut())
+        {
+            if *polled {
+                *last = Some(now);
+            }
+        }
+    }
+
+    /// Draw the panel into the bottom right corner of an RGBA frame `width` pixels wide.
+    pub fn draw(&self, emu: &Chip8Emulator, frame: &mut [u8], width: usize) {
+        if !self.visible {
+            return;
+        }
+
+        let height = frame.len() / 4 / width;
+        let left = width - PANEL_SIZE;
+        let top = height - PANEL_SIZE;
+        let now = Instant::now();
+
+        for y in 0..PANEL_SIZE {
+            for x in 0..PANEL_SIZE {
+                let color = match (x % (CELL + 1), y % (CELL + 1)) {
+                    (0, _) | (_, 0) => BACKGROUND,
+                    _ => {
+                        let key = LAYOUT[y / (CELL + 1)][x / (CELL + 1)];
+                        let held = emu.keys()[key];
+                        let polled = self.last_polled[key]
+                            .is_some_and(|t| now.duration_since(t) < POLL_HIGHLIGHT);
+                        match (held, polled) {
+                            (true, true) => HELD_AND_POLLED,
+                            (true, false) => HELD,
+                            (false, true) => POLLED,
+                            (false, false) => IDLE,
+                        }
+                    }
+                };
+                let idx = 4 * ((top + y) * width + left + x);
+                frame[idx..idx + 4].copy_from_slice(&color);
+            }
+        }
+    }
+}
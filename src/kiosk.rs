@@ -0,0 +1,77 @@
+use log::info;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Attract mode for unattended machines: cycles through a playlist of ROMs, and hands control
+/// back to the playlist once a player has left a game alone for a while.
+pub struct Kiosk {
+    playlist: Vec<PathBuf>,
+    current: usize,
+    // How long each ROM is shown while nobody is playing.
+    dwell: Duration,
+    // How long without input before a game is considered abandoned.
+    idle_timeout: Duration,
+    switched_at: Instant,
+    last_input: Option<Instant>,
+}
+
+impl Kiosk {
+    /// Load a playlist: one ROM path per line, relative to the playlist itself. Blank lines and
+    /// lines starting with `#` are ignored.
+    pub fn from_playlist(path: &str, dwell: Duration, idle_timeout: Duration) -> io::Result<Self> {
+        let dir = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+        let playlist: Vec<PathBuf> = fs::read_to_string(path)?
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| dir.join(line))
+            .collect();
+        if playlist.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the playlist has no ROMs",
+            ));
+        }
+
+        Ok(Self {
+            playlist,
+            current: 0,
+            dwell,
+            idle_timeout,
+            switched_at: Instant::now(),
+            last_input: None,
+        })
+    }
+
+    pub fn current_rom(&self) -> &Path {
+        &self.playlist[self.current]
+    }
+
+    /// Record that a player pressed something, which pauses the cycling.
+    pub fn input(&mut self) {
+        self.last_input = Some(Instant::now());
+    }
+
+    /// Returns the next ROM to load when it is time to move on.
+    pub fn poll(&mut self) -> Option<PathBuf> {
+        let now = Instant::now();
+        match self.last_input {
+            Some(last_input) if now.duration_since(last_input) < self.idle_timeout => return None,
+            Some(_) => {
+                info!(
+                    "no input for {:?}, returning to attract mode",
+                    self.idle_timeout
+                );
+                self.last_input = None;
+            }
+            None if now.duration_since(self.switched_at) < self.dwell => return None,
+            None => {}
+        }
+
+        self.current = (self.current + 1) % self.playlist.len();
+        self.switched_at = now;
+        Some(self.current_rom().to_path_buf())
+    }
+}
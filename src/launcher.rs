@@ -0,0 +1,137 @@
+use log::warn;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::overlay::{self, ADVANCE, LINE_HEIGHT};
+
+/// File extensions ROMs are recognized by.
+pub const ROM_EXTENSIONS: [&str; 6] = ["ch8", "c8", "sc8", "xo8", "rom", "c8b"];
+
+// Rows taken by the heading above the list.
+const HEADER_ROWS: usize = 2;
+
+/// ROM picker shown when the emulator is started without a ROM: the recently played ROMs,
+/// followed by the rest of the ROM directory.
+pub struct Launcher {
+    // Each ROM, and the name it is listed under.
+    roms: Vec<(PathBuf, String)>,
+    selected: usize,
+    // First ROM shown, once the list is longer than the screen.
+    scroll: usize,
+}
+
+impl Launcher {
+    /// List the ROMs under their `title`, if they have one, or their file names.
+    pub fn new(
+        rom_dir: &Path,
+        recent: &[PathBuf],
+        title: impl Fn(&Path) -> Option<String>,
+    ) -> Self {
+        let mut roms: Vec<PathBuf> = recent.iter().filter(|rom| rom.is_file()).cloned().collect();
+        match scan(rom_dir) {
+            Ok(found) => {
+                for rom in found {
+                    let canonical = fs::canonicalize(&rom).unwrap_or_else(|_| rom.clone());
+                    if !roms.contains(&canonical) {
+                        roms.push(rom);
+                    }
+                }
+            }
+            Err(e) => warn!("failed to list ROMs in {}: {}", rom_dir.display(), e),
+        }
+        let roms = roms
+            .into_iter()
+            .map(|rom| {
+                let name = title(&rom).unwrap_or_else(|| {
+                    let stem = rom.file_stem().unwrap_or(rom.as_os_str());
+                    stem.to_string_lossy().into_owned()
+                });
+                (rom, name)
+            })
+            .collect();
+        Self {
+            roms,
+            selected: 0,
+            scroll: 0,
+        }
+    }
+
+    /// Move the selection by `delta` ROMs, stopping at either end of the list.
+    pub fn move_selection(&mut self, delta: isize) {
+        let last = self.roms.len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(delta).min(last);
+    }
+
+    pub fn selected(&self) -> Option<&Path> {
+        self.roms.get(self.selected).map(|(rom, _)| rom.as_path())
+    }
+
+    /// Draw the list over the whole of an RGBA buffer `width` pixels wide, in the palette's
+    /// colors.
+    pub fn draw(
+        &mut self,
+        buffer: &mut [u8],
+        width: usize,
+        foreground: [u8; 4],
+        background: [u8; 4],
+    ) {
+        for pixel in buffer.chunks_exact_mut(4) {
+            pixel.copy_from_slice(&background);
+        }
+        let scale = (width / 256).max(1);
+        let height = buffer.len() / 4 / width;
+        let columns = width / (ADVANCE * scale) - 1;
+        let rows = (height / (LINE_HEIGHT * scale))
+            .saturating_sub(HEADER_ROWS)
+            .max(1);
+
+        let heading = if self.roms.is_empty() {
+            "NO ROMS FOUND, DROP ONE HERE"
+        } else {
+            "PICK A ROM, ENTER TO PLAY"
+        };
+        overlay::draw_text(buffer, width, (scale, scale), scale, heading, foreground);
+
+        // Keep the selection on screen.
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + rows {
+            self.scroll = self.selected + 1 - rows;
+        }
+        for (row, (_, name)) in self.roms.iter().enumerate().skip(self.scroll).take(rows) {
+            let top = (HEADER_ROWS + row - self.scroll) * LINE_HEIGHT * scale + scale;
+            let name: String = name.chars().take(columns).collect();
+            let color = if row == self.selected {
+                // Highlight the selected ROM by swapping the colors of its row.
+                let band = top - scale..top + (LINE_HEIGHT - 1) * scale;
+                for pixel in
+                    buffer[4 * width * band.start..4 * width * band.end].chunks_exact_mut(4)
+                {
+                    pixel.copy_from_slice(&foreground);
+                }
+                background
+            } else {
+                foreground
+            };
+            overlay::draw_text(buffer, width, (scale, top), scale, &name, color);
+        }
+    }
+}
+
+/// ROM files directly inside `dir`, sorted by name.
+fn scan(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut roms = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_rom = path.extension().is_some_and(|extension| {
+            ROM_EXTENSIONS
+                .iter()
+                .any(|rom| extension.eq_ignore_ascii_case(rom))
+        });
+        if is_rom && path.is_file() {
+            roms.push(path);
+        }
+    }
+    roms.sort();
+    Ok(roms)
+}
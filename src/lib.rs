@@ -0,0 +1,41 @@
+//! CHIP-8 emulator core, free of any windowing, rendering or audio dependencies so that it can
+//! be embedded in other frontends, tests or tools.
+//!
+//! ```no_run
+//! let mut emu = chip8emu::Chip8Emulator::new();
+//! emu.initialize();
+//! emu.load_game("pong.rom").unwrap();
+//! loop {
+//!     // A frame's instructions, or fewer if the ROM waits for a key or the display.
+//!     emu.run_for(8).unwrap();
+//!     emu.tick_timers();
+//!     let mut frame = vec![0; 4 * emu.screen().len()];
+//!     emu.draw_screen(&mut frame, [0xff; 4], [0, 0, 0, 0xff]);
+//! }
+//! ```
+
+pub mod asm;
+pub mod beeper;
+pub mod c8b;
+pub mod chip8;
+pub mod clock;
+pub mod compare;
+pub mod debugger;
+pub mod disasm;
+pub mod error;
+pub mod headless;
+pub mod movie;
+pub mod opcode;
+pub mod palette;
+pub mod quirks;
+pub mod timeline;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use beeper::Beeper;
+pub use chip8::{Chip8Emulator, Chip8Observer};
+pub use error::Chip8Error;
+pub use headless::run_headless;
+pub use opcode::Opcode;
+pub use palette::Palette;
+pub use quirks::Quirks;
@@ -1,84 +1,1323 @@
-use game_loop::{game_loop, Time, TimeTrait as _};
-use log::error;
+use chip8emu::beeper::{Beeper, NullBeeper};
+use chip8emu::c8b::{self, C8b};
+use chip8emu::chip8::StepOutcome;
+use chip8emu::clock::{Clock, CycleTable, TIMER_HZ};
+use chip8emu::compare::Comparison;
+use chip8emu::debugger::Debugger;
+use chip8emu::movie::Movie;
+use chip8emu::palette::{self, Palette};
+use chip8emu::quirks::Quirks;
+use chip8emu::timeline::Timeline;
+use chip8emu::Chip8Error;
+use chip8emu::{asm, chip8, disasm, headless};
+use game_loop::{game_loop, GameLoop, Time, TimeTrait as _};
+use log::{error, info, warn};
 use pixels::{Pixels, SurfaceTexture};
-use std::env;
-use std::time::Duration;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
 use winit::{
-    dpi::LogicalSize, event::VirtualKeyCode, event_loop::EventLoop, window::WindowBuilder,
+    dpi::LogicalSize,
+    event::{Event, VirtualKeyCode},
+    event_loop::EventLoopBuilder,
+    window::{Fullscreen, Window, WindowBuilder},
 };
 use winit_input_helper::WinitInputHelper;
 
-mod chip8;
+#[cfg(feature = "audio")]
+mod audio;
+mod callgraph;
+mod capture;
+mod cli;
+mod clipboard;
+mod config;
+mod debug_server;
+#[cfg(feature = "debug-ui")]
+mod debug_ui;
+mod display;
+mod events;
+mod gamepad;
+mod highscore;
+mod inspect;
+mod keymap;
+mod keypad_panel;
+mod kiosk;
+mod launcher;
+mod net;
+mod octo;
+mod overlay;
+mod patch;
+mod plugin;
+mod profile;
+mod render;
+mod romdb;
+#[cfg(feature = "scripting")]
+mod script;
+mod server;
+mod signals;
+mod single_instance;
+mod test_roms;
+mod trace;
+#[cfg(feature = "tui")]
+mod tui;
 
 struct Game {
     /// Emulator.
     emu: chip8::Chip8Emulator,
+    /// Draws and buzzer changes reported by the emulator.
+    events: events::MachineEvents,
     /// Software renderer.
     pixels: Pixels,
     /// Event manager.
     input: WinitInputHelper,
+    /// Score tracking, for ROMs that declare where their score lives.
+    score: Option<highscore::ScoreWatch>,
+    /// Keypad debug panel, toggled with F2.
+    keypad_panel: keypad_panel::KeypadPanel,
+    /// Call graph being recorded, and the path it is written to on exit.
+    callgraph: Option<(callgraph::CallGraph, String)>,
+    /// Execution timeline being recorded, and the path it is written to on exit.
+    profiler: Option<(profile::Profiler, String)>,
+    /// JSON Lines execution trace.
+    trace: Option<trace::TraceWriter>,
+    /// CPU clock for the loaded ROM.
+    clock: Clock,
+    /// Clock rate given on the command line, which takes priority over the ROM's own settings.
+    clock_override: Option<Clock>,
+    /// Colors the screen is drawn in.
+    palette: Palette,
+    /// Screenshots copied with F3, and save states copied and pasted with Shift+F5 and
+    /// Shift+F7.
+    clipboard: clipboard::SystemClipboard,
+    /// Playlist being cycled through in kiosk mode.
+    kiosk: Option<kiosk::Kiosk>,
+    /// ROM picker shown until a ROM is loaded, when started without one.
+    launcher: Option<launcher::Launcher>,
+    /// What memory outside the ROM holds when a ROM is loaded.
+    memory_fill: chip8::MemoryFill,
+    /// Instruction costs from --cycle-table.
+    cycle_table: Option<Arc<CycleTable>>,
+    /// Address ROMs are loaded at and start running from.
+    load_address: usize,
+    /// Seed for the ROM's random numbers, given on the command line.
+    seed: Option<u64>,
+    /// Colors given on the command line or picked with F4, instead of the ROM's.
+    palette_override: Option<Palette>,
+    /// Name, hash and platform of the loaded ROM.
+    rom_info: String,
+    /// Whether the window title shows the effective configuration and the debug overlay is
+    /// drawn, toggled with F1.
+    show_info: bool,
+    /// Registers, next instruction and speed drawn over the screen.
+    overlay: overlay::Overlay,
+    /// Instructions executed since starting, for measuring the speed.
+    executed: u64,
+    /// Input and output integrations loaded from shared libraries.
+    plugins: Vec<plugin::Plugin>,
+    /// Accessibility settings and effects for how the screen is drawn.
+    display: display::DisplayOptions,
+    /// Scales the screen up to the window's buffer, applying the display effects.
+    renderer: render::Renderer,
+    /// Extra files loaded into memory after each ROM, and their addresses.
+    segments: Vec<(String, usize)>,
+    /// IPS or BPS patches applied to each ROM as it is loaded.
+    patches: Vec<String>,
+    /// SIGUSR1 dump and SIGTERM/SIGINT exit requests.
+    signals: Option<signals::Signals>,
+    /// Buzzer output.
+    beeper: Box<dyn Beeper>,
+    /// The second player's connection, with --host.
+    host: Option<net::Host>,
+    /// Resolution the pixel buffer was last sized for.
+    screen_size: (usize, usize),
+    /// Whether the window needs drawing again even if the screen hasn't changed, because of
+    /// a resize or a change to how the screen is shown.
+    redraw: bool,
+    /// Dialect given on the command line, instead of guessing it from each ROM.
+    variant_override: Option<chip8::Variant>,
+    /// Quirks preset given on the command line, instead of the variant's.
+    quirks_override: Option<Quirks>,
+    /// Single quirks switched on or off on the command line, applied on top of the preset.
+    quirk_toggles: Vec<(String, bool)>,
+    /// How the quirks of the machine run alongside with --compare differ.
+    compare: Vec<cli::QuirkChange>,
+    /// The machine run alongside with --compare, started afresh with each ROM.
+    comparison: Option<Comparison>,
+    /// Breakpoints, watchpoints and stepping.
+    debugger: Debugger,
+    /// Script hooked into the machine with --script.
+    #[cfg(feature = "scripting")]
+    script: Option<script::Script>,
+    /// The client of `--debug-server` that last sent a command, which is told when execution
+    /// stops.
+    debug_client: Option<mpsc::Sender<String>>,
+    /// Whether execution was stopped when the client last heard about it.
+    debug_stopped: bool,
+    /// Path of the loaded ROM, which save states are stored next to.
+    rom_path: String,
+    /// Save state slot used by F5 and F7, chosen with F6.
+    state_slot: u8,
+    /// Keyboard keys for each CHIP-8 key.
+    keymap: keymap::KeyMap,
+    /// Key bindings given on the command line, instead of the ROM's or the config's.
+    keymap_override: Option<keymap::KeyMap>,
+    /// Connected gamepads, whose buttons press keys along with the keyboard.
+    gamepads: Option<gamepad::Gamepads>,
+    /// Gamepad buttons for each CHIP-8 key, which can be set per ROM.
+    gamepad_map: gamepad::GamepadMap,
+    /// Buttons on the second pad, for two-player ROMs, when they aren't the first pad's.
+    gamepad2_map: Option<gamepad::GamepadMap>,
+    /// How the analog sticks press keys.
+    stick: gamepad::Stick,
+    /// Why execution stopped, if the ROM did something the machine can't do.
+    fault: Option<Chip8Error>,
+    /// Settings file, for the defaults and per-ROM settings applied as each ROM is loaded.
+    config: config::Config,
+    /// Debugger windows, toggled with the backquote key.
+    #[cfg(feature = "debug-ui")]
+    debug_ui: debug_ui::DebugUi,
+    /// Animated GIF of the screen being recorded, toggled with Shift+F12.
+    gif: Option<capture::GifRecorder>,
+    /// Movie being recorded, and the path it is written to on exit.
+    recording: Option<(Movie, PathBuf)>,
+    /// Movie being played back instead of taking input.
+    playback: Option<Playback>,
+    /// Whether to run as fast as possible rather than in real time, while Tab is held.
+    turbo: bool,
+    /// Whether to run a single frame at the next update while paused.
+    advance_frame: bool,
+}
+
+/// A movie being played back, kept after its last frame so that it can still be sought
+/// through and edited.
+struct Playback {
+    timeline: Timeline,
+    /// Where the movie was read from, and edits are saved to.
+    #[cfg(feature = "debug-ui")]
+    path: PathBuf,
+    /// The next frame to play.
+    frame: usize,
+    /// Whether playback ran out of frames or desynced, handing input back to the keyboard.
+    finished: bool,
 }
 
 impl Game {
-    fn new(pixels: Pixels, rom_path: &str) -> Self {
+    /// Set up the machine and everything around it from the options, failing if a file they
+    /// name can't be read or created.
+    fn new(
+        pixels: Pixels,
+        window: &Window,
+        options: cli::Options,
+        config: config::Config,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let beeper = open_beeper(&options, &config);
+        let clock_override = options.clock();
+        let kiosk = if options.kiosk {
+            let playlist = options
+                .rom_path
+                .as_deref()
+                .ok_or("--kiosk needs a playlist")?;
+            let kiosk = kiosk::Kiosk::from_playlist(
+                playlist,
+                Duration::from_secs(options.kiosk_seconds),
+                Duration::from_secs(options.kiosk_idle),
+            )
+            .map_err(|e| format!("{}: {}", playlist, e))?;
+            Some(kiosk)
+        } else {
+            None
+        };
+        let rom_path = match &kiosk {
+            Some(kiosk) => Some(kiosk.current_rom().to_string_lossy().into_owned()),
+            None => options.rom_path.clone(),
+        };
+        // Without a ROM to start with, the player picks one.
+        let launcher = rom_path.is_none().then(|| {
+            let rom_dir = config.rom_dir.clone().unwrap_or_else(|| PathBuf::from("."));
+            launcher::Launcher::new(&rom_dir, &config.recent, |rom| {
+                let data = std::fs::read(rom).ok()?;
+                config.rom(&inspect::sha1_hex(&data)).title
+            })
+        });
+
+        #[cfg(feature = "debug-ui")]
+        let debug_ui = {
+            let mut debug_ui =
+                debug_ui::DebugUi::new(&pixels, window.inner_size(), window.scale_factor());
+            debug_ui.visible = options.view;
+            debug_ui
+        };
+        #[cfg(not(feature = "debug-ui"))]
+        let _ = window;
+
+        let playback = options
+            .playback
+            .map(|path| {
+                let movie = std::fs::read(&path)
+                    .and_then(|data| Movie::from_bytes(&data))
+                    .map_err(|e| format!("{}: {}", path.display(), e))?;
+                // Started over once the ROM is loaded.
+                let start = chip8::Chip8Emulator::new();
+                let timeline = Timeline::new(movie, &start, Clock::from_hz(DEFAULT_CLOCK_HZ));
+                Ok::<_, String>(Playback {
+                    timeline,
+                    #[cfg(feature = "debug-ui")]
+                    path,
+                    frame: 0,
+                    finished: false,
+                })
+            })
+            .transpose()?;
+        let callgraph = options
+            .callgraph_path
+            .map(|path| {
+                let rom_path = rom_path.as_deref().ok_or("--callgraph needs a ROM")?;
+                let rom = std::fs::read(rom_path).map_err(|e| format!("{}: {}", rom_path, e))?;
+                let graph = callgraph::CallGraph::from_rom(&rom, options.load_addr as u16);
+                Ok::<_, String>((graph, path))
+            })
+            .transpose()?;
+        let trace = options
+            .trace_json_path
+            .map(|path| trace::TraceWriter::create(&path).map_err(|e| format!("{}: {}", path, e)))
+            .transpose()?;
+        // Movies only play back the same if the random numbers do too.
+        let seed = match &playback {
+            Some(playback) => Some(playback.timeline.movie().seed),
+            None if options.record.is_some() => Some(options.seed.unwrap_or_else(rand::random)),
+            None => options.seed,
+        };
+
+        let mut game = Self {
+            emu: chip8::Chip8Emulator::new(),
+            events: events::MachineEvents::default(),
+            pixels,
+            input: WinitInputHelper::new(),
+            score: None,
+            keypad_panel: keypad_panel::KeypadPanel::new(),
+            callgraph,
+            profiler: options
+                .profile_path
+                .map(|path| (profile::Profiler::new(rom_path.as_deref().unwrap()), path)),
+            trace,
+            clock: Clock::from_hz(DEFAULT_CLOCK_HZ),
+            clock_override,
+            palette: Palette::default(),
+            clipboard: clipboard::SystemClipboard::new(),
+            kiosk,
+            launcher,
+            memory_fill: options.memory_fill,
+            cycle_table: options.cycle_table.clone(),
+            load_address: options.load_addr,
+            seed,
+            palette_override: options.palette,
+            rom_info: String::new(),
+            show_info: false,
+            overlay: overlay::Overlay::new(),
+            executed: 0,
+            plugins: options
+                .plugins
+                .iter()
+                .filter_map(|path| match plugin::Plugin::load(path) {
+                    Ok(plugin) => Some(plugin),
+                    Err(e) => {
+                        error!("failed to load plugin {}: {}", path, e);
+                        None
+                    }
+                })
+                .collect(),
+            display: options.display,
+            renderer: render::Renderer::new(),
+            segments: options.segments,
+            patches: options.patches,
+            signals: signals::Signals::register()
+                .map_err(|e| error!("failed to install signal handlers: {}", e))
+                .ok(),
+            beeper,
+            host: options.host.as_deref().and_then(|address| {
+                net::Host::listen(address)
+                    .map_err(|e| error!("failed to host on {}: {}", address, e))
+                    .ok()
+            }),
+            screen_size: (WIDTH as usize, HEIGHT as usize),
+            redraw: true,
+            variant_override: options.variant,
+            quirks_override: options.quirks,
+            quirk_toggles: options.quirk_toggles,
+            compare: options.compare,
+            comparison: None,
+            rom_path: String::new(),
+            state_slot: 0,
+            keymap: options
+                .keymap
+                .clone()
+                .or_else(|| config.keymap())
+                .unwrap_or_default(),
+            keymap_override: options.keymap,
+            gamepads: gamepad::Gamepads::open(),
+            gamepad_map: gamepad::GamepadMap::default(),
+            gamepad2_map: None,
+            stick: {
+                let default = gamepad::Stick::default();
+                gamepad::Stick {
+                    deadzone: config
+                        .stick_deadzone
+                        .unwrap_or(default.deadzone)
+                        .clamp(0.0, 1.0),
+                    diagonals: config.stick_diagonals.unwrap_or(default.diagonals),
+                }
+            },
+            fault: None,
+            config,
+            #[cfg(feature = "debug-ui")]
+            debug_ui,
+            recording: options
+                .record
+                .map(|path| (Movie::new(seed.unwrap_or_default(), &[]), path)),
+            playback,
+            gif: None,
+            turbo: false,
+            advance_frame: false,
+            debugger: {
+                let mut debugger = Debugger::new();
+                for address in options.breakpoints {
+                    debugger.add_breakpoint(address);
+                }
+                for watchpoint in options.watchpoints {
+                    debugger.add_watchpoint(watchpoint);
+                }
+                if options.view {
+                    debugger.pause();
+                }
+                debugger
+            },
+            debug_client: None,
+            debug_stopped: false,
+            #[cfg(feature = "scripting")]
+            script: None,
+        };
+        if let Some(rom_path) = &rom_path {
+            game.load_rom(rom_path)
+                .map_err(|e| format!("{}: {}", rom_path, e))?;
+        }
+        #[cfg(feature = "scripting")]
+        if let Some(path) = &options.script {
+            match script::Script::load(path, &mut game.emu) {
+                Ok(script) => game.script = Some(script),
+                Err(e) => error!("failed to load script {}", e),
+            }
+        }
+        #[cfg(not(feature = "scripting"))]
+        if options.script.is_some() {
+            warn!("ignoring --script, this build has no scripting support");
+        }
+        Ok(game)
+    }
+
+    /// Reset the emulator and load a ROM, along with any settings that come with it.
+    fn load_rom(&mut self, rom_path: &str) -> Result<(), Chip8Error> {
+        let mut rom = std::fs::read(rom_path)?;
+        // A .c8b file carries its settings in front of the program.
+        let c8b = if c8b::is_c8b(&rom) {
+            let c8b = C8b::parse(&rom)?;
+            rom = c8b.program.clone();
+            Some(c8b)
+        } else {
+            None
+        };
+        for path in &self.patches {
+            rom = patch::apply(path, &rom)
+                .map_err(|e| std::io::Error::new(e.kind(), format!("{}: {}", path, e)))?;
+        }
+
+        // Settings come from, in order of priority: the command line, this ROM's entry in the
+        // config or the ROM database, the settings in a .c8b file or from Octo, and the rest of
+        // the config.
+        let sha1 = inspect::sha1_hex(&rom);
+        let rom_config = self.config.rom(&sha1);
+
+        let origin = self.load_address as u16;
+        let platform = inspect::detected_platform(&rom, origin);
+        let variant = self
+            .variant_override
+            .or(rom_config.variant)
+            .or(c8b.as_ref().map(|c8b| c8b.variant))
+            .unwrap_or_else(|| inspect::guess_variant(&rom, origin));
+
         let chip8 = {
             let mut chip8 = chip8::Chip8Emulator::new();
+            chip8.set_variant(variant);
+            chip8.set_memory_fill(self.memory_fill);
+            chip8.set_cycle_table(self.cycle_table.clone());
+            chip8.set_load_address(self.load_address);
+            if let Some(seed) = self.seed {
+                chip8.set_rng_seed(seed);
+            }
             chip8.initialize();
-            chip8.load_game(rom_path).unwrap();
+            chip8.load_rom_bytes(&rom)?;
+            for (path, address) in &self.segments {
+                chip8.load_segment(path, *address)?;
+            }
+            self.events.attach(&mut chip8);
             chip8
         };
+        self.emu = chip8;
+        self.fault = None;
+        if let Some((movie, _)) = &mut self.recording {
+            *movie = Movie::new(movie.seed, &rom);
+        }
+        if let Some(playback) = &self.playback {
+            if !playback.timeline.movie().matches_rom(&rom) {
+                warn!("the movie was recorded with a different ROM");
+            }
+        }
 
-        Self {
-            emu: chip8,
-            pixels,
-            input: WinitInputHelper::new(),
+        let name = match &rom_config.title {
+            Some(title) => {
+                info!("recognized {}", title);
+                title.clone()
+            }
+            None => Path::new(rom_path)
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned(),
+        };
+        self.rom_info = format!("{} | SHA-1 {} | {}", name, &sha1[..8], platform.name());
+
+        self.rom_path = rom_path.to_string();
+        self.launcher = None;
+        if self.kiosk.is_none() {
+            self.config.remember_rom(Path::new(rom_path));
+        }
+        self.save_score();
+        self.score = highscore::ScoreWatch::for_rom(rom_path);
+
+        // ROMs exported from Octo may come with the settings their authors intended.
+        let octo = octo::OctoOptions::for_rom(rom_path);
+        let mut quirks = self
+            .quirks_override
+            .or(rom_config.quirks)
+            .or(c8b.as_ref().and_then(|c8b| c8b.quirks))
+            .or_else(|| octo.as_ref().map(octo::OctoOptions::quirks))
+            .or(self.config.quirks)
+            .unwrap_or_else(|| self.emu.quirks());
+        for (name, enabled) in &self.quirk_toggles {
+            quirks.set(name, *enabled).unwrap();
+        }
+        self.emu.set_quirks(quirks);
+
+        let octo = octo.unwrap_or_default();
+        self.clock = self
+            .clock_override
+            .or_else(|| rom_config.clock_hz.map(Clock::from_hz))
+            .or_else(|| c8b.as_ref()?.clock_hz.map(Clock::from_hz))
+            .or_else(|| octo.instructions_per_frame().map(Clock::from_ipf))
+            .unwrap_or_else(|| Clock::from_hz(DEFAULT_CLOCK_HZ));
+        self.keymap = self
+            .keymap_override
+            .clone()
+            .or(rom_config.keymap)
+            .or_else(|| keymap::KeyMap::from_ascii(c8b.as_ref()?.keys))
+            .or_else(|| self.config.keymap())
+            .unwrap_or_default();
+        self.gamepad_map = rom_config
+            .gamepad
+            .or_else(|| self.config.gamepad.clone())
+            .unwrap_or_default();
+        self.gamepad2_map = rom_config.gamepad2.or_else(|| self.config.gamepad2.clone());
+        self.palette = self
+            .palette_override
+            .or(rom_config.palette)
+            .or(c8b.as_ref().and_then(|c8b| c8b.palette))
+            .or_else(|| octo.palette())
+            .or(self.config.palette)
+            .unwrap_or_default();
+        self.start_comparison();
+        self.restart_playback();
+        Ok(())
+    }
+
+    /// Play the movie being played back from its first frame, on the machine as it is now.
+    fn restart_playback(&mut self) {
+        if let Some(playback) = &mut self.playback {
+            playback.timeline.restart(&self.emu, self.clock);
+            playback.frame = 0;
+            playback.finished = false;
+        }
+    }
+
+    /// Go to the start of `frame` of the movie being played back, restoring the machine from
+    /// the nearest snapshot before it and playing the rest, and pause there.
+    #[cfg(feature = "debug-ui")]
+    fn seek(&mut self, frame: usize) {
+        let Some(playback) = &mut self.playback else {
+            return;
+        };
+        match playback.timeline.seek(frame) {
+            Ok((mut emu, clock)) => {
+                self.events.attach(&mut emu);
+                self.emu = emu;
+                self.clock = clock;
+                playback.frame = frame;
+                playback.finished = false;
+                self.fault = None;
+                self.debugger.pause();
+                self.redraw = true;
+            }
+            Err(e) => error!("failed to seek to frame {}: {}", frame, e),
+        }
+    }
+
+    /// Write the movie being played back, with any edits, over the file it was read from.
+    #[cfg(feature = "debug-ui")]
+    fn save_playback(&self) {
+        if let Some(playback) = &self.playback {
+            let data = playback.timeline.movie().to_bytes();
+            match std::fs::write(&playback.path, data) {
+                Ok(()) => info!("saved movie to {}", playback.path.display()),
+                Err(e) => error!("failed to save {}: {}", playback.path.display(), e),
+            }
+        }
+    }
+
+    /// Write the loaded ROM, with the settings it runs with, next to it as a `.c8b` file.
+    fn export_c8b(&self) -> std::io::Result<PathBuf> {
+        let c8b = C8b {
+            variant: self.emu.variant(),
+            quirks: Some(self.emu.quirks()),
+            clock_hz: Some(self.clock.instructions_per_second()).filter(|_| !self.clock.is_vip()),
+            palette: Some(self.palette),
+            keys: self.keymap.to_ascii(),
+            program: self.emu.rom().to_vec(),
+        };
+        let path = Path::new(&self.rom_path).with_extension("c8b");
+        std::fs::write(&path, c8b.to_bytes())?;
+        Ok(path)
+    }
+
+    /// Start the --compare machine over as a copy of this one.
+    fn start_comparison(&mut self) {
+        if self.compare.is_empty() {
+            return;
+        }
+        let mut quirks = self.emu.quirks();
+        for change in &self.compare {
+            change.apply(&mut quirks);
+        }
+        info!("comparing against quirks: {}", quirks);
+        self.comparison = Some(Comparison::new(&self.emu, quirks));
+    }
+
+    /// Switch to the next built-in theme, keeping it for ROMs loaded later.
+    fn next_theme(&mut self) {
+        let next = palette::THEMES
+            .iter()
+            .position(|(_, theme)| *theme == self.palette)
+            .map_or(0, |i| (i + 1) % palette::THEMES.len());
+        let (name, theme) = palette::THEMES[next];
+        info!("palette: {}", name);
+        self.palette = theme;
+        self.palette_override = Some(theme);
+    }
+
+    /// Step the instruction rate up or down to the next of `CLOCK_STEPS`, keeping it for ROMs
+    /// loaded later.
+    fn change_speed(&mut self, faster: bool) {
+        let current = self.clock.instructions_per_second();
+        let next = if faster {
+            CLOCK_STEPS.iter().find(|hz| **hz > current)
+        } else {
+            CLOCK_STEPS.iter().rev().find(|hz| **hz < current)
+        };
+        if let Some(hz) = next {
+            info!("speed: {} Hz", hz);
+            self.clock = Clock::from_hz(*hz);
+            self.clock_override = Some(self.clock);
+        }
+    }
+
+    /// Run a frame's worth of instructions, then tick the timers unless execution is paused.
+    fn run_frame(&mut self) {
+        if !self.debugger.is_paused() {
+            self.movie_frame();
+        }
+        if self.clock.is_vip() {
+            let end = self.clock.vip_frame_end(self.emu.cycles());
+            while self.emu.cycles() < end && self.step() {}
+        } else {
+            for _ in 0..self.clock.instructions_this_frame() {
+                if !self.step() {
+                    break;
+                }
+            }
+        }
+        if !self.debugger.is_paused() {
+            self.emu.tick_timers();
+            self.end_movie_frame();
+            if let Some(comparison) = &mut self.comparison {
+                if let Some(divergence) = comparison.end_frame(&self.emu) {
+                    warn!("the machines diverged at {}", divergence);
+                    self.debugger.pause();
+                }
+            }
+        }
+    }
+
+    /// Tell the debug server's client once execution stops after running.
+    fn notify_debug_client(&mut self) {
+        let stopped = self.debugger.is_stopped();
+        if stopped && !self.debug_stopped {
+            if let Some(client) = &self.debug_client {
+                let mut event = debug_server::state(&self.emu, &self.debugger);
+                event["event"] = "stopped".into();
+                if client.send(event.to_string()).is_err() {
+                    self.debug_client = None;
+                }
+            }
+        }
+        self.debug_stopped = stopped;
+    }
+
+    /// Hold the keys of the next frame of the movie being played back, and record the keys
+    /// held for the movie being recorded.
+    fn movie_frame(&mut self) {
+        if let Some(playback) = self.playback.as_mut().filter(|playback| !playback.finished) {
+            playback
+                .timeline
+                .reached(playback.frame, &self.emu, self.clock);
+            match playback.timeline.movie().frame(playback.frame) {
+                Some(keys) => {
+                    self.emu.set_keys(&keys);
+                    playback.frame += 1;
+                }
+                None => {
+                    info!("playback finished after {} frames", playback.frame);
+                    playback.finished = true;
+                }
+            }
+        }
+        if let Some((movie, _)) = &mut self.recording {
+            movie.record(self.emu.keys());
+        }
+    }
+
+    /// Check the frame just played back against the movie's state hashes, stopping playback if
+    /// they differ, and keep the hashes of the movie being recorded.
+    fn end_movie_frame(&mut self) {
+        if let Some(playback) = self.playback.as_mut().filter(|playback| !playback.finished) {
+            // The frame counter already points at the next frame.
+            let played = playback.frame.saturating_sub(1);
+            if let Err(e) = playback.timeline.movie().check_frame(played, &self.emu) {
+                error!("{}, stopping playback", e);
+                playback.finished = true;
+                self.debugger.pause();
+            }
+        }
+        if let Some((movie, _)) = &mut self.recording {
+            movie.end_frame(&self.emu);
+        }
+    }
+
+    /// Start recording a GIF, or finish the one being recorded.
+    fn toggle_gif(&mut self) {
+        match self.gif.take() {
+            Some(gif) => match gif.finish() {
+                Ok(()) => info!("finished recording GIF"),
+                Err(e) => error!("failed to write GIF: {}", e),
+            },
+            None => {
+                let path = capture::capture_path("recording", "gif");
+                match capture::GifRecorder::create(&path, &self.palette) {
+                    Ok(gif) => {
+                        info!("recording GIF to {}", path);
+                        self.gif = Some(gif);
+                    }
+                    Err(e) => error!("failed to start recording GIF: {}", e),
+                }
+            }
+        }
+    }
+
+    /// Restart the current ROM, loading any extra files on top of it again.
+    fn reset(&mut self) -> Result<(), Chip8Error> {
+        self.emu.reset()?;
+        for (path, address) in &self.segments {
+            self.emu.load_segment(path, *address)?;
+        }
+        self.fault = None;
+        if let Some((movie, _)) = &mut self.recording {
+            movie.clear();
+        }
+        self.start_comparison();
+        self.restart_playback();
+        Ok(())
+    }
+
+    /// Window title. With F1 it includes the effective configuration, so that it shows up in
+    /// screenshots attached to bug reports.
+    fn title(&self) -> String {
+        if let Some(fault) = &self.fault {
+            return format!("{} - stopped: {} | Space to retry", TITLE, fault);
+        }
+        if let Some(divergence) = self.comparison.as_ref().and_then(Comparison::divergence) {
+            return format!("{} - diverged at {}", TITLE, divergence);
+        }
+        if !self.show_info {
+            return TITLE.to_string();
+        }
+        format!(
+            "{} - {} | running as {:?} | {} | memory fill {:?} | quirks: {} | keys {} | palette {}",
+            TITLE,
+            self.rom_info,
+            self.emu.variant(),
+            self.clock,
+            self.memory_fill,
+            self.emu.quirks(),
+            self.keymap.name(),
+            self.palette
+        )
+    }
+
+    /// Size of the frame drawn each update: the screen, or with --compare both machines'
+    /// screens side by side with a column between them.
+    fn frame_size(&self) -> (usize, usize) {
+        let (width, height) = (self.emu.screen_width(), self.emu.screen_height());
+        match &self.comparison {
+            Some(comparison) => {
+                let other = comparison.other();
+                (
+                    width + 1 + other.screen_width(),
+                    height.max(other.screen_height()),
+                )
+            }
+            None => (width, height),
+        }
+    }
+
+    /// Whether the window has to be drawn again. Most frames of most ROMs leave the screen as
+    /// it was, and those are skipped unless something else shown over it moves.
+    fn needs_drawing(&mut self) -> bool {
+        // Take both flags, so neither carries over to the next frame.
+        let changed = self.events.take_drawn() | std::mem::take(&mut self.redraw);
+        #[cfg(feature = "debug-ui")]
+        if self.debug_ui.visible {
+            return true;
+        }
+        changed
+            || self.launcher.is_some()
+            || self.show_info
+            || self.keypad_panel.visible
+            || self.comparison.is_some()
+            || self.renderer.is_fading()
+    }
+
+    /// Whether the ROM faulted, and whether the --compare machines diverged, which the title
+    /// shows.
+    fn stop_reason(&self) -> (bool, bool) {
+        let diverged = self.comparison.as_ref().and_then(Comparison::divergence);
+        (self.fault.is_some(), diverged.is_some())
+    }
+
+    /// Run the next instruction, unless the debugger holds it back, with the tools watching.
+    /// Returns whether another can run this frame.
+    fn step(&mut self) -> bool {
+        if !self.debugger.before_cycle(&self.emu) {
+            return false;
+        }
+        if let Some((graph, _)) = &mut self.callgraph {
+            graph.observe(&self.emu);
+        }
+        if let Some((profiler, _)) = &mut self.profiler {
+            profiler.observe(&self.emu);
+        }
+        if let Some(trace) = &mut self.trace {
+            trace.before_cycle(&self.emu);
+        }
+        #[cfg(feature = "scripting")]
+        if let Some(script) = &mut self.script {
+            script.before_cycle(&mut self.emu);
+        }
+        match self.emu.run_for(1) {
+            Ok(StepOutcome::Completed) => self.fault = None,
+            // Waiting for a key or the display, or exited, so nothing ran.
+            Ok(_) => return false,
+            Err(e) => {
+                error!("{}", e);
+                self.fault = Some(e);
+                self.debugger.pause();
+                return false;
+            }
+        }
+        self.executed += 1;
+        if let Some(comparison) = &mut self.comparison {
+            comparison.step(&self.emu);
+        }
+        self.debugger.after_cycle(&self.emu);
+        if let Some(trace) = &mut self.trace {
+            if let Err(e) = trace.after_cycle(&self.emu) {
+                error!("failed to write trace: {}", e);
+                self.trace = None;
+            }
+        }
+        if let Some(score) = &mut self.score {
+            score.sample(&self.emu);
+        }
+        #[cfg(feature = "scripting")]
+        if let Some(script) = &mut self.script {
+            script.after_cycle(&mut self.emu);
+            if script.take_pause_request() {
+                self.debugger.pause();
+            }
+        }
+        true
+    }
+
+    fn state_path(&self) -> String {
+        format!("{}.state{}", self.rom_path, self.state_slot)
+    }
+
+    /// Save the machine to the current slot.
+    fn save_state(&self) {
+        let path = self.state_path();
+        match std::fs::write(&path, self.emu.save_state()) {
+            Ok(()) => info!("saved state to {}", path),
+            Err(e) => error!("failed to save state to {}: {}", path, e),
+        }
+    }
+
+    /// Restore the machine from the current slot.
+    fn load_state(&mut self) {
+        let path = self.state_path();
+        match std::fs::read(&path).and_then(|data| self.emu.load_state(&data)) {
+            Ok(()) => {
+                info!("loaded state from {}", path);
+                self.fault = None;
+                self.start_comparison();
+            }
+            Err(e) => error!("failed to load state from {}: {}", path, e),
+        }
+    }
+
+    /// Restore the machine from a save state on the clipboard.
+    fn paste_state(&mut self) {
+        match self.clipboard.paste_state(&mut self.emu) {
+            Ok(()) => {
+                info!("loaded state from the clipboard");
+                self.fault = None;
+                self.start_comparison();
+            }
+            Err(e) => error!("failed to paste state: {}", e),
+        }
+    }
+
+    fn save_score(&self) {
+        if let Some(score) = &self.score {
+            if let Err(e) = score.finish() {
+                error!("failed to save high scores: {}", e);
+            }
         }
     }
 
     fn update_keys(&mut self) {
-        let keys: [bool; 16] = [
-            self.input.key_held(VirtualKeyCode::Key0),
-            self.input.key_held(VirtualKeyCode::Key1),
-            self.input.key_held(VirtualKeyCode::Key2),
-            self.input.key_held(VirtualKeyCode::Key3),
-            self.input.key_held(VirtualKeyCode::Key4),
-            self.input.key_held(VirtualKeyCode::Key5),
-            self.input.key_held(VirtualKeyCode::Key6),
-            self.input.key_held(VirtualKeyCode::Key7),
-            self.input.key_held(VirtualKeyCode::Key8),
-            self.input.key_held(VirtualKeyCode::Key9),
-            self.input.key_held(VirtualKeyCode::A),
-            self.input.key_held(VirtualKeyCode::B),
-            self.input.key_held(VirtualKeyCode::C),
-            self.input.key_held(VirtualKeyCode::D),
-            self.input.key_held(VirtualKeyCode::E),
-            self.input.key_held(VirtualKeyCode::F),
-        ];
+        if self
+            .playback
+            .as_ref()
+            .is_some_and(|playback| !playback.finished)
+        {
+            return;
+        }
+        let mut keys = self.keymap.held(&self.input);
+        if let Some(gamepads) = &mut self.gamepads {
+            let second = self.gamepad2_map.as_ref().unwrap_or(&self.gamepad_map);
+            gamepads.poll([&self.gamepad_map, second], &self.stick, &mut keys);
+        }
+        if let Some(host) = &self.host {
+            host.add_keys(&mut keys);
+        }
+        for plugin in &self.plugins {
+            plugin.poll_keys(&mut keys);
+        }
+        #[cfg(feature = "scripting")]
+        if let Some(script) = &self.script {
+            script.poll_keys(&mut keys);
+        }
 
         self.emu.set_keys(&keys);
     }
 }
 
+/// The buzzer the options ask for, or a silent one if sound is muted or unavailable.
+#[cfg(feature = "audio")]
+fn open_beeper(options: &cli::Options, config: &config::Config) -> Box<dyn Beeper> {
+    if options.mute {
+        return Box::new(NullBeeper);
+    }
+    let volume = options.volume.or(config.volume).unwrap_or(25);
+    let tone = audio::Tone {
+        waveform: options.waveform.or(config.waveform).unwrap_or_default(),
+        frequency: options.tone.or(config.tone).unwrap_or(440.0),
+        volume: f32::from(volume.min(100)) / 100.0,
+        attack: config.attack_ms.unwrap_or(2) as f32 / 1000.0,
+        release: config.release_ms.unwrap_or(10) as f32 / 1000.0,
+    };
+    match audio::Buzzer::new(tone) {
+        Ok(buzzer) => Box::new(buzzer),
+        Err(e) => {
+            error!("failed to open audio output: {}", e);
+            Box::new(NullBeeper)
+        }
+    }
+}
+
+#[cfg(not(feature = "audio"))]
+fn open_beeper(options: &cli::Options, _config: &config::Config) -> Box<dyn Beeper> {
+    if options.mute
+        || options.volume.is_some()
+        || options.tone.is_some()
+        || options.waveform.is_some()
+    {
+        warn!("ignoring sound options, this build has no audio support");
+    }
+    Box::new(NullBeeper)
+}
+
+/// Put `other`'s screen to the right of a frame `width` pixels wide, with a column of the
+/// foreground color between them.
+fn side_by_side(
+    frame: &[u8],
+    width: usize,
+    other: &chip8::Chip8Emulator,
+    palette: &Palette,
+) -> Vec<u8> {
+    let other_width = other.screen_width();
+    let mut other_frame = vec![0; 4 * other_width * other.screen_height()];
+    other.draw_screen_with_palette(&mut other_frame, palette);
+
+    let height = (frame.len() / 4 / width).max(other.screen_height());
+    let mut combined = Vec::with_capacity(4 * (width + 1 + other_width) * height);
+    for y in 0..height {
+        let row = |frame: &[u8], width: usize| {
+            let row = frame.get(4 * width * y..4 * width * (y + 1));
+            row.map(<[u8]>::to_vec)
+                .unwrap_or_else(|| palette.background.repeat(width))
+        };
+        combined.extend(row(frame, width));
+        combined.extend_from_slice(&palette.foreground);
+        combined.extend(row(&other_frame, other_width));
+    }
+    combined
+}
+
+/// Events sent to the event loop from other threads.
+enum UserEvent {
+    /// Reset and load another ROM.
+    OpenRom(PathBuf),
+    /// Carry out a command from the debug server's client.
+    Debug(debug_server::Request),
+}
+
+/// Switch the running game to another ROM.
+fn open_rom(g: &mut GameLoop<Game, Time, Window>, path: &Path) {
+    match g.game.load_rom(&path.to_string_lossy()) {
+        Ok(()) => g.window.set_title(&g.game.title()),
+        Err(e) => error!("failed to load {}: {}", path.display(), e),
+    }
+}
+
+/// Ask for a ROM to open with the desktop's file dialog.
+fn pick_rom() -> Option<PathBuf> {
+    rfd::FileDialog::new()
+        .add_filter("CHIP-8 ROMs", &launcher::ROM_EXTENSIONS)
+        .pick_file()
+}
+
+fn on_off(enabled: bool) -> &'static str {
+    if enabled {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+const TITLE: &str = "Chip 8 Emulator";
 const WIDTH: u32 = 64;
 const HEIGHT: u32 = 32;
 
-const FPS: usize = 500;
-const TIME_STEP: Duration = Duration::from_nanos(1_000_000_000 / FPS as u64);
+const DEFAULT_CLOCK_HZ: u32 = 500;
+/// Instruction rates `+` and `-` step through.
+const CLOCK_STEPS: [u32; 12] = [
+    60, 120, 240, 360, 500, 700, 1000, 1500, 2000, 3000, 5000, 10000,
+];
+
+/// Run a ROM without a window for a number of frames.
+fn run_frames(
+    rom_path: &str,
+    frames: u32,
+    variant: Option<chip8::Variant>,
+    quirks: Option<Quirks>,
+) -> Result<chip8::Chip8Emulator, Chip8Error> {
+    let rom = std::fs::read(rom_path)?;
+    let mut emu = chip8::Chip8Emulator::new();
+    emu.set_variant(variant.unwrap_or_else(|| inspect::guess_variant(&rom, 0x200)));
+    if let Some(quirks) = quirks {
+        emu.set_quirks(quirks);
+    }
+    emu.initialize();
+    emu.load_rom_bytes(&rom)?;
+
+    headless::run_frames(&mut emu, frames, Clock::from_hz(DEFAULT_CLOCK_HZ))?;
+    Ok(emu)
+}
+
+/// Run a ROM without a window for a number of frames and print its screen, lit pixels as `#`.
+fn run_test(
+    rom_path: &str,
+    frames: u32,
+    variant: Option<chip8::Variant>,
+    quirks: Option<Quirks>,
+) -> Result<(), Chip8Error> {
+    let emu = run_frames(rom_path, frames, variant, quirks)?;
+    for row in emu.screen().chunks(emu.screen_width()) {
+        let line: String = row
+            .iter()
+            .map(|planes| if *planes != 0 { '#' } else { '.' })
+            .collect();
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+/// Set up a machine with the ROM and settings given on the command line, without reading the
+/// config, for the frontends other than the window.
+fn build_emulator(
+    options: &cli::Options,
+) -> Result<chip8::Chip8Emulator, Box<dyn std::error::Error>> {
+    let mut rom = std::fs::read(options.rom_path.as_deref().unwrap())?;
+    for path in &options.patches {
+        rom = patch::apply(path, &rom)?;
+    }
+
+    let mut emu = chip8::Chip8Emulator::new();
+    emu.set_variant(
+        options
+            .variant
+            .unwrap_or_else(|| inspect::guess_variant(&rom, options.load_addr as u16)),
+    );
+    emu.set_memory_fill(options.memory_fill);
+    emu.set_load_address(options.load_addr);
+    emu.set_cycle_table(options.cycle_table.clone());
+    if let Some(seed) = options.seed {
+        emu.set_rng_seed(seed);
+    }
+    emu.initialize();
+    emu.load_rom_bytes(&rom)?;
+    for (path, address) in &options.segments {
+        emu.load_segment(path, *address)?;
+    }
+    let mut quirks = options.quirks.unwrap_or_else(|| emu.quirks());
+    for (name, enabled) in &options.quirk_toggles {
+        quirks.set(name, *enabled)?;
+    }
+    emu.set_quirks(quirks);
+    Ok(emu)
+}
+
+/// `--headless`: run a ROM without a window and print the machine's final state as JSON.
+fn run_headless(options: &cli::Options) -> Result<(), Box<dyn std::error::Error>> {
+    let mut emu = build_emulator(options)?;
+    let clock = options
+        .clock()
+        .unwrap_or_else(|| Clock::from_hz(DEFAULT_CLOCK_HZ));
+    let report = match &options.playback {
+        Some(path) => {
+            let movie = Movie::from_bytes(&std::fs::read(path)?)?;
+            headless::run_movie(&mut emu, &movie, clock)?
+        }
+        None => headless::run_headless(&mut emu, options.cycles, clock)?,
+    };
+    if let Some(path) = &options.screenshot {
+        let is_png = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("png"));
+        let image = if is_png {
+            headless::screen_png(&emu)
+        } else {
+            headless::screen_pbm(&emu)
+        };
+        std::fs::write(path, image)?;
+    }
+    println!("{}", report.to_json());
+    Ok(())
+}
+
+/// `--bench`: time a headless run of a ROM and print its speed.
+fn run_bench(options: &cli::Options, millions: u64) -> Result<(), Box<dyn std::error::Error>> {
+    let mut emu = build_emulator(options)?;
+    let clock = options
+        .clock()
+        .unwrap_or_else(|| Clock::from_hz(DEFAULT_CLOCK_HZ));
+    let start = Instant::now();
+    let report = headless::run_headless(&mut emu, millions * 1_000_000, clock)?;
+    let seconds = start.elapsed().as_secs_f64();
+    println!(
+        "{} instructions in {:.3} s: {:.1} million instructions per second",
+        report.cycles,
+        seconds,
+        report.cycles as f64 / seconds / 1e6
+    );
+    if emu.is_halted() {
+        println!("the ROM exited before the end of the run");
+    }
+    Ok(())
+}
+
+/// `--frontend tui`: run a ROM in the terminal.
+#[cfg(feature = "tui")]
+fn run_tui(options: &cli::Options) -> Result<(), Box<dyn std::error::Error>> {
+    let mut emu = build_emulator(options)?;
+    let clock = options
+        .clock()
+        .unwrap_or_else(|| Clock::from_hz(DEFAULT_CLOCK_HZ));
+    tui::run(&mut emu, clock, options.palette.unwrap_or_default())
+}
 
-fn main() -> std::io::Result<()> {
-    env_logger::init();
+#[cfg(not(feature = "tui"))]
+fn run_tui(_options: &cli::Options) -> Result<(), Box<dyn std::error::Error>> {
+    Err("this build has no terminal frontend; rebuild with `--features tui`".into())
+}
 
-    let args: Vec<String> = env::args().collect();
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let cli = cli::Cli::parse_args();
+    let options = match cli.command {
+        Some(cli::Command::Run(options)) => *options,
+        Some(cli::Command::Disasm { rom }) => {
+            let rom = std::fs::read(rom)?;
+            print!("{}", disasm::disassemble(&rom, 0x200));
+            return Ok(());
+        }
+        Some(cli::Command::Assemble { source, output }) => {
+            let text = std::fs::read_to_string(&source)?;
+            let rom =
+                asm::assemble(&text, 0x200).map_err(|e| format!("{}: {}", source.display(), e))?;
+            std::fs::write(output.unwrap_or_else(|| source.with_extension("ch8")), rom)?;
+            return Ok(());
+        }
+        Some(cli::Command::Inspect { rom: path }) => {
+            let rom = std::fs::read(&path)?;
+            print!("{}", inspect::inspect(&path, &rom, 0x200));
+            return Ok(());
+        }
+        Some(cli::Command::Callgraph { rom }) => {
+            let rom = std::fs::read(rom)?;
+            print!("{}", callgraph::CallGraph::from_rom(&rom, 0x200).to_dot());
+            return Ok(());
+        }
+        Some(cli::Command::Serve { rom, address }) => {
+            env_logger::init();
+            return Ok(server::serve(
+                &rom,
+                &address,
+                Clock::from_hz(DEFAULT_CLOCK_HZ),
+            )?);
+        }
+        Some(cli::Command::Dump {
+            rom,
+            rom_only,
+            frames,
+        }) => {
+            let emu = run_frames(&rom, frames, None, None)?;
+            let range = if rom_only {
+                emu.rom_range()
+            } else {
+                0..emu.memory().len()
+            };
+            print!(
+                "{}",
+                disasm::hex_dump(emu.read_memory(range.clone()), range.start)
+            );
+            return Ok(());
+        }
+        Some(cli::Command::Test {
+            rom,
+            frames,
+            variant,
+            quirks,
+        }) => {
+            env_logger::init();
+            return Ok(run_test(&rom, frames, variant, quirks)?);
+        }
+        Some(cli::Command::TestRoms { manifest, update }) => {
+            env_logger::init();
+            return test_roms::run(&manifest, update, Clock::from_hz(DEFAULT_CLOCK_HZ));
+        }
+        // `args_conflicts_with_subcommands` means the ROM path is there without a subcommand.
+        None => cli.run.unwrap(),
+    };
 
-    let event_loop = EventLoop::new();
+    // `--trace`: log every executed instruction with the registers before it runs.
+    let mut logger = env_logger::Builder::from_default_env();
+    if options.trace {
+        logger.filter_module("chip8emu::chip8", log::LevelFilter::Trace);
+    }
+    logger.init();
+
+    if options.headless {
+        return run_headless(&options);
+    }
+    if let Some(millions) = options.bench {
+        return run_bench(&options, millions);
+    }
+    if options.frontend == cli::Frontend::Tui {
+        return run_tui(&options);
+    }
+
+    let config = options
+        .config_path
+        .clone()
+        .or_else(config::Config::default_path)
+        .map(|path| config::Config::load(&path))
+        .unwrap_or_default();
+
+    // Connect before opening a window, so that a wrong address fails straight away.
+    let guest = options
+        .join
+        .as_deref()
+        .map(net::Guest::connect)
+        .transpose()?;
+
+    let rom_path = options.rom_path.as_deref();
+    if options.single_instance && rom_path.is_some_and(single_instance::forward_to_running_instance)
+    {
+        return Ok(());
+    }
+
+    let event_loop = EventLoopBuilder::with_user_event().build();
+    if options.single_instance {
+        let proxy = event_loop.create_proxy();
+        // Without the socket, later instances just open their own windows.
+        if let Err(e) = single_instance::listen(move |path| {
+            let _ = proxy.send_event(UserEvent::OpenRom(path));
+        }) {
+            warn!("not taking ROMs from other instances: {}", e);
+        }
+    }
+    if let Some(address) = &options.debug_server {
+        let proxy = event_loop.create_proxy();
+        debug_server::listen(address, move |request| {
+            let _ = proxy.send_event(UserEvent::Debug(request));
+        })?;
+    }
 
     let window = {
         let size = LogicalSize::new(WIDTH as f64, HEIGHT as f64);
-        let scaled_size = LogicalSize::new(WIDTH as f64 * 10.0, HEIGHT as f64 * 10.0);
+        let scale = f64::from(options.scale.or(config.scale).unwrap_or(10).max(1));
+        let scaled_size = LogicalSize::new(WIDTH as f64 * scale, HEIGHT as f64 * scale);
         WindowBuilder::new()
-            .with_title("Chip 8 Emulator")
+            .with_title(TITLE)
             .with_inner_size(scaled_size)
             .with_min_inner_size(size)
+            .with_fullscreen(options.fullscreen.then_some(Fullscreen::Borderless(None)))
             .build(&event_loop)
             .unwrap()
     };
@@ -86,50 +1325,454 @@ fn main() -> std::io::Result<()> {
     let pixels = {
         let window_size = window.inner_size();
         let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, &window);
-        Pixels::new(WIDTH, HEIGHT, surface_texture).unwrap()
+        let scale = render::SCALE as u32;
+        Pixels::new(WIDTH * scale, HEIGHT * scale, surface_texture).unwrap()
     };
 
-    let game = Game::new(pixels, &args[1]);
+    if let Some(guest) = guest {
+        window.set_title(&format!("{} - {}", TITLE, options.join.as_deref().unwrap()));
+        let settings = net::GuestSettings {
+            keymap: options
+                .keymap
+                .clone()
+                .or_else(|| config.keymap())
+                .unwrap_or_default(),
+            palette: options.palette.or(config.palette).unwrap_or_default(),
+            beeper: open_beeper(&options, &config),
+            display: options.display,
+        };
+        guest.run(event_loop, window, pixels, settings);
+    }
+
+    let game = Game::new(pixels, &window, options, config)?;
 
     game_loop(
         event_loop,
         window,
         game,
-        FPS as u32,
+        TIMER_HZ,
         0.1,
         move |g| {
-            g.game.emu.emulate_cycle();
+            if g.game.launcher.is_some() {
+                return;
+            }
+            let stopped = g.game.stop_reason();
+            if g.game.advance_frame {
+                g.game.advance_frame = false;
+                g.game.debugger.resume();
+                g.game.run_frame();
+                g.game.debugger.pause();
+            } else if g.game.turbo {
+                // As many frames as fit before the next one is due, leaving time to draw.
+                let start = Instant::now();
+                let budget = Duration::from_secs_f64(g.fixed_time_step() * 0.75);
+                while start.elapsed() < budget && !g.game.debugger.is_paused() {
+                    g.game.run_frame();
+                }
+            } else {
+                g.game.run_frame();
+            }
+            if g.game.stop_reason() != stopped {
+                g.window.set_title(&g.game.title());
+            }
+            #[cfg(feature = "scripting")]
+            if let Some(script) = &mut g.game.script {
+                script.frame(&mut g.game.emu);
+                if script.take_pause_request() {
+                    g.game.debugger.pause();
+                }
+                if script.quit_requested() {
+                    g.exit();
+                }
+            }
+            g.game.notify_debug_client();
+            if let Some(gif) = &mut g.game.gif {
+                if let Err(e) = gif.capture(&g.game.emu) {
+                    error!("failed to record GIF: {}", e);
+                    g.game.gif = None;
+                }
+            }
+            let beeping = g.game.events.is_beeping() && !g.game.debugger.is_paused();
+            g.game.beeper.set_pattern(g.game.emu.sound_pattern());
+            g.game.beeper.set_beeping(beeping);
+            if let Some(host) = &mut g.game.host {
+                host.send_frame(&g.game.emu, beeping);
+            }
         },
         move |g| {
             // Drawing
-            g.game.emu.draw_screen(g.game.pixels.get_frame());
-            if let Err(e) = g.game.pixels.render() {
-                error!("pixels.render() failed: {}", e);
-                g.exit();
+            if let Some((profiler, _)) = &mut g.game.profiler {
+                profiler.frame_boundary();
+            }
+            let game = &mut g.game;
+            game.keypad_panel.update(&mut game.emu);
+            if game.needs_drawing() {
+                let palette = game.palette;
+                let (width, height) = game.frame_size();
+                if (width, height) != game.screen_size {
+                    // SUPER-CHIP switched resolution.
+                    let scale = render::SCALE;
+                    game.pixels
+                        .resize_buffer((width * scale) as u32, (height * scale) as u32);
+                    game.screen_size = (width, height);
+                }
+                if let Some(launcher) = &mut game.launcher {
+                    // Drawn straight to the window, past the effects.
+                    let palette = game.display.palette(palette);
+                    launcher.draw(
+                        game.pixels.get_frame(),
+                        width * render::SCALE,
+                        palette.foreground,
+                        palette.background,
+                    );
+                } else {
+                    let emu_width = game.emu.screen_width();
+                    let mut frame = vec![0; 4 * emu_width * game.emu.screen_height()];
+                    game.emu.draw_screen_with_palette(&mut frame, &palette);
+                    for plugin in &game.plugins {
+                        plugin.frame(game.emu.screen(), emu_width);
+                    }
+                    game.keypad_panel.draw(&game.emu, &mut frame, emu_width);
+                    if let Some(comparison) = &game.comparison {
+                        frame = side_by_side(&frame, emu_width, comparison.other(), &palette);
+                    }
+                    game.renderer.render(
+                        &frame,
+                        width,
+                        palette.background,
+                        &game.display,
+                        game.pixels.get_frame(),
+                    );
+                    game.overlay.update(game.executed);
+                    if game.show_info {
+                        game.overlay.draw(
+                            &game.emu,
+                            game.pixels.get_frame(),
+                            width * render::SCALE,
+                        );
+                    }
+                }
+                #[cfg(feature = "debug-ui")]
+                let rendered = {
+                    let playback = game
+                        .playback
+                        .as_mut()
+                        .map(|playback| (&mut playback.timeline, playback.frame));
+                    game.debug_ui
+                        .run(&mut game.emu, &mut game.debugger, &game.palette, playback);
+                    if let Some(frame) = game.debug_ui.take_seek() {
+                        game.seek(frame);
+                    }
+                    if game.debug_ui.take_save_movie() {
+                        game.save_playback();
+                    }
+                    if game.debug_ui.take_restart() {
+                        match game.reset() {
+                            Ok(()) => g.window.set_title(&game.title()),
+                            Err(e) => error!("failed to reset: {}", e),
+                        }
+                    }
+                    let debug_ui = &mut game.debug_ui;
+                    game.pixels.render_with(|encoder, target, context| {
+                        context.scaling_renderer.render(encoder, target);
+                        debug_ui.render(encoder, target, context);
+                        Ok(())
+                    })
+                };
+                #[cfg(not(feature = "debug-ui"))]
+                let rendered = game.pixels.render();
+                if let Err(e) = rendered {
+                    error!("pixels.render() failed: {}", e);
+                    g.exit();
+                }
             }
 
             // Sleep the main thread to limit drawing to the fixed time step.
             // See: https://github.com/parasyte/pixels/issues/174
-            let dt = TIME_STEP.as_secs_f64() - Time::now().sub(&g.current_instant());
+            let dt = g.fixed_time_step() - Time::now().sub(&g.current_instant());
             if dt > 0.0 {
                 std::thread::sleep(Duration::from_secs_f64(dt));
             }
         },
         |g, event| {
+            if let Some(signals) = &g.game.signals {
+                if signals.take_dump_request() {
+                    let game = &g.game;
+                    match signals::write_dump(&game.emu, &game.rom_info, &game.palette) {
+                        Ok(path) => info!("wrote state dump to {}", path),
+                        Err(e) => error!("failed to write state dump: {}", e),
+                    }
+                }
+                // Exiting the loop saves everything below, the same as closing the window.
+                if signals.terminate_requested() {
+                    g.exit();
+                }
+            }
+
+            if let Event::UserEvent(UserEvent::OpenRom(path)) = event {
+                open_rom(g, path);
+                return;
+            }
+            if let Event::UserEvent(UserEvent::Debug(request)) = event {
+                let game = &mut g.game;
+                let reply =
+                    debug_server::handle(&request.command, &mut game.emu, &mut game.debugger);
+                let _ = request.client.send(reply.to_string());
+                game.debug_client = Some(request.client.clone());
+                game.debug_stopped = game.debugger.is_stopped();
+                return;
+            }
+
+            if let Event::LoopDestroyed = event {
+                g.game.save_score();
+                if let Some((graph, path)) = &g.game.callgraph {
+                    if let Err(e) = std::fs::write(path, graph.to_dot()) {
+                        error!("failed to write call graph: {}", e);
+                    }
+                }
+                if let Some((profiler, path)) = &g.game.profiler {
+                    if let Err(e) = std::fs::write(path, profiler.to_speedscope()) {
+                        error!("failed to write profile: {}", e);
+                    }
+                }
+                if let Some(trace) = &mut g.game.trace {
+                    if let Err(e) = trace.flush() {
+                        error!("failed to write trace: {}", e);
+                    }
+                }
+                if g.game.gif.is_some() {
+                    g.game.toggle_gif();
+                }
+                if let Some((movie, path)) = &g.game.recording {
+                    match std::fs::write(path, movie.to_bytes()) {
+                        Ok(()) => info!("recorded {} frames to {}", movie.len(), path.display()),
+                        Err(e) => error!("failed to write movie: {}", e),
+                    }
+                }
+                return;
+            }
+
+            #[cfg(feature = "debug-ui")]
+            if let Event::WindowEvent { event, .. } = event {
+                g.game.debug_ui.handle_event(event);
+            }
+
             // Let winit_input_helper collect events to build its state.
             if g.game.input.update(event) {
+                // Resize the window
+                if let Some(size) = g.game.input.window_resized() {
+                    g.game.pixels.resize_surface(size.width, size.height);
+                    g.game.redraw = true;
+                }
+
+                // Keys typed into the debugger windows are theirs alone.
+                #[cfg(feature = "debug-ui")]
+                if g.game.debug_ui.wants_keyboard() {
+                    return;
+                }
+
+                // The launcher takes the arrow keys to pick a ROM and Enter to play it, and ROMs
+                // can be dropped or opened as usual
+                if let Some(launcher) = &mut g.game.launcher {
+                    let input = &g.game.input;
+                    if input.key_pressed(VirtualKeyCode::Escape) || input.quit() {
+                        g.exit();
+                        return;
+                    }
+                    for (key, delta) in [
+                        (VirtualKeyCode::Up, -1),
+                        (VirtualKeyCode::Down, 1),
+                        (VirtualKeyCode::PageUp, -10),
+                        (VirtualKeyCode::PageDown, 10),
+                    ] {
+                        if input.key_pressed(key) {
+                            launcher.move_selection(delta);
+                        }
+                    }
+                    let chosen = if input.key_pressed(VirtualKeyCode::Return) {
+                        launcher.selected().map(Path::to_path_buf)
+                    } else if input.held_control() && input.key_pressed(VirtualKeyCode::O) {
+                        pick_rom()
+                    } else {
+                        input.dropped_file()
+                    };
+                    if let Some(path) = chosen {
+                        open_rom(g, &path);
+                    }
+                    return;
+                }
+
                 // Update controls
                 g.game.update_keys();
 
+                if let Some(kiosk) = &mut g.game.kiosk {
+                    if g.game.emu.keys().iter().any(|held| *held) {
+                        kiosk.input();
+                    }
+                    if let Some(path) = kiosk.poll() {
+                        open_rom(g, &path);
+                    }
+                }
+
                 // Close events
                 if g.game.input.key_pressed(VirtualKeyCode::Escape) || g.game.input.quit() {
                     g.exit();
                     return;
                 }
 
-                // Resize the window
-                if let Some(size) = g.game.input.window_resized() {
-                    g.game.pixels.resize_surface(size.width, size.height);
+                if g.game.input.key_pressed(VirtualKeyCode::F1) {
+                    g.game.redraw = true;
+                    g.game.show_info = !g.game.show_info;
+                    g.window.set_title(&g.game.title());
+                }
+
+                if g.game.input.key_pressed(VirtualKeyCode::F2) {
+                    g.game.redraw = true;
+                    g.game.keypad_panel.visible = !g.game.keypad_panel.visible;
+                }
+
+                if g.game.input.key_pressed(VirtualKeyCode::F3) {
+                    let game = &mut g.game;
+                    if let Err(e) = game.clipboard.copy_screen(&game.emu, &game.palette) {
+                        error!("failed to copy screen to clipboard: {}", e);
+                    }
+                }
+
+                if g.game.input.key_pressed(VirtualKeyCode::F4) {
+                    g.game.redraw = true;
+                    g.game.next_theme();
+                    g.window.set_title(&g.game.title());
+                }
+
+                // F12 saves a screenshot, and Shift+F12 starts and stops recording a GIF
+                if g.game.input.key_pressed(VirtualKeyCode::F12) {
+                    if g.game.input.held_shift() {
+                        g.game.toggle_gif();
+                    } else {
+                        let path = capture::capture_path("screenshot", "png");
+                        match capture::write_png(&path, &g.game.emu, &g.game.palette) {
+                            Ok(()) => info!("saved screenshot to {}", path),
+                            Err(e) => error!("failed to save screenshot: {}", e),
+                        }
+                    }
+                }
+
+                // Display effects: F8 phosphor decay, F9 scanlines, F11 pixel grid
+                if g.game.input.key_pressed(VirtualKeyCode::F8) {
+                    g.game.redraw = true;
+                    let display = &mut g.game.display;
+                    display.phosphor = !display.phosphor;
+                    info!("phosphor decay {}", on_off(display.phosphor));
+                }
+                if g.game.input.key_pressed(VirtualKeyCode::F9) {
+                    g.game.redraw = true;
+                    let display = &mut g.game.display;
+                    display.scanlines = !display.scanlines;
+                    info!("scanlines {}", on_off(display.scanlines));
+                }
+                if g.game.input.key_pressed(VirtualKeyCode::F11) {
+                    g.game.redraw = true;
+                    let display = &mut g.game.display;
+                    display.pixel_grid = !display.pixel_grid;
+                    info!("pixel grid {}", on_off(display.pixel_grid));
+                }
+
+                // Save states: F5 saves, F7 loads, F6 picks the slot, and with Shift F5 and F7
+                // copy and paste them through the clipboard
+                if g.game.input.key_pressed(VirtualKeyCode::F5) {
+                    if g.game.input.held_shift() {
+                        let game = &mut g.game;
+                        match game.clipboard.copy_state(&game.emu) {
+                            Ok(()) => info!("copied state to the clipboard"),
+                            Err(e) => error!("failed to copy state to clipboard: {}", e),
+                        }
+                    } else {
+                        g.game.save_state();
+                    }
+                }
+                if g.game.input.key_pressed(VirtualKeyCode::F6) {
+                    g.game.state_slot = (g.game.state_slot + 1) % 10;
+                    info!("save state slot {}", g.game.state_slot);
+                }
+                if g.game.input.key_pressed(VirtualKeyCode::F7) {
+                    if g.game.input.held_shift() {
+                        g.game.paste_state();
+                    } else {
+                        g.game.load_state();
+                    }
+                }
+
+                // ROMs dropped onto the window or opened with Ctrl+O replace the running one,
+                // and Ctrl+R restarts it
+                if let Some(path) = g.game.input.dropped_file() {
+                    open_rom(g, &path);
+                }
+                if g.game.input.held_control() && g.game.input.key_pressed(VirtualKeyCode::O) {
+                    if let Some(path) = pick_rom() {
+                        open_rom(g, &path);
+                    }
+                }
+                if g.game.input.held_control() && g.game.input.key_pressed(VirtualKeyCode::R) {
+                    match g.game.reset() {
+                        Ok(()) => g.window.set_title(&g.game.title()),
+                        Err(e) => error!("failed to reset: {}", e),
+                    }
+                }
+
+                // Ctrl+E exports the ROM as a .c8b file with the settings it runs with
+                if g.game.input.held_control()
+                    && g.game.input.key_pressed(VirtualKeyCode::E)
+                    && !g.game.rom_path.is_empty()
+                {
+                    match g.game.export_c8b() {
+                        Ok(path) => info!("exported the ROM to {}", path.display()),
+                        Err(e) => error!("failed to export the ROM: {}", e),
+                    }
+                }
+
+                // Speed: P pauses, the period key advances a frame while paused, + and - change
+                // the instruction rate, and holding Tab runs as fast as possible
+                if g.game.input.key_pressed(VirtualKeyCode::P) {
+                    g.game.debugger.toggle_pause();
+                }
+                if g.game.input.key_pressed(VirtualKeyCode::Period) && g.game.debugger.is_paused() {
+                    g.game.advance_frame = true;
+                }
+                let input = &g.game.input;
+                let faster = [
+                    VirtualKeyCode::Equals,
+                    VirtualKeyCode::Plus,
+                    VirtualKeyCode::NumpadAdd,
+                ]
+                .into_iter()
+                .any(|key| input.key_pressed(key));
+                let slower = [VirtualKeyCode::Minus, VirtualKeyCode::NumpadSubtract]
+                    .into_iter()
+                    .any(|key| input.key_pressed(key));
+                if faster {
+                    g.game.change_speed(true);
+                }
+                if slower {
+                    g.game.change_speed(false);
+                }
+                g.game.turbo = g.game.input.key_held(VirtualKeyCode::Tab);
+
+                // Debugger: Space pauses and resumes, N steps, F10 steps over calls
+                if g.game.input.key_pressed(VirtualKeyCode::Space) {
+                    g.game.debugger.toggle_pause();
+                }
+                if g.game.input.key_pressed(VirtualKeyCode::N) {
+                    g.game.debugger.step();
+                }
+                if g.game.input.key_pressed(VirtualKeyCode::F10) {
+                    let game = &mut g.game;
+                    game.debugger.step_over(&game.emu);
+                }
+
+                #[cfg(feature = "debug-ui")]
+                if g.game.input.key_pressed(VirtualKeyCode::Grave) {
+                    g.game.debug_ui.visible = !g.game.debug_ui.visible;
+                    g.game.redraw = true;
                 }
             }
         },
@@ -0,0 +1,322 @@
+use sha1::{Digest, Sha1};
+use std::io;
+
+use crate::chip8::Chip8Emulator;
+use crate::error::Chip8Error;
+
+// Identifies movie files, and the version of their layout. Version 1 had no state hashes, and
+// version 2 no branches.
+const MAGIC: &[u8; 4] = b"C8M3";
+const MAGIC_V2: &[u8; 4] = b"C8M2";
+const MAGIC_V1: &[u8; 4] = b"C8M1";
+const HEADER_LEN: usize = MAGIC.len() + 8 + 20;
+
+/// How many frames apart the state hashes checked during playback are.
+pub const HASH_INTERVAL: usize = 60;
+
+/// The keys held in each frame of a run, and the seed its random numbers came from, so that the
+/// run can be played back exactly. Saved as `C8M3`, the seed, the ROM's SHA-1, the number of
+/// frames as four bytes, two bytes per frame with a bit per key, the number of state hashes as
+/// four bytes, the eight byte state hash at the end of every `HASH_INTERVAL`th frame, then the
+/// number of branches as four bytes and each branch as the length of its name as a byte, the
+/// name, the number of its frames as four bytes and the frames, all little-endian.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Movie {
+    pub seed: u64,
+    /// SHA-1 of the ROM the movie was recorded with.
+    pub rom_sha1: [u8; 20],
+    frames: Vec<u16>,
+    // `Chip8Emulator::state_hash()` at the end of frames HASH_INTERVAL - 1, 2 * HASH_INTERVAL - 1
+    // and so on.
+    hashes: Vec<u64>,
+    branches: Vec<Branch>,
+}
+
+/// A named point in a movie to come back to: the frames leading up to it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Branch {
+    pub name: String,
+    frames: Vec<u16>,
+}
+
+impl Branch {
+    /// The number of frames up to the branch point.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+}
+
+impl Movie {
+    /// An empty movie of `rom`, run with random numbers from `seed`.
+    pub fn new(seed: u64, rom: &[u8]) -> Self {
+        Self {
+            seed,
+            rom_sha1: Sha1::digest(rom).into(),
+            frames: Vec::new(),
+            hashes: Vec::new(),
+            branches: Vec::new(),
+        }
+    }
+
+    /// Add a frame with these keys held.
+    pub fn record(&mut self, keys: &[bool; 16]) {
+        self.frames.push(mask(keys));
+    }
+
+    /// Call at the end of every recorded frame, to keep the state hashes playback is checked
+    /// against.
+    pub fn end_frame(&mut self, emu: &Chip8Emulator) {
+        if let Some(n) = self.frames.len().checked_sub(1) {
+            self.hash_frame(n, emu);
+        }
+    }
+
+    /// Call at the end of frame `n` when it is played after an edit, to put back the state
+    /// hash the edit dropped. Hashes are only added in order, so frames must be played from the
+    /// last hashed one on.
+    pub fn hash_frame(&mut self, n: usize, emu: &Chip8Emulator) {
+        if (n + 1).is_multiple_of(HASH_INTERVAL) && (n + 1) / HASH_INTERVAL == self.hashes.len() + 1
+        {
+            self.hashes.push(emu.state_hash());
+        }
+    }
+
+    /// Change the keys held in frame `n`, which must be in the movie.
+    pub fn set_frame(&mut self, n: usize, keys: &[bool; 16]) {
+        self.frames[n] = mask(keys);
+        self.forget_hashes_from(n);
+    }
+
+    /// Add a frame before frame `n`, or at the end if `n` is the length of the movie.
+    pub fn insert_frame(&mut self, n: usize, keys: &[bool; 16]) {
+        self.frames.insert(n, mask(keys));
+        self.forget_hashes_from(n);
+    }
+
+    /// Drop frame `n`, which must be in the movie.
+    pub fn remove_frame(&mut self, n: usize) {
+        self.frames.remove(n);
+        self.forget_hashes_from(n);
+    }
+
+    /// Drop every frame from `len` on.
+    pub fn truncate(&mut self, len: usize) {
+        self.frames.truncate(len);
+        self.forget_hashes_from(len);
+    }
+
+    /// Keep the first `len` frames as a branch called `name`, replacing any branch already
+    /// called that. Names are cut to 255 bytes.
+    pub fn save_branch(&mut self, name: &str, len: usize) -> &Branch {
+        let mut end = name.len().min(255);
+        while !name.is_char_boundary(end) {
+            end -= 1;
+        }
+        let branch = Branch {
+            name: name[..end].to_string(),
+            frames: self.frames[..len].to_vec(),
+        };
+        match self.branches.iter().position(|b| b.name == branch.name) {
+            Some(i) => {
+                self.branches[i] = branch;
+                &self.branches[i]
+            }
+            None => {
+                self.branches.push(branch);
+                self.branches.last().unwrap()
+            }
+        }
+    }
+
+    /// Replace the frames with those leading up to the branch called `name`, returning the
+    /// first frame that changed, or `None` if there is no such branch.
+    pub fn switch_branch(&mut self, name: &str) -> Option<usize> {
+        let branch = self.branches.iter().find(|b| b.name == name)?;
+        let changed = self
+            .frames
+            .iter()
+            .zip(&branch.frames)
+            .take_while(|(a, b)| a == b)
+            .count();
+        self.frames.clone_from(&branch.frames);
+        self.forget_hashes_from(changed);
+        Some(changed)
+    }
+
+    pub fn remove_branch(&mut self, name: &str) {
+        self.branches.retain(|b| b.name != name);
+    }
+
+    pub fn branches(&self) -> &[Branch] {
+        &self.branches
+    }
+
+    // The state at the end of every frame from `n` on may have changed, so drop their hashes.
+    fn forget_hashes_from(&mut self, n: usize) {
+        self.hashes.truncate(n / HASH_INTERVAL);
+    }
+
+    /// Call at the end of frame `n` of playback. Fails if the movie has a state hash for that
+    /// frame and the machine no longer matches it.
+    pub fn check_frame(&self, n: usize, emu: &Chip8Emulator) -> Result<(), Chip8Error> {
+        if !(n + 1).is_multiple_of(HASH_INTERVAL) {
+            return Ok(());
+        }
+        match self.hashes.get((n + 1) / HASH_INTERVAL - 1) {
+            Some(hash) if *hash != emu.state_hash() => Err(Chip8Error::Desync { frame: n }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Drop the recorded frames, to start over from the beginning of the ROM.
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.hashes.clear();
+    }
+
+    /// The keys held in frame `n`, or `None` past the end of the movie.
+    pub fn frame(&self, n: usize) -> Option<[bool; 16]> {
+        let mask = self.frames.get(n)?;
+        Some(std::array::from_fn(|key| mask & 1 << key != 0))
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn matches_rom(&self, rom: &[u8]) -> bool {
+        self.rom_sha1 == <[u8; 20]>::from(Sha1::digest(rom))
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data =
+            Vec::with_capacity(HEADER_LEN + 12 + 2 * self.frames.len() + 8 * self.hashes.len());
+        data.extend_from_slice(MAGIC);
+        data.extend_from_slice(&self.seed.to_le_bytes());
+        data.extend_from_slice(&self.rom_sha1);
+        write_frames(&mut data, &self.frames);
+        data.extend_from_slice(&(self.hashes.len() as u32).to_le_bytes());
+        for hash in &self.hashes {
+            data.extend_from_slice(&hash.to_le_bytes());
+        }
+        data.extend_from_slice(&(self.branches.len() as u32).to_le_bytes());
+        for branch in &self.branches {
+            data.push(branch.name.len() as u8);
+            data.extend_from_slice(branch.name.as_bytes());
+            write_frames(&mut data, &branch.frames);
+        }
+        data
+    }
+
+    /// Read a movie, including those saved before state hashes were added, which play back
+    /// without being checked, and those saved before branches were.
+    pub fn from_bytes(data: &[u8]) -> io::Result<Self> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+        if data.len() < HEADER_LEN
+            || ![MAGIC, MAGIC_V2, MAGIC_V1]
+                .iter()
+                .any(|m| data.starts_with(*m))
+        {
+            return Err(invalid("not a movie file"));
+        }
+        let mut movie = Self {
+            seed: u64::from_le_bytes(data[4..12].try_into().unwrap()),
+            rom_sha1: data[12..HEADER_LEN].try_into().unwrap(),
+            frames: Vec::new(),
+            hashes: Vec::new(),
+            branches: Vec::new(),
+        };
+        let mut rest = &data[HEADER_LEN..];
+        if data.starts_with(MAGIC_V1) {
+            if !rest.len().is_multiple_of(2) {
+                return Err(invalid("movie file ends in the middle of a frame"));
+            }
+            movie.frames = read_masks(rest);
+            return Ok(movie);
+        }
+
+        movie.frames = read_frames(&mut rest)?;
+        let hashes = if data.starts_with(MAGIC_V2) {
+            std::mem::take(&mut rest)
+        } else {
+            let count = read_u32(&mut rest)? as usize;
+            take(&mut rest, 8usize.saturating_mul(count))?
+        };
+        if !hashes.len().is_multiple_of(8) || hashes.len() / 8 > movie.frames.len() / HASH_INTERVAL
+        {
+            return Err(invalid("movie file has damaged state hashes"));
+        }
+        movie.hashes = hashes
+            .chunks_exact(8)
+            .map(|hash| u64::from_le_bytes(hash.try_into().unwrap()))
+            .collect();
+        if data.starts_with(MAGIC) {
+            for _ in 0..read_u32(&mut rest)? {
+                let (&length, after) = rest
+                    .split_first()
+                    .ok_or_else(|| invalid("movie file is truncated"))?;
+                rest = after;
+                let name = take(&mut rest, usize::from(length))?;
+                let name = String::from_utf8(name.to_vec())
+                    .map_err(|_| invalid("movie file has a branch name that isn't UTF-8"))?;
+                let frames = read_frames(&mut rest)?;
+                movie.branches.push(Branch { name, frames });
+            }
+        }
+        if !rest.is_empty() {
+            return Err(invalid("movie file has data after its end"));
+        }
+        Ok(movie)
+    }
+}
+
+fn write_frames(data: &mut Vec<u8>, frames: &[u16]) {
+    data.extend_from_slice(&(frames.len() as u32).to_le_bytes());
+    for mask in frames {
+        data.extend_from_slice(&mask.to_le_bytes());
+    }
+}
+
+// Take the next `len` bytes of a movie file being read.
+fn take<'a>(rest: &mut &'a [u8], len: usize) -> io::Result<&'a [u8]> {
+    if rest.len() < len {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "movie file is truncated",
+        ));
+    }
+    let (taken, after) = rest.split_at(len);
+    *rest = after;
+    Ok(taken)
+}
+
+fn read_u32(rest: &mut &[u8]) -> io::Result<u32> {
+    Ok(u32::from_le_bytes(take(rest, 4)?.try_into().unwrap()))
+}
+
+// A count of frames and the frames.
+fn read_frames(rest: &mut &[u8]) -> io::Result<Vec<u16>> {
+    let count = read_u32(rest)? as usize;
+    Ok(read_masks(take(rest, 2usize.saturating_mul(count))?))
+}
+
+fn read_masks(data: &[u8]) -> Vec<u16> {
+    data.chunks_exact(2)
+        .map(|mask| u16::from_le_bytes([mask[0], mask[1]]))
+        .collect()
+}
+
+fn mask(keys: &[bool; 16]) -> u16 {
+    keys.iter()
+        .enumerate()
+        .filter(|(_, held)| **held)
+        .fold(0, |mask, (key, _)| mask | 1 << key)
+}
@@ -0,0 +1,348 @@
+use log::{error, info, warn};
+use pixels::Pixels;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use winit::event::{Event, VirtualKeyCode};
+use winit::event_loop::{ControlFlow, EventLoop};
+use winit::window::Window;
+use winit_input_helper::WinitInputHelper;
+
+use chip8emu::beeper::Beeper;
+use chip8emu::chip8::Chip8Emulator;
+use chip8emu::clock::TIMER_HZ;
+use chip8emu::palette::Palette;
+
+use crate::display::DisplayOptions;
+use crate::keymap::KeyMap;
+use crate::render::{self, Renderer};
+
+// Every message in either direction is a one byte type, a big-endian u16 payload length, and
+// the payload.
+//
+// Sent by the host:
+//   SCREEN: the screen's width and height in pixels, one byte each, then one byte per pixel,
+//           row by row, with the mask of the XO-CHIP planes lit there. Sent when a player joins,
+//           when the resolution changes, and in place of a DIFF that would be longer.
+//   DIFF:   the pixels that changed since the last frame, three bytes each: the pixel's index
+//           as a big-endian u16, then its planes.
+//   SOUND:  one byte, 1 while the buzzer sounds and 0 once it stops.
+// Sent by the guest:
+//   KEYS:   the keys held, as a big-endian u16 with bit N set for key N. Sent whenever they
+//           change.
+// Unknown message types are skipped.
+const SCREEN: u8 = 0x01;
+const SOUND: u8 = 0x02;
+const DIFF: u8 = 0x03;
+const KEYS: u8 = 0x01;
+
+pub fn write_message(stream: &mut impl Write, kind: u8, payload: &[u8]) -> io::Result<()> {
+    let len = u16::try_from(payload.len()).unwrap();
+    let mut message = vec![kind];
+    message.extend_from_slice(&len.to_be_bytes());
+    message.extend_from_slice(payload);
+    stream.write_all(&message)
+}
+
+pub fn read_message(stream: &mut impl Read) -> io::Result<(u8, Vec<u8>)> {
+    let mut header = [0; 3];
+    stream.read_exact(&mut header)?;
+    let mut payload = vec![0; usize::from(u16::from_be_bytes([header[1], header[2]]))];
+    stream.read_exact(&mut payload)?;
+    Ok((header[0], payload))
+}
+
+/// Runs the machine for a second player on another computer, who sees its screen, hears its
+/// buzzer and holds keys along with the local player.
+pub struct Host {
+    // Players who have just connected, from the thread accepting them.
+    joined: Receiver<TcpStream>,
+    // The player connected now, if any.
+    stream: Option<TcpStream>,
+    // The keys the player holds, bit N for key N, kept up to date by the accepting thread.
+    keys: Arc<AtomicU16>,
+    // The screen width and pixels the player was last sent, None until they get a whole screen.
+    sent: Option<(usize, Vec<u8>)>,
+    sounding: bool,
+}
+
+impl Host {
+    /// Wait for a player on `address`, in the background. Players who connect while another is
+    /// playing wait until that one leaves.
+    pub fn listen(address: &str) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        info!("waiting for a second player on {}", listener.local_addr()?);
+        let (joins, joined) = mpsc::channel();
+        let keys = Arc::new(AtomicU16::new(0));
+        let held = Arc::clone(&keys);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let accepted = stream.and_then(|stream| {
+                    stream.set_nodelay(true)?;
+                    let reader = stream.try_clone()?;
+                    let peer = stream.peer_addr()?;
+                    Ok((stream, reader, peer))
+                });
+                let (stream, mut reader, peer) = match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        warn!("failed to accept a player: {}", e);
+                        continue;
+                    }
+                };
+                info!("second player joined from {}", peer);
+                if joins.send(stream).is_err() {
+                    break;
+                }
+                while let Ok((kind, payload)) = read_message(&mut reader) {
+                    if let (KEYS, &[high, low]) = (kind, &payload[..]) {
+                        held.store(u16::from_be_bytes([high, low]), Ordering::Relaxed);
+                    }
+                }
+                held.store(0, Ordering::Relaxed);
+                info!("second player {} left", peer);
+            }
+        });
+        Ok(Self {
+            joined,
+            stream: None,
+            keys,
+            sent: None,
+            sounding: false,
+        })
+    }
+
+    /// Hold the keys the second player holds, on top of those already in `keys`.
+    pub fn add_keys(&self, keys: &mut [bool; 16]) {
+        let held = self.keys.load(Ordering::Relaxed);
+        for (key, down) in keys.iter_mut().enumerate() {
+            *down |= held & (1 << key) != 0;
+        }
+    }
+
+    /// Bring the second player's screen and buzzer up to date. Call once a frame.
+    pub fn send_frame(&mut self, emu: &Chip8Emulator, beeping: bool) {
+        if let Some(stream) = self.joined.try_iter().last() {
+            self.stream = Some(stream);
+            self.sent = None;
+            self.sounding = false;
+        }
+        let Some(stream) = &mut self.stream else {
+            return;
+        };
+        let mut send = || -> io::Result<()> {
+            if let Some((kind, payload)) = screen_update(self.sent.as_ref(), emu) {
+                write_message(stream, kind, &payload)?;
+                self.sent = Some((emu.screen_width(), emu.screen().to_vec()));
+            }
+            if beeping != self.sounding {
+                write_message(stream, SOUND, &[u8::from(beeping)])?;
+                self.sounding = beeping;
+            }
+            Ok(())
+        };
+        if let Err(e) = send() {
+            warn!("lost the second player: {}", e);
+            self.stream = None;
+        }
+    }
+}
+
+/// The message that brings a player who was last sent `sent` up to date with the screen, if
+/// anything changed.
+fn screen_update(sent: Option<&(usize, Vec<u8>)>, emu: &Chip8Emulator) -> Option<(u8, Vec<u8>)> {
+    let (width, screen) = (emu.screen_width(), emu.screen());
+    if let Some((sent_width, sent_screen)) = sent {
+        if *sent_width == width && sent_screen.len() == screen.len() {
+            let mut diff = Vec::new();
+            for (i, (old, new)) in sent_screen.iter().zip(screen).enumerate() {
+                if old != new {
+                    diff.extend_from_slice(&(i as u16).to_be_bytes());
+                    diff.push(*new);
+                }
+            }
+            if diff.is_empty() {
+                return None;
+            }
+            if diff.len() < screen.len() {
+                return Some((DIFF, diff));
+            }
+        }
+    }
+    let mut payload = vec![width as u8, emu.screen_height() as u8];
+    payload.extend_from_slice(screen);
+    Some((SCREEN, payload))
+}
+
+/// The host's screen and buzzer as the guest last heard of them.
+struct RemoteScreen {
+    width: usize,
+    pixels: Vec<u8>,
+    beeping: bool,
+    // Set when the pixels change, until the window is drawn.
+    changed: bool,
+    // Why the connection ended, once it has.
+    closed: Option<io::Error>,
+}
+
+impl RemoteScreen {
+    fn apply(&mut self, kind: u8, payload: &[u8]) {
+        match (kind, payload) {
+            (SCREEN, [width, height, pixels @ ..])
+                if pixels.len() == usize::from(*width) * usize::from(*height) =>
+            {
+                self.width = usize::from(*width);
+                self.pixels = pixels.to_vec();
+                self.changed = true;
+            }
+            (DIFF, _) => {
+                for change in payload.chunks_exact(3) {
+                    let index = usize::from(u16::from_be_bytes([change[0], change[1]]));
+                    if let Some(pixel) = self.pixels.get_mut(index) {
+                        *pixel = change[2];
+                    }
+                }
+                self.changed = true;
+            }
+            (SOUND, [sounding]) => self.beeping = *sounding != 0,
+            _ => {}
+        }
+    }
+}
+
+/// How the guest's window shows the host's screen and takes input.
+pub struct GuestSettings {
+    pub keymap: KeyMap,
+    pub palette: Palette,
+    pub display: DisplayOptions,
+    pub beeper: Box<dyn Beeper>,
+}
+
+/// Plays on a machine run by another instance's `Host`: shows its screen and sends the keys
+/// held here.
+pub struct Guest {
+    stream: TcpStream,
+    remote: Arc<Mutex<RemoteScreen>>,
+}
+
+impl Guest {
+    pub fn connect(address: &str) -> io::Result<Self> {
+        let stream = TcpStream::connect(address)?;
+        stream.set_nodelay(true)?;
+        info!("joined the game at {}", address);
+        let remote = Arc::new(Mutex::new(RemoteScreen {
+            width: 64,
+            pixels: vec![0; 64 * 32],
+            beeping: false,
+            changed: true,
+            closed: None,
+        }));
+
+        // The host's messages are read on their own thread, so that the window stays
+        // responsive while it is quiet.
+        let mut reader = stream.try_clone()?;
+        let heard = Arc::clone(&remote);
+        thread::spawn(move || loop {
+            match read_message(&mut reader) {
+                Ok((kind, payload)) => heard.lock().unwrap().apply(kind, &payload),
+                Err(e) => {
+                    heard.lock().unwrap().closed = Some(e);
+                    break;
+                }
+            }
+        });
+        Ok(Self { stream, remote })
+    }
+
+    /// Show the host's screen in `window` until it is closed or the host goes away.
+    pub fn run<T>(
+        self,
+        event_loop: EventLoop<T>,
+        window: Window,
+        mut pixels: Pixels,
+        settings: GuestSettings,
+    ) -> ! {
+        let GuestSettings {
+            keymap,
+            palette,
+            display,
+            mut beeper,
+        } = settings;
+        let Self { mut stream, remote } = self;
+        let mut input = WinitInputHelper::new();
+        let mut renderer = Renderer::new();
+        let mut screen_size = (0, 0);
+        let mut sent_keys = [false; 16];
+        let frame_time = Duration::from_secs_f64(1.0 / f64::from(TIMER_HZ));
+
+        event_loop.run(move |event, _, control_flow| {
+            if let Event::RedrawRequested(_) = event {
+                let remote = remote.lock().unwrap();
+                let (width, height) = (remote.width, remote.pixels.len() / remote.width.max(1));
+                if (width, height) != screen_size {
+                    let scale = render::SCALE;
+                    pixels.resize_buffer((width * scale) as u32, (height * scale) as u32);
+                    screen_size = (width, height);
+                }
+                let colors = palette.colors();
+                let frame: Vec<u8> = remote
+                    .pixels
+                    .iter()
+                    .flat_map(|planes| colors[usize::from(*planes & 3)])
+                    .collect();
+                drop(remote);
+                renderer.render(
+                    &frame,
+                    width,
+                    palette.background,
+                    &display,
+                    pixels.get_frame(),
+                );
+                if let Err(e) = pixels.render() {
+                    error!("pixels.render() failed: {}", e);
+                    *control_flow = ControlFlow::Exit;
+                }
+                return;
+            }
+
+            if !input.update(&event) {
+                return;
+            }
+            if input.key_pressed(VirtualKeyCode::Escape) || input.quit() {
+                *control_flow = ControlFlow::Exit;
+                return;
+            }
+            if let Some(size) = input.window_resized() {
+                pixels.resize_surface(size.width, size.height);
+                window.request_redraw();
+            }
+
+            let keys = keymap.held(&input);
+            if keys != sent_keys {
+                let bits = (0..16).fold(0u16, |bits, key| bits | u16::from(keys[key]) << key);
+                if let Err(e) = write_message(&mut stream, KEYS, &bits.to_be_bytes()) {
+                    error!("lost the connection to the host: {}", e);
+                    *control_flow = ControlFlow::Exit;
+                    return;
+                }
+                sent_keys = keys;
+            }
+
+            let mut remote = remote.lock().unwrap();
+            if let Some(e) = &remote.closed {
+                info!("the host ended the game: {}", e);
+                *control_flow = ControlFlow::Exit;
+                return;
+            }
+            if std::mem::take(&mut remote.changed) || renderer.is_fading() {
+                window.request_redraw();
+            }
+            beeper.set_beeping(remote.beeping);
+            *control_flow = ControlFlow::WaitUntil(Instant::now() + frame_time);
+        })
+    }
+}
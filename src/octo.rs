@@ -0,0 +1,90 @@
+use log::{info, warn};
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+use chip8emu::palette::{parse_color, Palette};
+use chip8emu::quirks::Quirks;
+
+/// Project options saved by Octo alongside an exported ROM, read from a `.json` file with the
+/// same name as the ROM. Only the settings this emulator supports are applied.
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct OctoOptions {
+    /// Instructions executed per 60 Hz frame.
+    tickrate: Option<u32>,
+    fill_color: Option<String>,
+    fill_color2: Option<String>,
+    blend_color: Option<String>,
+    background_color: Option<String>,
+    shift_quirks: bool,
+    load_store_quirks: bool,
+    jump_quirks: bool,
+    clip_quirks: bool,
+    logic_quirks: bool,
+    v_blank_quirks: bool,
+    vf_order_quirks: bool,
+}
+
+impl OctoOptions {
+    pub fn for_rom(rom_path: &str) -> Option<Self> {
+        let options_path = Path::new(rom_path).with_extension("json");
+        let text = fs::read_to_string(&options_path).ok()?;
+        match serde_json::from_str::<Self>(&text) {
+            Ok(options) => {
+                info!("using Octo options from {}", options_path.display());
+                options.warn_unsupported_quirks();
+                Some(options)
+            }
+            Err(e) => {
+                warn!("ignoring {}: {}", options_path.display(), e);
+                None
+            }
+        }
+    }
+
+    fn warn_unsupported_quirks(&self) {
+        if self.vf_order_quirks {
+            warn!("vfOrderQuirks is not supported yet, the ROM may misbehave");
+        }
+    }
+
+    /// Octo's quirk settings. Octo counts a quirk as on when it differs from the original
+    /// interpreter, so some are inverted.
+    pub fn quirks(&self) -> Quirks {
+        Quirks {
+            vf_reset: self.logic_quirks,
+            shift_vy: !self.shift_quirks,
+            increment_i: !self.load_store_quirks,
+            jump_vx: self.jump_quirks,
+            clip_sprites: self.clip_quirks,
+            display_wait: self.v_blank_quirks,
+        }
+    }
+
+    /// Instructions to execute per 60 Hz frame.
+    pub fn instructions_per_frame(&self) -> Option<u32> {
+        self.tickrate
+    }
+
+    /// The ROM's colors, if it sets any. Colors it leaves out are the default palette's.
+    pub fn palette(&self) -> Option<Palette> {
+        let color = |color: &Option<String>| color.as_deref().and_then(parse_color);
+        let colors = [
+            color(&self.background_color),
+            color(&self.fill_color),
+            color(&self.fill_color2),
+            color(&self.blend_color),
+        ];
+        if colors.iter().all(Option::is_none) {
+            return None;
+        }
+        let default = Palette::default();
+        Some(Palette {
+            background: colors[0].unwrap_or(default.background),
+            foreground: colors[1].unwrap_or(default.foreground),
+            plane_2: colors[2].unwrap_or(default.plane_2),
+            both_planes: colors[3].unwrap_or(default.both_planes),
+        })
+    }
+}
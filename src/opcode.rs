@@ -1,83 +1,404 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::chip8::Variant;
+
 type Address = u16;
 type Const8 = u8;
 type Const4 = u8;
 type RegId = u8;
 
-// All 35 opcodes
+// All 35 CHIP-8 opcodes, followed by the SUPER-CHIP 1.1 and XO-CHIP additions
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Opcode {
+    /// 0NNN
     MachineCode(Address),
+    /// 00E0
     ClearScreen,
+    /// 00EE
     ReturnFromSub,
+    /// 1NNN
     Goto(Address),
+    /// 2NNN
     CallSub(Address),
+    /// 3XNN
     SkipEQ(RegId, Const8),
+    /// 4XNN
     SkipNEQ(RegId, Const8),
+    /// 5XY0
     SkipRegEQ(RegId, RegId),
+    /// 6XNN
     SetConst(RegId, Const8),
+    /// 7XNN
     AddConst(RegId, Const8),
+    /// 8XY0
     SetReg(RegId, RegId),
+    /// 8XY1
     Or(RegId, RegId),
+    /// 8XY2
     And(RegId, RegId),
+    /// 8XY3
     Xor(RegId, RegId),
+    /// 8XY4
     AddReg(RegId, RegId),
+    /// 8XY5
     SubReg(RegId, RegId),
+    /// 8XY6
     Div2(RegId, RegId),
+    /// 8XY7
     DiffReg(RegId, RegId),
+    /// 8XYE
     Mul2(RegId, RegId),
+    /// 9XY0
     SkipRegNEQ(RegId, RegId),
+    /// ANNN
     SetAR(Address),
+    /// BNNN
     Jump(Address),
+    /// CXNN
     Rand(RegId, Const8),
+    /// DXYN
     Draw(RegId, RegId, Const4),
+    /// EX9E
     KeyEQ(RegId),
+    /// EXA1
     KeyNEQ(RegId),
+    /// FX07
     GetDelayTimer(RegId),
+    /// FX0A
     GetKey(RegId),
+    /// FX15
     SetDelayTimer(RegId),
+    /// FX18
     SetSoundTimer(RegId),
+    /// FX1E
     AddToI(RegId),
+    /// FX29
     SetISprite(RegId),
-    BCD(RegId), // no clue rn - trying to understand a lecture while writing this
+    /// FX33: store the decimal digits of VX at I, I+1 and I+2.
+    BCD(RegId),
+    /// FX55
     RegDump(RegId),
+    /// FX65
     RegLoad(RegId),
+    /// 00CN
+    ScrollDown(Const4),
+    /// 00FB
+    ScrollRight,
+    /// 00FC
+    ScrollLeft,
+    /// 00FD
+    Exit,
+    /// 00FE
+    LowRes,
+    /// 00FF
+    HighRes,
+    /// FX30
+    SetIBigSprite(RegId),
+    /// FX75
+    SaveFlags(RegId),
+    /// FX85
+    LoadFlags(RegId),
+    /// 00DN
+    ScrollUp(Const4),
+    /// 5XY2
+    SaveRange(RegId, RegId),
+    /// 5XY3
+    LoadRange(RegId, RegId),
+    /// F000 NNNN: the address is in the following word.
+    SetILong,
+    /// FN01
+    SelectPlanes(Const4),
+    /// F002
+    LoadAudio,
+    /// FX3A
+    SetPitch(RegId),
 }
 
-mod nibble {
-    enum Nibble {
-        x0,
-        x1,
-        x2,
-        x3,
-        x4,
-        x5,
-        x6,
-        x7,
-        x8,
-        x9,
-        xA,
-        xB,
-        xC,
-        xD,
-        xE,
-        xF,
+impl Opcode {
+    /// The first dialect to have this instruction. On CHIP-8, 00CN to 00FF are machine code
+    /// calls.
+    pub fn variant(&self) -> Variant {
+        use Opcode::*;
+        match self {
+            ScrollDown(_) | ScrollRight | ScrollLeft | Exit | LowRes | HighRes
+            | SetIBigSprite(_) | SaveFlags(_) | LoadFlags(_) => Variant::SuperChip,
+            ScrollUp(_) | SaveRange(..) | LoadRange(..) | SetILong | SelectPlanes(_)
+            | LoadAudio | SetPitch(_) => Variant::XoChip,
+            _ => Variant::Chip8,
+        }
+    }
+
+    /// Roughly how many machine cycles (8 clock periods of the 1.76 MHz CDP1802) the COSMAC
+    /// VIP's interpreter takes over this instruction, including fetching and decoding it.
+    /// Skips are counted as not taken, BCD as if every digit were 0, and draws without the
+    /// wait for the display. Instructions the VIP never had cost as much as the closest one it
+    /// did.
+    pub fn vip_cycles(&self) -> u32 {
+        use Opcode::*;
+        // Fetching the two bytes and jumping to the handler, common to every instruction.
+        const FETCH: u32 = 40;
+        // Clearing or scrolling the 256 bytes of display memory.
+        const WHOLE_SCREEN: u32 = 3078;
+        let registers = |x: u8| 14 + 14 * u32::from(x);
+        FETCH
+            + match *self {
+                ClearScreen | ScrollDown(_) | ScrollRight | ScrollLeft | ScrollUp(_) => {
+                    WHOLE_SCREEN
+                }
+                ReturnFromSub => 10,
+                Goto(_) | SetAR(_) | MachineCode(_) => 12,
+                CallSub(_) => 26,
+                SkipEQ(..) | SkipNEQ(..) => 10,
+                SkipRegEQ(..) | SkipRegNEQ(..) | KeyEQ(_) | KeyNEQ(_) => 14,
+                SetConst(..) => 6,
+                AddConst(..) => 10,
+                SetReg(..) => 12,
+                Or(..) | And(..) | Xor(..) | AddReg(..) | SubReg(..) | Div2(..) | DiffReg(..)
+                | Mul2(..) => 44,
+                Jump(_) => 22,
+                Rand(..) => 36,
+                // Each row is shifted into place and XORed into two bytes of display memory.
+                Draw(_, _, n) => 26 + 68 * u32::from(n),
+                GetDelayTimer(_) | GetKey(_) | SetDelayTimer(_) | SetSoundTimer(_) => 10,
+                Exit | LowRes | HighRes | SelectPlanes(_) | SetPitch(_) => 10,
+                AddToI(_) | SetISprite(_) | SetIBigSprite(_) => 16,
+                SetILong => 24,
+                BCD(_) => 80,
+                RegDump(x) | RegLoad(x) | SaveFlags(x) | LoadFlags(x) => registers(x),
+                SaveRange(x, y) | LoadRange(x, y) => registers(x.abs_diff(y)),
+                LoadAudio => registers(15),
+            }
+    }
+
+    /// The address operand of jumps, calls and `LD I`.
+    pub fn address(&self) -> Option<Address> {
+        use Opcode::*;
+        match *self {
+            MachineCode(address) | Goto(address) | CallSub(address) | SetAR(address)
+            | Jump(address) => Some(address),
+            _ => None,
+        }
+    }
+
+    /// The assembly mnemonic, with `target` written in place of the address operand, e.g. a
+    /// label. Follows Cowgod's reference, and the usual names for the SUPER-CHIP and XO-CHIP
+    /// additions.
+    pub fn mnemonic(&self, target: &str) -> String {
+        use Opcode::*;
+        match *self {
+            MachineCode(_) => format!("SYS {}", target),
+            ClearScreen => "CLS".to_string(),
+            ReturnFromSub => "RET".to_string(),
+            Goto(_) => format!("JP {}", target),
+            CallSub(_) => format!("CALL {}", target),
+            SkipEQ(x, c) => format!("SE V{:X}, {:#04X}", x, c),
+            SkipNEQ(x, c) => format!("SNE V{:X}, {:#04X}", x, c),
+            SkipRegEQ(x, y) => format!("SE V{:X}, V{:X}", x, y),
+            SetConst(x, c) => format!("LD V{:X}, {:#04X}", x, c),
+            AddConst(x, c) => format!("ADD V{:X}, {:#04X}", x, c),
+            SetReg(x, y) => format!("LD V{:X}, V{:X}", x, y),
+            Or(x, y) => format!("OR V{:X}, V{:X}", x, y),
+            And(x, y) => format!("AND V{:X}, V{:X}", x, y),
+            Xor(x, y) => format!("XOR V{:X}, V{:X}", x, y),
+            AddReg(x, y) => format!("ADD V{:X}, V{:X}", x, y),
+            SubReg(x, y) => format!("SUB V{:X}, V{:X}", x, y),
+            Div2(x, y) => format!("SHR V{:X}, V{:X}", x, y),
+            DiffReg(x, y) => format!("SUBN V{:X}, V{:X}", x, y),
+            Mul2(x, y) => format!("SHL V{:X}, V{:X}", x, y),
+            SkipRegNEQ(x, y) => format!("SNE V{:X}, V{:X}", x, y),
+            SetAR(_) => format!("LD I, {}", target),
+            Jump(_) => format!("JP V0, {}", target),
+            Rand(x, c) => format!("RND V{:X}, {:#04X}", x, c),
+            Draw(x, y, n) => format!("DRW V{:X}, V{:X}, {:#X}", x, y, n),
+            KeyEQ(x) => format!("SKP V{:X}", x),
+            KeyNEQ(x) => format!("SKNP V{:X}", x),
+            GetDelayTimer(x) => format!("LD V{:X}, DT", x),
+            GetKey(x) => format!("LD V{:X}, K", x),
+            SetDelayTimer(x) => format!("LD DT, V{:X}", x),
+            SetSoundTimer(x) => format!("LD ST, V{:X}", x),
+            AddToI(x) => format!("ADD I, V{:X}", x),
+            SetISprite(x) => format!("LD F, V{:X}", x),
+            BCD(x) => format!("LD B, V{:X}", x),
+            RegDump(x) => format!("LD [I], V{:X}", x),
+            RegLoad(x) => format!("LD V{:X}, [I]", x),
+            ScrollDown(n) => format!("SCD {:#X}", n),
+            ScrollRight => "SCR".to_string(),
+            ScrollLeft => "SCL".to_string(),
+            Exit => "EXIT".to_string(),
+            LowRes => "LOW".to_string(),
+            HighRes => "HIGH".to_string(),
+            SetIBigSprite(x) => format!("LD HF, V{:X}", x),
+            SaveFlags(x) => format!("LD R, V{:X}", x),
+            LoadFlags(x) => format!("LD V{:X}, R", x),
+            ScrollUp(n) => format!("SCU {:#X}", n),
+            SaveRange(x, y) => format!("SAVE V{:X} - V{:X}", x, y),
+            LoadRange(x, y) => format!("LOAD V{:X} - V{:X}", x, y),
+            SetILong => "LD I, LONG".to_string(),
+            SelectPlanes(n) => format!("PLANE {}", n),
+            LoadAudio => "AUDIO".to_string(),
+            SetPitch(x) => format!("PITCH V{:X}", x),
+        }
     }
 
-    fn split_u16(value: u16) -> (Nibble, Nibble, Nibble, Nibble) {
-        (
-            ((value >> 24) & 0xF).try_into().unwrap(),
-            ((value >> 16) & 0xF).try_into().unwrap(),
-            ((value >> 8) & 0xF).try_into().unwrap(),
-            ((value >> 0) & 0xF).try_into().unwrap(),
-        )
+    /// Encode the instruction as its 16 bit word, the inverse of `from_value`. `SetILong`'s
+    /// address isn't part of the word and has to follow it.
+    pub fn to_value(&self) -> u16 {
+        use Opcode::*;
+        let xy = |x: RegId, y: RegId, n: u16| 0x100 * u16::from(x) + 0x10 * u16::from(y) + n;
+        let xnn = |x: RegId, nn: Const8| 0x100 * u16::from(x) + u16::from(nn);
+        match *self {
+            MachineCode(address) => address & 0xFFF,
+            ClearScreen => 0x00E0,
+            ReturnFromSub => 0x00EE,
+            Goto(address) => 0x1000 | address & 0xFFF,
+            CallSub(address) => 0x2000 | address & 0xFFF,
+            SkipEQ(x, c) => 0x3000 | xnn(x, c),
+            SkipNEQ(x, c) => 0x4000 | xnn(x, c),
+            SkipRegEQ(x, y) => 0x5000 | xy(x, y, 0x0),
+            SetConst(x, c) => 0x6000 | xnn(x, c),
+            AddConst(x, c) => 0x7000 | xnn(x, c),
+            SetReg(x, y) => 0x8000 | xy(x, y, 0x0),
+            Or(x, y) => 0x8000 | xy(x, y, 0x1),
+            And(x, y) => 0x8000 | xy(x, y, 0x2),
+            Xor(x, y) => 0x8000 | xy(x, y, 0x3),
+            AddReg(x, y) => 0x8000 | xy(x, y, 0x4),
+            SubReg(x, y) => 0x8000 | xy(x, y, 0x5),
+            Div2(x, y) => 0x8000 | xy(x, y, 0x6),
+            DiffReg(x, y) => 0x8000 | xy(x, y, 0x7),
+            Mul2(x, y) => 0x8000 | xy(x, y, 0xE),
+            SkipRegNEQ(x, y) => 0x9000 | xy(x, y, 0x0),
+            SetAR(address) => 0xA000 | address & 0xFFF,
+            Jump(address) => 0xB000 | address & 0xFFF,
+            Rand(x, c) => 0xC000 | xnn(x, c),
+            Draw(x, y, n) => 0xD000 | xy(x, y, u16::from(n)),
+            KeyEQ(x) => 0xE09E | xnn(x, 0),
+            KeyNEQ(x) => 0xE0A1 | xnn(x, 0),
+            GetDelayTimer(x) => 0xF007 | xnn(x, 0),
+            GetKey(x) => 0xF00A | xnn(x, 0),
+            SetDelayTimer(x) => 0xF015 | xnn(x, 0),
+            SetSoundTimer(x) => 0xF018 | xnn(x, 0),
+            AddToI(x) => 0xF01E | xnn(x, 0),
+            SetISprite(x) => 0xF029 | xnn(x, 0),
+            BCD(x) => 0xF033 | xnn(x, 0),
+            RegDump(x) => 0xF055 | xnn(x, 0),
+            RegLoad(x) => 0xF065 | xnn(x, 0),
+            ScrollDown(n) => 0x00C0 | u16::from(n),
+            ScrollRight => 0x00FB,
+            ScrollLeft => 0x00FC,
+            Exit => 0x00FD,
+            LowRes => 0x00FE,
+            HighRes => 0x00FF,
+            SetIBigSprite(x) => 0xF030 | xnn(x, 0),
+            SaveFlags(x) => 0xF075 | xnn(x, 0),
+            LoadFlags(x) => 0xF085 | xnn(x, 0),
+            ScrollUp(n) => 0x00D0 | u16::from(n),
+            SaveRange(x, y) => 0x5000 | xy(x, y, 0x2),
+            LoadRange(x, y) => 0x5000 | xy(x, y, 0x3),
+            SetILong => 0xF000,
+            SelectPlanes(n) => 0xF001 | xnn(n, 0),
+            LoadAudio => 0xF002,
+            SetPitch(x) => 0xF03A | xnn(x, 0),
+        }
     }
 }
 
-pub fn from_value(value: u16) -> Opcode {
-    use nibbles::Nibble::*;
-    let nibbles = nibble::split_u16(value);
-    match nibbles {
-        (x0, n1, n2, n3) => 
-        _ => panic!();
+impl fmt::Display for Opcode {
+    /// The mnemonic, with any address operand as a plain number.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let target = self
+            .address()
+            .map(|address| format!("{:#05X}", address))
+            .unwrap_or_default();
+        f.pad(&self.mnemonic(&target))
     }
-    Opcode::ClearScreen
+}
+
+/// A word that doesn't decode to any instruction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UnknownOpcode(pub u16);
+
+impl fmt::Display for UnknownOpcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:#06X} is not a recognized opcode", self.0)
+    }
+}
+
+impl Error for UnknownOpcode {}
+
+pub fn from_value(value: u16) -> Result<Opcode, UnknownOpcode> {
+    use Opcode::*;
+    // Every field an instruction might have. Decoding runs for every instruction executed, so
+    // it dispatches on the top nibble first rather than matching all four at once.
+    let x = ((value >> 8) & 0xF) as u8;
+    let y = ((value >> 4) & 0xF) as u8;
+    let n = (value & 0xF) as u8;
+    let nn = value as u8;
+    let nnn = value & 0xFFF;
+    let opcode = match value >> 12 {
+        0x0 => match nnn {
+            0x0E0 => ClearScreen,
+            0x0EE => ReturnFromSub,
+            0x0C0..=0x0CF => ScrollDown(n),
+            0x0D0..=0x0DF => ScrollUp(n),
+            0x0FB => ScrollRight,
+            0x0FC => ScrollLeft,
+            0x0FD => Exit,
+            0x0FE => LowRes,
+            0x0FF => HighRes,
+            _ => MachineCode(nnn),
+        },
+        0x1 => Goto(nnn),
+        0x2 => CallSub(nnn),
+        0x3 => SkipEQ(x, nn),
+        0x4 => SkipNEQ(x, nn),
+        0x5 => match n {
+            0x0 => SkipRegEQ(x, y),
+            0x2 => SaveRange(x, y),
+            0x3 => LoadRange(x, y),
+            _ => return Err(UnknownOpcode(value)),
+        },
+        0x6 => SetConst(x, nn),
+        0x7 => AddConst(x, nn),
+        0x8 => match n {
+            0x0 => SetReg(x, y),
+            0x1 => Or(x, y),
+            0x2 => And(x, y),
+            0x3 => Xor(x, y),
+            0x4 => AddReg(x, y),
+            0x5 => SubReg(x, y),
+            0x6 => Div2(x, y),
+            0x7 => DiffReg(x, y),
+            0xE => Mul2(x, y),
+            _ => return Err(UnknownOpcode(value)),
+        },
+        0x9 if n == 0 => SkipRegNEQ(x, y),
+        0xA => SetAR(nnn),
+        0xB => Jump(nnn),
+        0xC => Rand(x, nn),
+        0xD => Draw(x, y, n),
+        0xE if nn == 0x9E => KeyEQ(x),
+        0xE if nn == 0xA1 => KeyNEQ(x),
+        0xF => match nn {
+            0x00 if x == 0 => SetILong,
+            0x01 => SelectPlanes(x),
+            0x02 if x == 0 => LoadAudio,
+            0x07 => GetDelayTimer(x),
+            0x0A => GetKey(x),
+            0x15 => SetDelayTimer(x),
+            0x18 => SetSoundTimer(x),
+            0x1E => AddToI(x),
+            0x29 => SetISprite(x),
+            0x30 => SetIBigSprite(x),
+            0x33 => BCD(x),
+            0x3A => SetPitch(x),
+            0x55 => RegDump(x),
+            0x65 => RegLoad(x),
+            0x75 => SaveFlags(x),
+            0x85 => LoadFlags(x),
+            _ => return Err(UnknownOpcode(value)),
+        },
+        _ => return Err(UnknownOpcode(value)),
+    };
+    Ok(opcode)
 }
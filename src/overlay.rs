@@ -0,0 +1,195 @@
+use std::time::{Duration, Instant};
+
+use chip8emu::chip8::Chip8Emulator;
+use chip8emu::disasm;
+
+// Glyphs are 3x5 pixels, with a pixel between characters and between lines.
+const GLYPH_WIDTH: usize = 3;
+const GLYPH_HEIGHT: usize = 5;
+pub const ADVANCE: usize = GLYPH_WIDTH + 1;
+pub const LINE_HEIGHT: usize = GLYPH_HEIGHT + 1;
+
+// Buffer width the text is drawn one buffer pixel per font pixel at; wider buffers, like
+// SUPER-CHIP's high resolution, scale it up to stay the same size on screen.
+const BASE_WIDTH: usize = 256;
+
+const TEXT: [u8; 4] = [0xff, 0xff, 0xff, 0xff];
+// How much of the screen shows through the box behind the text.
+const SHADE: u32 = 30;
+
+// How often the instruction rate is measured.
+const SAMPLE_PERIOD: Duration = Duration::from_secs(1);
+
+/// Debug overlay with the registers, timers, next instruction and measured speed, drawn over
+/// the top left of the screen.
+pub struct Overlay {
+    // Instructions executed when the current sample started, and when that was.
+    sample_start: (u64, Instant),
+    // Instructions per second over the last complete sample.
+    rate: u64,
+}
+
+impl Overlay {
+    pub fn new() -> Self {
+        Self {
+            sample_start: (0, Instant::now()),
+            rate: 0,
+        }
+    }
+
+    /// Measure the instruction rate, given how many instructions have run so far.
+    pub fn update(&mut self, executed: u64) {
+        let (start_count, start_time) = self.sample_start;
+        let elapsed = start_time.elapsed();
+        if elapsed >= SAMPLE_PERIOD {
+            let count = executed.saturating_sub(start_count);
+            self.rate = (count as f64 / elapsed.as_secs_f64()).round() as u64;
+            self.sample_start = (executed, Instant::now());
+        }
+    }
+
+    fn lines(&self, emu: &Chip8Emulator) -> Vec<String> {
+        let memory = emu.memory();
+        let word = u16::from(memory[emu.pc() % memory.len()]) << 8
+            | u16::from(memory[(emu.pc() + 1) % memory.len()]);
+        let mut lines = vec![
+            format!("PC {:03X}  I {:03X}  SP {}", emu.pc(), emu.i(), emu.sp()),
+            format!(
+                "DT {:02X}  ST {:02X}  {} IPS",
+                emu.delay_timer(),
+                emu.sound_timer(),
+                self.rate
+            ),
+        ];
+        for (row, values) in emu.registers().chunks(4).enumerate() {
+            let cells: Vec<String> = values
+                .iter()
+                .enumerate()
+                .map(|(i, value)| format!("V{:X} {:02X}", row * 4 + i, value))
+                .collect();
+            lines.push(cells.join(" "));
+        }
+        lines.push(format!(
+            "{:04X} {}",
+            word,
+            disasm::format_instruction(word).unwrap_or_else(|| "?".to_string())
+        ));
+        lines
+    }
+
+    /// Draw the overlay into an RGBA buffer `width` pixels wide.
+    pub fn draw(&self, emu: &Chip8Emulator, buffer: &mut [u8], width: usize) {
+        let scale = (width / BASE_WIDTH).max(1);
+        let height = buffer.len() / 4 / width;
+        let lines = self.lines(emu);
+
+        // Shade a box behind the text so it can be read over anything.
+        let columns = lines.iter().map(|line| line.len()).max().unwrap_or(0);
+        let box_width = ((columns * ADVANCE + 1) * scale).min(width);
+        let box_height = ((lines.len() * LINE_HEIGHT + 1) * scale).min(height);
+        for y in 0..box_height {
+            for x in 0..box_width {
+                let offset = 4 * (y * width + x);
+                for channel in &mut buffer[offset..offset + 3] {
+                    *channel = (u32::from(*channel) * SHADE / 100) as u8;
+                }
+            }
+        }
+
+        for (row, line) in lines.iter().enumerate() {
+            let position = (scale, (1 + row * LINE_HEIGHT) * scale);
+            draw_text(buffer, width, position, scale, line, TEXT);
+        }
+    }
+}
+
+/// Draw a line of text with its top left corner at `(left, top)`, `scale` buffer pixels per
+/// font pixel, into an RGBA buffer `width` pixels wide. Text past the edges is cut off.
+pub fn draw_text(
+    buffer: &mut [u8],
+    width: usize,
+    (left, top): (usize, usize),
+    scale: usize,
+    text: &str,
+    color: [u8; 4],
+) {
+    let height = buffer.len() / 4 / width;
+    for (column, c) in text.chars().enumerate() {
+        let left = left + column * ADVANCE * scale;
+        for (gy, bits) in glyph(c).iter().enumerate() {
+            for gx in 0..GLYPH_WIDTH {
+                if bits & (0b100 >> gx) == 0 {
+                    continue;
+                }
+                for y in top + gy * scale..top + (gy + 1) * scale {
+                    for x in left + gx * scale..left + (gx + 1) * scale {
+                        if x < width && y < height {
+                            let offset = 4 * (y * width + x);
+                            buffer[offset..offset + 4].copy_from_slice(&color);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Rows of a character's 3x5 glyph, top first, with the leftmost pixel in bit 2. Lowercase
+/// letters are drawn as capitals.
+fn glyph(c: char) -> [u8; GLYPH_HEIGHT] {
+    match c.to_ascii_uppercase() {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        ' ' => [0; GLYPH_HEIGHT],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        '!' => [0b010, 0b010, 0b010, 0b000, 0b010],
+        '\'' => [0b010, 0b010, 0b000, 0b000, 0b000],
+        '(' => [0b010, 0b100, 0b100, 0b100, 0b010],
+        ')' => [0b010, 0b001, 0b001, 0b001, 0b010],
+        '>' => [0b100, 0b010, 0b001, 0b010, 0b100],
+        '=' => [0b000, 0b111, 0b000, 0b111, 0b000],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '[' => [0b110, 0b100, 0b100, 0b100, 0b110],
+        ']' => [0b011, 0b001, 0b001, 0b001, 0b011],
+        _ => [0b111, 0b001, 0b010, 0b000, 0b010],
+    }
+}
@@ -0,0 +1,148 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Colors the screen is drawn in. XO-CHIP draws on two bit planes, so a pixel can be unlit, lit
+/// on the first plane, the second, or both; other ROMs only ever use the first two colors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Palette {
+    pub background: [u8; 4],
+    pub foreground: [u8; 4],
+    /// Pixels lit on XO-CHIP's second plane only.
+    pub plane_2: [u8; 4],
+    /// Pixels lit on both planes.
+    pub both_planes: [u8; 4],
+}
+
+/// Built-in themes, by name.
+pub const THEMES: [(&str, Palette); 5] = [
+    (
+        "white",
+        Palette::new([0xff, 0xff, 0xff, 0xff], [0, 0, 0, 0xff]),
+    ),
+    (
+        "green",
+        Palette {
+            background: [0x0a, 0x1a, 0x0f, 0xff],
+            foreground: [0x33, 0xff, 0x66, 0xff],
+            plane_2: [0x1f, 0x99, 0x3d, 0xff],
+            both_planes: [0xb3, 0xff, 0xc6, 0xff],
+        },
+    ),
+    (
+        "amber",
+        Palette {
+            background: [0x1a, 0x10, 0x00, 0xff],
+            foreground: [0xff, 0xb0, 0x00, 0xff],
+            plane_2: [0x99, 0x69, 0x00, 0xff],
+            both_planes: [0xff, 0xe0, 0x99, 0xff],
+        },
+    ),
+    (
+        "high-contrast",
+        Palette {
+            background: [0, 0, 0, 0xff],
+            foreground: [0xff, 0xff, 0x00, 0xff],
+            plane_2: [0x00, 0xff, 0xff, 0xff],
+            both_planes: [0xff, 0xff, 0xff, 0xff],
+        },
+    ),
+    (
+        "octo",
+        Palette::new([0xff, 0xcc, 0x00, 0xff], [0x99, 0x66, 0x00, 0xff]),
+    ),
+];
+
+impl Palette {
+    /// Two colors, with XO-CHIP's second plane in Octo's default colors.
+    pub const fn new(foreground: [u8; 4], background: [u8; 4]) -> Self {
+        Self {
+            background,
+            foreground,
+            plane_2: [0xff, 0x66, 0x00, 0xff],
+            both_planes: [0x66, 0x22, 0x00, 0xff],
+        }
+    }
+
+    pub fn theme(name: &str) -> Option<Self> {
+        THEMES
+            .iter()
+            .find(|(theme, _)| *theme == name)
+            .map(|(_, palette)| *palette)
+    }
+
+    /// The colors indexed by the planes lit at a pixel, as in `Chip8Emulator::screen()`.
+    pub fn colors(&self) -> [[u8; 4]; 4] {
+        [
+            self.background,
+            self.foreground,
+            self.plane_2,
+            self.both_planes,
+        ]
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        THEMES[0].1
+    }
+}
+
+impl FromStr for Palette {
+    type Err = String;
+
+    /// Parses a theme name, or colors like `#FFCC00,#996600`: foreground and background, then
+    /// optionally the colors of the second plane and of both planes.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(palette) = Self::theme(s) {
+            return Ok(palette);
+        }
+        let colors = s
+            .split(',')
+            .map(|color| parse_color(color).ok_or(format!("{} is not a #RRGGBB color", color)))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| {
+                let names: Vec<&str> = THEMES.iter().map(|(name, _)| *name).collect();
+                format!("{}; expected colors or one of {}", e, names.join(", "))
+            })?;
+        match colors[..] {
+            [foreground, background] => Ok(Self::new(foreground, background)),
+            [foreground, background, plane_2, both_planes] => Ok(Self {
+                background,
+                foreground,
+                plane_2,
+                both_planes,
+            }),
+            _ => Err("expected 2 or 4 colors".to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Palette {
+    /// The theme's name, or its colors as they would be parsed.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some((name, _)) = THEMES.iter().find(|(_, theme)| theme == self) {
+            return write!(f, "{}", name);
+        }
+        let colors: Vec<String> = [
+            self.foreground,
+            self.background,
+            self.plane_2,
+            self.both_planes,
+        ]
+        .iter()
+        .map(|[r, g, b, _]| format!("#{:02X}{:02X}{:02X}", r, g, b))
+        .collect();
+        write!(f, "{}", colors.join(","))
+    }
+}
+
+/// Parse a color like `#FFCC00` into opaque RGBA.
+pub fn parse_color(color: &str) -> Option<[u8; 4]> {
+    let hex = color.strip_prefix('#')?;
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    if hex.len() != 6 {
+        return None;
+    }
+    let [_, r, g, b] = value.to_be_bytes();
+    Some([r, g, b, 0xff])
+}
@@ -0,0 +1,185 @@
+use std::fs;
+use std::io;
+
+// Patched ROMs are loaded at 0x200 and must still fit in memory.
+const MAX_ROM_SIZE: usize = 4096 - 0x200;
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Apply an IPS or BPS patch file to a ROM image, returning the patched image.
+pub fn apply(patch_path: &str, rom: &[u8]) -> io::Result<Vec<u8>> {
+    let patch = fs::read(patch_path)?;
+    let patched = if patch.starts_with(b"PATCH") {
+        apply_ips(&patch, rom)?
+    } else if patch.starts_with(b"BPS1") {
+        apply_bps(&patch, rom)?
+    } else {
+        return Err(invalid("not an IPS or BPS patch"));
+    };
+    if patched.len() > MAX_ROM_SIZE {
+        return Err(invalid("patched ROM is too large to load"));
+    }
+    Ok(patched)
+}
+
+/// Reads the big-endian numbers and byte runs a patch is made of.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| invalid("patch is truncated"))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn number(&mut self, len: usize) -> io::Result<usize> {
+        Ok(self
+            .bytes(len)?
+            .iter()
+            .fold(0, |n, byte| (n << 8) | usize::from(*byte)))
+    }
+
+    /// BPS variable-length number.
+    fn varint(&mut self) -> io::Result<usize> {
+        let mut n = 0;
+        let mut shift = 1;
+        loop {
+            let byte = usize::from(self.bytes(1)?[0]);
+            n += (byte & 0x7f) * shift;
+            if byte & 0x80 != 0 {
+                return Ok(n);
+            }
+            shift <<= 7;
+            n += shift;
+            if shift > MAX_ROM_SIZE << 7 {
+                return Err(invalid("patch number out of range"));
+            }
+        }
+    }
+}
+
+fn apply_ips(patch: &[u8], rom: &[u8]) -> io::Result<Vec<u8>> {
+    let mut reader = Reader {
+        data: patch,
+        pos: b"PATCH".len(),
+    };
+    let mut out = rom.to_vec();
+    loop {
+        if reader.data[reader.pos..].starts_with(b"EOF") {
+            reader.pos += 3;
+            break;
+        }
+        let offset = reader.number(3)?;
+        let len = reader.number(2)?;
+        // A zero length record repeats one byte.
+        let (len, fill) = if len == 0 {
+            (reader.number(2)?, Some(reader.bytes(1)?[0]))
+        } else {
+            (len, None)
+        };
+        if offset + len > MAX_ROM_SIZE {
+            return Err(invalid("patch writes past the end of memory"));
+        }
+        if out.len() < offset + len {
+            out.resize(offset + len, 0);
+        }
+        match fill {
+            Some(byte) => out[offset..offset + len].fill(byte),
+            None => out[offset..offset + len].copy_from_slice(reader.bytes(len)?),
+        }
+    }
+    // Some patches end with the size to truncate the ROM to.
+    if reader.pos + 3 <= patch.len() {
+        out.truncate(reader.number(3)?);
+    }
+    Ok(out)
+}
+
+fn apply_bps(patch: &[u8], rom: &[u8]) -> io::Result<Vec<u8>> {
+    if patch.len() < 4 + 12 {
+        return Err(invalid("patch is truncated"));
+    }
+    let (body, footer) = patch.split_at(patch.len() - 12);
+    let checksum = |bytes: &[u8]| u32::from_le_bytes(bytes.try_into().unwrap());
+    if crc32fast::hash(&patch[..patch.len() - 4]) != checksum(&footer[8..]) {
+        return Err(invalid("patch is corrupt"));
+    }
+    if crc32fast::hash(rom) != checksum(&footer[..4]) {
+        return Err(invalid("patch was made for a different ROM"));
+    }
+
+    let mut reader = Reader {
+        data: body,
+        pos: b"BPS1".len(),
+    };
+    let source_size = reader.varint()?;
+    let target_size = reader.varint()?;
+    let metadata_size = reader.varint()?;
+    reader.bytes(metadata_size)?;
+    if source_size != rom.len() {
+        return Err(invalid("patch was made for a different ROM"));
+    }
+    if target_size > MAX_ROM_SIZE {
+        return Err(invalid("patched ROM is too large to load"));
+    }
+
+    let out_of_range = || invalid("patch reads outside the ROM");
+    let mut out = Vec::with_capacity(target_size);
+    let mut source_offset: isize = 0;
+    let mut target_offset: isize = 0;
+    while reader.pos < body.len() {
+        let action = reader.varint()?;
+        let len = (action >> 2) + 1;
+        if out.len() + len > target_size {
+            return Err(invalid("patch writes past the end of the ROM"));
+        }
+        match action & 3 {
+            // Source read: copy from the same position in the original.
+            0 => {
+                let start = out.len();
+                out.extend_from_slice(rom.get(start..start + len).ok_or_else(out_of_range)?);
+            }
+            // Target read: new bytes from the patch.
+            1 => out.extend_from_slice(reader.bytes(len)?),
+            // Source copy and target copy: copy from a relative position in the original or in
+            // the output so far.
+            kind => {
+                let delta = reader.varint()?;
+                let delta = if delta & 1 != 0 {
+                    -((delta >> 1) as isize)
+                } else {
+                    (delta >> 1) as isize
+                };
+                let offset = if kind == 2 {
+                    &mut source_offset
+                } else {
+                    &mut target_offset
+                };
+                *offset += delta;
+                for _ in 0..len {
+                    let index = usize::try_from(*offset).map_err(|_| out_of_range())?;
+                    let byte = if kind == 2 {
+                        rom.get(index)
+                    } else {
+                        out.get(index)
+                    };
+                    out.push(*byte.ok_or_else(out_of_range)?);
+                    *offset += 1;
+                }
+            }
+        }
+    }
+
+    if out.len() != target_size || crc32fast::hash(&out) != checksum(&footer[4..8]) {
+        return Err(invalid("patched ROM does not match the patch's checksum"));
+    }
+    Ok(out)
+}
@@ -0,0 +1,102 @@
+use libloading::Library;
+use log::info;
+use std::io;
+
+/// Version of the plugin interface. Plugins must export `chip8_plugin_abi_version` returning
+/// this, so that libraries built against another version are rejected instead of crashing.
+pub const ABI_VERSION: u32 = 1;
+
+// The C interface a plugin library exports. Everything but the version is optional.
+//
+//     uint32_t chip8_plugin_abi_version(void);
+//     void chip8_plugin_frame(const uint8_t *pixels, size_t width, size_t height);
+//     void chip8_plugin_poll_keys(uint8_t keys[16]);
+//     void chip8_plugin_shutdown(void);
+//
+// `pixels` holds one byte per pixel, 1 when lit, row by row. `poll_keys` is called every frame
+// with the keypad state from the keyboard and sets any keys it wants held to 1.
+type AbiVersionFn = unsafe extern "C" fn() -> u32;
+type FrameFn = unsafe extern "C" fn(*const u8, usize, usize);
+type PollKeysFn = unsafe extern "C" fn(*mut u8);
+type ShutdownFn = unsafe extern "C" fn();
+
+/// An input or output integration loaded from a shared library at startup.
+pub struct Plugin {
+    path: String,
+    frame: Option<FrameFn>,
+    poll_keys: Option<PollKeysFn>,
+    shutdown: Option<ShutdownFn>,
+    // Keeps the functions above loaded.
+    _library: Library,
+}
+
+impl Plugin {
+    pub fn load(path: &str) -> io::Result<Self> {
+        // Loading a library runs its initializers, so plugins are trusted like the emulator.
+        let library = unsafe { Library::new(path) }.map_err(io::Error::other)?;
+        let version = unsafe {
+            let abi_version = library
+                .get::<AbiVersionFn>(b"chip8_plugin_abi_version\0")
+                .map_err(io::Error::other)?;
+            abi_version()
+        };
+        if version != ABI_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{} uses plugin interface version {}, expected {}",
+                    path, version, ABI_VERSION
+                ),
+            ));
+        }
+
+        // The function pointers stay valid for as long as the library is loaded.
+        let plugin = unsafe {
+            Self {
+                path: path.to_string(),
+                frame: library
+                    .get::<FrameFn>(b"chip8_plugin_frame\0")
+                    .ok()
+                    .map(|f| *f),
+                poll_keys: library
+                    .get::<PollKeysFn>(b"chip8_plugin_poll_keys\0")
+                    .ok()
+                    .map(|f| *f),
+                shutdown: library
+                    .get::<ShutdownFn>(b"chip8_plugin_shutdown\0")
+                    .ok()
+                    .map(|f| *f),
+                _library: library,
+            }
+        };
+        info!("loaded plugin {}", plugin.path);
+        Ok(plugin)
+    }
+
+    /// Show the plugin the emulator's screen.
+    pub fn frame(&self, screen: &[u8], width: usize) {
+        if let Some(frame) = self.frame {
+            let pixels: Vec<u8> = screen.iter().map(|planes| u8::from(*planes != 0)).collect();
+            unsafe { frame(pixels.as_ptr(), width, screen.len() / width) };
+        }
+    }
+
+    /// Let the plugin hold down keys in addition to the ones already held.
+    pub fn poll_keys(&self, keys: &mut [bool; 16]) {
+        if let Some(poll_keys) = self.poll_keys {
+            let mut state = keys.map(u8::from);
+            unsafe { poll_keys(state.as_mut_ptr()) };
+            for (key, pressed) in keys.iter_mut().zip(state) {
+                *key |= pressed != 0;
+            }
+        }
+    }
+}
+
+impl Drop for Plugin {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown {
+            unsafe { shutdown() };
+        }
+    }
+}
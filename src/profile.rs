@@ -0,0 +1,125 @@
+use serde_json::json;
+use std::collections::HashMap;
+
+use chip8emu::chip8::Chip8Emulator;
+use chip8emu::disasm::{self, Flow};
+
+// Indices of the frames every profile starts with.
+const DISPLAY_FRAME: usize = 0;
+const MAIN: usize = 1;
+
+struct Event {
+    open: bool,
+    frame: usize,
+    at: u64,
+}
+
+/// Records which subroutines run over time, measured in executed instructions, and exports the
+/// timeline as a speedscope profile. Each displayed frame is an outer span, so the per-frame
+/// budget and the routines that dominate it are easy to spot.
+pub struct Profiler {
+    name: String,
+    frame_names: Vec<String>,
+    frame_ids: HashMap<u16, usize>,
+    events: Vec<Event>,
+    // Subroutines currently being executed, outermost first.
+    stack: Vec<usize>,
+    cycle: u64,
+}
+
+impl Profiler {
+    pub fn new(name: &str) -> Self {
+        let mut profiler = Self {
+            name: name.to_string(),
+            frame_names: vec!["frame".to_string(), "main".to_string()],
+            frame_ids: HashMap::new(),
+            events: Vec::new(),
+            stack: vec![MAIN],
+            cycle: 0,
+        };
+        profiler.push(true, DISPLAY_FRAME, 0);
+        profiler.push(true, MAIN, 0);
+        profiler
+    }
+
+    fn push(&mut self, open: bool, frame: usize, at: u64) {
+        self.events.push(Event { open, frame, at });
+    }
+
+    fn frame_id(&mut self, address: u16) -> usize {
+        let names = &mut self.frame_names;
+        *self.frame_ids.entry(address).or_insert_with(|| {
+            names.push(format!("sub_{:03X}", address));
+            names.len() - 1
+        })
+    }
+
+    /// Record the instruction the emulator is about to execute. Call this before every cycle.
+    pub fn observe(&mut self, emu: &Chip8Emulator) {
+        let pc = emu.pc();
+        if let Some(bytes) = emu.memory().get(pc..pc + 2) {
+            let word = u16::from(bytes[0]) << 8 | u16::from(bytes[1]);
+            match disasm::flow(word) {
+                Flow::Call(target) => {
+                    let frame = self.frame_id(target);
+                    self.push(true, frame, self.cycle);
+                    self.stack.push(frame);
+                }
+                // Never pop main, even if the ROM returns more often than it calls.
+                Flow::Stop if word == 0x00EE && self.stack.len() > 1 => {
+                    let frame = self.stack.pop().unwrap();
+                    self.push(false, frame, self.cycle + 1);
+                }
+                _ => {}
+            }
+        }
+        self.cycle += 1;
+    }
+
+    /// Mark the point where a new frame is displayed.
+    pub fn frame_boundary(&mut self) {
+        let at = self.cycle;
+        for frame in self.stack.clone().into_iter().rev() {
+            self.push(false, frame, at);
+        }
+        self.push(false, DISPLAY_FRAME, at);
+        self.push(true, DISPLAY_FRAME, at);
+        for frame in self.stack.clone() {
+            self.push(true, frame, at);
+        }
+    }
+
+    /// Export everything recorded so far in speedscope's evented profile format.
+    pub fn to_speedscope(&self) -> String {
+        let mut events: Vec<_> = self
+            .events
+            .iter()
+            .map(|e| json!({"type": if e.open { "O" } else { "C" }, "frame": e.frame, "at": e.at}))
+            .collect();
+        // Close whatever is still running at the end of the recording.
+        for frame in self.stack.iter().rev().chain([&DISPLAY_FRAME]) {
+            events.push(json!({"type": "C", "frame": frame, "at": self.cycle}));
+        }
+
+        let frames: Vec<_> = self
+            .frame_names
+            .iter()
+            .map(|name| json!({ "name": name }))
+            .collect();
+        json!({
+            "$schema": "https://www.speedscope.app/file-format-schema.json",
+            "exporter": "chip8emu",
+            "name": self.name,
+            "shared": { "frames": frames },
+            "profiles": [{
+                "type": "evented",
+                "name": self.name,
+                "unit": "none",
+                "startValue": 0,
+                "endValue": self.cycle,
+                "events": events,
+            }],
+        })
+        .to_string()
+    }
+}
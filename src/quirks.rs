@@ -0,0 +1,140 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::chip8::Variant;
+
+/// Behaviours CHIP-8 interpreters disagree on. ROMs written for one interpreter can break on
+/// another, so these can be set to match what a ROM expects.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Quirks {
+    /// 8XY1, 8XY2 and 8XY3 reset VF to 0.
+    pub vf_reset: bool,
+    /// 8XY6 and 8XYE shift VY into VX, rather than shifting VX in place.
+    pub shift_vy: bool,
+    /// FX55 and FX65 leave I pointing just past the last register stored or loaded.
+    pub increment_i: bool,
+    /// BNNN jumps to NNN plus VX, where X is the top nibble of NNN, instead of plus V0.
+    pub jump_vx: bool,
+    /// Sprites are clipped at the edges of the screen instead of wrapping around.
+    pub clip_sprites: bool,
+    /// DXYN waits for the next 60 Hz display refresh before the following instruction runs,
+    /// which limits ROMs to one sprite per frame as on the COSMAC VIP.
+    #[serde(default)]
+    pub display_wait: bool,
+}
+
+// Names used for each quirk on the command line.
+pub const QUIRK_NAMES: [&str; 6] = [
+    "vf-reset",
+    "shift-vy",
+    "increment-i",
+    "jump-vx",
+    "clip",
+    "display-wait",
+];
+
+impl Quirks {
+    /// How each dialect's reference interpreter behaves: COSMAC VIP CHIP-8, SUPER-CHIP 1.1 on
+    /// the HP48, and Octo.
+    pub fn for_variant(variant: Variant) -> Self {
+        match variant {
+            Variant::Chip8 => Self {
+                vf_reset: true,
+                shift_vy: true,
+                increment_i: true,
+                jump_vx: false,
+                clip_sprites: true,
+                display_wait: true,
+            },
+            Variant::SuperChip => Self {
+                vf_reset: false,
+                shift_vy: false,
+                increment_i: false,
+                jump_vx: true,
+                clip_sprites: true,
+                display_wait: false,
+            },
+            Variant::XoChip => Self {
+                vf_reset: false,
+                shift_vy: true,
+                increment_i: true,
+                jump_vx: false,
+                clip_sprites: false,
+                display_wait: false,
+            },
+        }
+    }
+
+    fn flags(&self) -> [bool; 6] {
+        [
+            self.vf_reset,
+            self.shift_vy,
+            self.increment_i,
+            self.jump_vx,
+            self.clip_sprites,
+            self.display_wait,
+        ]
+    }
+
+    /// Whether a quirk named in `QUIRK_NAMES` is on.
+    pub fn is_set(&self, name: &str) -> bool {
+        QUIRK_NAMES
+            .iter()
+            .zip(self.flags())
+            .any(|(quirk, enabled)| *quirk == name && enabled)
+    }
+
+    /// Turn a quirk on or off by its name in `QUIRK_NAMES`.
+    pub fn set(&mut self, name: &str, enabled: bool) -> Result<(), String> {
+        let flag = match name {
+            "vf-reset" => &mut self.vf_reset,
+            "shift-vy" => &mut self.shift_vy,
+            "increment-i" => &mut self.increment_i,
+            "jump-vx" => &mut self.jump_vx,
+            "clip" => &mut self.clip_sprites,
+            "display-wait" => &mut self.display_wait,
+            _ => {
+                return Err(format!(
+                    "unknown quirk {}, expected one of {}",
+                    name,
+                    QUIRK_NAMES.join(", ")
+                ))
+            }
+        };
+        *flag = enabled;
+        Ok(())
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Self::for_variant(Variant::default())
+    }
+}
+
+impl FromStr for Quirks {
+    type Err = String;
+
+    /// Parses a preset named after its dialect: `chip8`, `schip` or `xochip`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(Self::for_variant)
+    }
+}
+
+impl fmt::Display for Quirks {
+    /// Lists the quirks that are on, or `none`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let enabled: Vec<&str> = QUIRK_NAMES
+            .iter()
+            .zip(self.flags())
+            .filter(|(_, enabled)| *enabled)
+            .map(|(name, _)| *name)
+            .collect();
+        if enabled.is_empty() {
+            write!(f, "none")
+        } else {
+            write!(f, "{}", enabled.join(", "))
+        }
+    }
+}
@@ -0,0 +1,287 @@
+use crate::display::{DisplayOptions, Effect};
+
+/// Buffer pixels per CHIP-8 pixel along each axis, enough room for thick pixels to grow into
+/// their neighbours and for scanlines and the pixel grid to darken an edge of each block.
+pub const SCALE: usize = 4;
+
+// Share of a pixel's light left after a frame once it goes dark, so that sprites XOR-drawn off
+// and on again between frames fade rather than flicker.
+const PHOSPHOR_PERSISTENCE: f32 = 0.6;
+// Brightness of the bottom buffer row of each CHIP-8 pixel with scanlines on.
+const SCANLINE_BRIGHTNESS: f32 = 0.5;
+// Brightness of the gaps between pixels with the grid on.
+const GRID_BRIGHTNESS: f32 = 0.7;
+// How far the corners of the picture are pulled in with curvature on, as a share of the
+// distance from the center.
+const CURVATURE: f32 = 0.08;
+
+// A buffer pixel as RGBA from 0 to 1, and whether it shows a lit CHIP-8 pixel, which the
+// phosphor and color effects treat differently from the background.
+#[derive(Clone, Copy)]
+struct Texel {
+    color: [f32; 4],
+    lit: bool,
+}
+
+const BLACK: Texel = Texel {
+    color: [0.0, 0.0, 0.0, 1.0],
+    lit: false,
+};
+
+/// Turns frames drawn one pixel per CHIP-8 pixel into the window's buffer, scaling them up and
+/// passing them through the chain of effects the display options ask for, in their order.
+pub struct Renderer {
+    // Each buffer pixel, as it goes through the chain.
+    scaled: Vec<Texel>,
+    // Spare buffer for effects that move pixels around.
+    spare: Vec<Texel>,
+    // The phosphor effect's output for the last frame, carried over for phosphor decay.
+    light: Vec<[f32; 4]>,
+    // Whether some pixel was still fading after the last frame.
+    fading: bool,
+}
+
+impl Renderer {
+    pub fn new() -> Self {
+        Self {
+            scaled: Vec::new(),
+            spare: Vec::new(),
+            light: Vec::new(),
+            fading: false,
+        }
+    }
+
+    /// Whether pixels are still fading out, so the next frame differs from the last even if
+    /// the screen doesn't.
+    pub fn is_fading(&self) -> bool {
+        self.fading
+    }
+
+    /// Draw `frame` into `buffer`, which is `SCALE` times its size. Lit pixels are those not in
+    /// `background`.
+    pub fn render(
+        &mut self,
+        frame: &[u8],
+        width: usize,
+        background: [u8; 4],
+        options: &DisplayOptions,
+        buffer: &mut [u8],
+    ) {
+        let height = frame.len() / 4 / width;
+        let (buffer_width, buffer_height) = (width * SCALE, height * SCALE);
+
+        self.scaled.clear();
+        for row in frame.chunks_exact(4 * width) {
+            for _ in 0..SCALE {
+                for pixel in row.chunks_exact(4) {
+                    self.scaled
+                        .extend(std::iter::repeat_n(texel(pixel, background), SCALE));
+                }
+            }
+        }
+        if options.thick_pixels {
+            for (i, pixel) in frame.chunks_exact(4).enumerate() {
+                if pixel == background {
+                    continue;
+                }
+                // Extend the block by one buffer pixel on every side.
+                let (x, y) = (i % width * SCALE, i / width * SCALE);
+                for by in y.saturating_sub(1)..(y + SCALE + 1).min(buffer_height) {
+                    for bx in x.saturating_sub(1)..(x + SCALE + 1).min(buffer_width) {
+                        self.scaled[by * buffer_width + bx] = texel(pixel, background);
+                    }
+                }
+            }
+        }
+
+        let chain = options.chain();
+        if !chain.contains(&Effect::Phosphor) {
+            self.light.clear();
+        }
+        self.fading = false;
+        for effect in chain {
+            match effect {
+                Effect::Phosphor => self.phosphor(),
+                Effect::Colors => self.colors(options),
+                Effect::Scanlines => darken(
+                    &mut self.scaled,
+                    buffer_width,
+                    SCANLINE_BRIGHTNESS,
+                    |_, y| y == SCALE - 1,
+                ),
+                Effect::Grid => darken(&mut self.scaled, buffer_width, GRID_BRIGHTNESS, |x, y| {
+                    x == SCALE - 1 || y == SCALE - 1
+                }),
+                Effect::Curvature => self.curvature(buffer_width, buffer_height),
+            }
+        }
+
+        for (texel, out) in self.scaled.iter().zip(buffer.chunks_exact_mut(4)) {
+            for (channel, value) in out.iter_mut().zip(texel.color) {
+                *channel = (value * 255.0).round() as u8;
+            }
+        }
+    }
+
+    // Lit pixels light up at once; unlit ones fade towards what they show now.
+    fn phosphor(&mut self) {
+        // Nothing to fade from after a resolution change.
+        if self.light.len() != self.scaled.len() {
+            self.light = self.scaled.iter().map(|texel| texel.color).collect();
+            return;
+        }
+        for (texel, light) in self.scaled.iter_mut().zip(&mut self.light) {
+            for (channel, target) in light.iter_mut().zip(&mut texel.color) {
+                if !texel.lit {
+                    *channel =
+                        *channel * PHOSPHOR_PERSISTENCE + *target * (1.0 - PHOSPHOR_PERSISTENCE);
+                    // Done once the difference no longer shows in 8 bit color.
+                    self.fading |= (*channel - *target).abs() * 255.0 >= 0.5;
+                } else {
+                    *channel = *target;
+                }
+                *target = *channel;
+            }
+        }
+    }
+
+    // The same adjustments `DisplayOptions::palette` makes to the colors.
+    fn colors(&mut self, options: &DisplayOptions) {
+        let on = f32::from(options.on_brightness.min(100)) / 100.0;
+        let off = f32::from(options.off_brightness.min(100)) / 100.0;
+        for texel in &mut self.scaled {
+            let [rgb @ .., alpha] = &mut texel.color;
+            if options.high_contrast {
+                let value = if texel.lit { 1.0 } else { 0.0 };
+                *rgb = [value; 3];
+                *alpha = 1.0;
+            }
+            if texel.lit {
+                rgb.iter_mut().for_each(|channel| *channel *= on);
+            } else if off > 0.0 {
+                rgb.iter_mut()
+                    .for_each(|channel| *channel += (1.0 - *channel) * off);
+                *alpha = 1.0;
+            }
+        }
+    }
+
+    // Pull each buffer pixel from further out the further it is from the center, leaving black
+    // where that falls off the picture.
+    fn curvature(&mut self, width: usize, height: usize) {
+        let (half_width, half_height) = (width as f32 / 2.0, height as f32 / 2.0);
+        self.spare.clear();
+        for y in 0..height {
+            for x in 0..width {
+                let u = (x as f32 + 0.5 - half_width) / half_width;
+                let v = (y as f32 + 0.5 - half_height) / half_height;
+                let bend = 1.0 + CURVATURE * (u * u + v * v);
+                let (sx, sy) = (
+                    u * bend * half_width + half_width,
+                    v * bend * half_height + half_height,
+                );
+                let texel =
+                    if (0.0..width as f32).contains(&sx) && (0.0..height as f32).contains(&sy) {
+                        self.scaled[sy as usize * width + sx as usize]
+                    } else {
+                        BLACK
+                    };
+                self.spare.push(texel);
+            }
+        }
+        std::mem::swap(&mut self.scaled, &mut self.spare);
+    }
+}
+
+fn texel(pixel: &[u8], background: [u8; 4]) -> Texel {
+    Texel {
+        color: std::array::from_fn(|channel| f32::from(pixel[channel]) / 255.0),
+        lit: pixel != background,
+    }
+}
+
+// Scale the color of the buffer pixels at the positions within each CHIP-8 pixel's block that
+// `picked` picks.
+fn darken(
+    scaled: &mut [Texel],
+    width: usize,
+    brightness: f32,
+    picked: impl Fn(usize, usize) -> bool,
+) {
+    for (i, texel) in scaled.iter_mut().enumerate() {
+        if picked(i % width % SCALE, i / width % SCALE) {
+            texel.color[..3]
+                .iter_mut()
+                .for_each(|channel| *channel *= brightness);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIT: [u8; 4] = [0xff; 4];
+    const UNLIT: [u8; 4] = [0, 0, 0, 0xff];
+
+    // The buffer pixel on the bottom row of the first CHIP-8 pixel's block.
+    fn bottom_left(renderer: &mut Renderer, frame: &[[u8; 4]], options: &DisplayOptions) -> u8 {
+        let mut buffer = vec![0; 4 * frame.len() * SCALE * SCALE];
+        renderer.render(
+            frame.as_flattened(),
+            frame.len(),
+            UNLIT,
+            options,
+            &mut buffer,
+        );
+        let row = 4 * frame.len() * SCALE;
+        buffer[(SCALE - 1) * row]
+    }
+
+    #[test]
+    fn effects_run_in_the_chosen_order() {
+        let mut options = DisplayOptions {
+            scanlines: true,
+            off_brightness: 50,
+            ..DisplayOptions::default()
+        };
+        // Scanlines darken the lifted background.
+        assert_eq!(
+            bottom_left(&mut Renderer::new(), &[UNLIT, LIT], &options),
+            64
+        );
+        // The background is lifted after it was darkened.
+        options.effects = vec![Effect::Scanlines];
+        assert_eq!(
+            bottom_left(&mut Renderer::new(), &[UNLIT, LIT], &options),
+            128
+        );
+
+        options.phosphor = true;
+        options.curvature = true;
+        options.effects = vec![Effect::Curvature, Effect::Scanlines, Effect::Curvature];
+        assert_eq!(
+            options.chain(),
+            [
+                Effect::Curvature,
+                Effect::Scanlines,
+                Effect::Phosphor,
+                Effect::Colors
+            ]
+        );
+    }
+
+    #[test]
+    fn unlit_pixels_fade_out() {
+        let options = DisplayOptions {
+            phosphor: true,
+            ..DisplayOptions::default()
+        };
+        let mut renderer = Renderer::new();
+        assert_eq!(bottom_left(&mut renderer, &[LIT, UNLIT], &options), 0xff);
+        assert!(!renderer.is_fading());
+        assert_eq!(bottom_left(&mut renderer, &[UNLIT, UNLIT], &options), 153);
+        assert!(renderer.is_fading());
+        assert_eq!(bottom_left(&mut renderer, &[LIT, UNLIT], &options), 0xff);
+    }
+}
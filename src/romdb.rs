@@ -0,0 +1,20 @@
+use log::warn;
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+use crate::config::RomConfig;
+
+// Settings for known ROMs, so that they run as intended without any flags.
+const DATABASE: &str = include_str!("romdb.toml");
+
+/// The built-in settings for the ROM with this SHA-1, if it is a known one.
+pub fn lookup(sha1: &str) -> Option<RomConfig> {
+    static ROMS: OnceLock<BTreeMap<String, RomConfig>> = OnceLock::new();
+    let roms = ROMS.get_or_init(|| {
+        toml::from_str(DATABASE).unwrap_or_else(|e| {
+            warn!("ignoring the built-in ROM database: {}", e);
+            BTreeMap::new()
+        })
+    });
+    roms.get(&sha1.to_ascii_lowercase()).cloned()
+}
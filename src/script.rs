@@ -0,0 +1,264 @@
+use log::{error, info};
+use rhai::{Dynamic, Engine, FnPtr, FuncArgs, AST, INT};
+use std::cell::RefCell;
+use std::ops::Range;
+use std::path::Path;
+use std::rc::Rc;
+
+use chip8emu::chip8::Chip8Emulator;
+
+// The functions a script can call:
+//
+//     on_frame(|| ...)                        after every 60 Hz frame
+//     on_breakpoint(address, || ...)          before the instruction at address runs
+//     on_write(address, |address, value| ...) after an instruction changes a byte, also given
+//     on_write(start..end, |address, value| ...)  a range of addresses
+//     reg(x), set_reg(x, value)               VX
+//     i(), set_i(value), pc(), set_pc(address), dt(), set_dt(value), st(), set_st(value)
+//     peek(address), poke(address, value)     memory
+//     pixel(x, y)                             the planes lit at a pixel, 0 when it's off
+//     is_held(key)                            whether a keypad key is held
+//     press(key), release(key)                hold a keypad key for the ROM, along with the player
+//     frame()                                 frames run since the script was loaded
+//     pause(), quit()                         pause in the debugger, or close the emulator
+//
+// A script's top level runs once, after the first ROM is loaded, and registers the callbacks.
+
+// What the engine's functions work on. The emulator is swapped in while the script runs, since
+// the engine can't borrow it.
+#[derive(Default)]
+struct Machine {
+    emu: Chip8Emulator,
+    keys: [bool; 16],
+    frame: INT,
+    pause: bool,
+    quit: bool,
+}
+
+#[derive(Default)]
+struct Hooks {
+    frame: Vec<FnPtr>,
+    breakpoints: Vec<(usize, FnPtr)>,
+    writes: Vec<(Range<usize>, FnPtr)>,
+}
+
+/// A Rhai script that watches and drives the machine through callbacks.
+pub struct Script {
+    path: String,
+    engine: Engine,
+    ast: AST,
+    machine: Rc<RefCell<Machine>>,
+    hooks: Rc<RefCell<Hooks>>,
+    // Each watched range before the current instruction.
+    watched: Vec<Vec<u8>>,
+    // Set once a callback fails, after which the script is left alone.
+    failed: bool,
+}
+
+fn key(key: INT) -> usize {
+    (key & 0xF) as usize
+}
+
+impl Script {
+    /// Compile the script and run its top level.
+    pub fn load(path: &str, emu: &mut Chip8Emulator) -> Result<Self, String> {
+        let machine = Rc::new(RefCell::new(Machine::default()));
+        let hooks = Rc::new(RefCell::new(Hooks::default()));
+        let engine = engine(&machine, &hooks);
+        let ast = engine
+            .compile_file(Path::new(path).to_path_buf())
+            .map_err(|e| format!("{}: {}", path, e))?;
+        let mut script = Self {
+            path: path.to_string(),
+            engine,
+            ast,
+            machine,
+            hooks,
+            watched: Vec::new(),
+            failed: false,
+        };
+        script.with_machine(emu, |script| {
+            script
+                .engine
+                .run_ast(&script.ast)
+                .map_err(|e| format!("{}: {}", path, e))
+        })?;
+        info!("loaded script {}", path);
+        Ok(script)
+    }
+
+    /// Run the callbacks at the instruction about to run, and note the watched memory.
+    pub fn before_cycle(&mut self, emu: &mut Chip8Emulator) {
+        if self.failed {
+            return;
+        }
+        let pc = emu.pc();
+        let (breakpoints, ranges) = {
+            let hooks = self.hooks.borrow();
+            let breakpoints: Vec<FnPtr> = hooks
+                .breakpoints
+                .iter()
+                .filter(|(address, _)| *address == pc)
+                .map(|(_, callback)| callback.clone())
+                .collect();
+            let ranges: Vec<Range<usize>> = hooks
+                .writes
+                .iter()
+                .map(|(range, _)| range.clone())
+                .collect();
+            (breakpoints, ranges)
+        };
+        if !breakpoints.is_empty() {
+            self.with_machine(emu, |script| {
+                for callback in &breakpoints {
+                    script.call(callback, ());
+                }
+            });
+        }
+        self.watched = ranges
+            .into_iter()
+            .map(|range| emu.read_memory(range).to_vec())
+            .collect();
+    }
+
+    /// Run the callbacks for the watched bytes the instruction changed.
+    pub fn after_cycle(&mut self, emu: &mut Chip8Emulator) {
+        if self.failed || self.watched.is_empty() {
+            return;
+        }
+        let mut changes = Vec::new();
+        for ((range, callback), old) in self.hooks.borrow().writes.iter().zip(&self.watched) {
+            let new = emu.read_memory(range.clone());
+            for (offset, (old, new)) in old.iter().zip(new).enumerate() {
+                if old != new {
+                    changes.push((callback.clone(), range.start + offset, *new));
+                }
+            }
+        }
+        if !changes.is_empty() {
+            self.with_machine(emu, |script| {
+                for (callback, address, value) in &changes {
+                    script.call(callback, (*address as INT, INT::from(*value)));
+                }
+            });
+        }
+    }
+
+    /// Run the per-frame callbacks.
+    pub fn frame(&mut self, emu: &mut Chip8Emulator) {
+        if self.failed {
+            return;
+        }
+        let callbacks = self.hooks.borrow().frame.clone();
+        self.with_machine(emu, |script| {
+            for callback in &callbacks {
+                script.call(callback, ());
+            }
+        });
+        self.machine.borrow_mut().frame += 1;
+    }
+
+    /// Hold the keys the script pressed, in addition to the ones already held.
+    pub fn poll_keys(&self, keys: &mut [bool; 16]) {
+        for (key, pressed) in keys.iter_mut().zip(self.machine.borrow().keys) {
+            *key |= pressed;
+        }
+    }
+
+    /// Whether the script asked to pause since the last call.
+    pub fn take_pause_request(&mut self) -> bool {
+        std::mem::take(&mut self.machine.borrow_mut().pause)
+    }
+
+    pub fn quit_requested(&self) -> bool {
+        self.machine.borrow().quit
+    }
+
+    fn with_machine<T>(&mut self, emu: &mut Chip8Emulator, f: impl FnOnce(&mut Self) -> T) -> T {
+        std::mem::swap(emu, &mut self.machine.borrow_mut().emu);
+        let result = f(self);
+        std::mem::swap(emu, &mut self.machine.borrow_mut().emu);
+        result
+    }
+
+    fn call(&mut self, callback: &FnPtr, args: impl FuncArgs) {
+        if self.failed {
+            return;
+        }
+        if let Err(e) = callback.call::<Dynamic>(&self.engine, &self.ast, args) {
+            error!("{}: {}, stopping the script", self.path, e);
+            self.failed = true;
+        }
+    }
+}
+
+/// An engine with the functions scripts use registered.
+fn engine(machine: &Rc<RefCell<Machine>>, hooks: &Rc<RefCell<Hooks>>) -> Engine {
+    let mut engine = Engine::new();
+    engine.on_print(|text| info!("script: {}", text));
+    engine.on_debug(|text, _, _| info!("script: {}", text));
+
+    let h = hooks.clone();
+    engine.register_fn("on_frame", move |callback: FnPtr| {
+        h.borrow_mut().frame.push(callback);
+    });
+    let h = hooks.clone();
+    engine.register_fn("on_breakpoint", move |address: INT, callback: FnPtr| {
+        h.borrow_mut()
+            .breakpoints
+            .push((address as usize, callback));
+    });
+    let h = hooks.clone();
+    engine.register_fn("on_write", move |address: INT, callback: FnPtr| {
+        let address = address as usize;
+        h.borrow_mut().writes.push((address..address + 1, callback));
+    });
+    let h = hooks.clone();
+    engine.register_fn("on_write", move |range: Range<INT>, callback: FnPtr| {
+        let range = range.start as usize..range.end as usize;
+        h.borrow_mut().writes.push((range, callback));
+    });
+
+    // Functions of the machine, each with its own handle on it.
+    macro_rules! register {
+        ($name:literal, |$m:ident $(, $arg:ident: $ty:ty)*| $body:expr) => {{
+            let machine = machine.clone();
+            engine.register_fn($name, move |$($arg: $ty),*| {
+                let $m = &mut *machine.borrow_mut();
+                $body
+            });
+        }};
+    }
+    register!("reg", |m, x: INT| INT::from(m.emu.registers()[key(x)]));
+    register!("set_reg", |m, x: INT, value: INT| m
+        .emu
+        .set_register(key(x), value as u8));
+    register!("i", |m| m.emu.i() as INT);
+    register!("set_i", |m, value: INT| m.emu.set_index(value as usize));
+    register!("pc", |m| m.emu.pc() as INT);
+    register!("set_pc", |m, address: INT| m.emu.set_pc(address as usize));
+    register!("dt", |m| INT::from(m.emu.delay_timer()));
+    register!("set_dt", |m, value: INT| m.emu.set_delay_timer(value as u8));
+    register!("st", |m| INT::from(m.emu.sound_timer()));
+    register!("set_st", |m, value: INT| m.emu.set_sound_timer(value as u8));
+    register!("peek", |m, address: INT| {
+        let memory = m.emu.memory();
+        INT::from(memory[address as usize % memory.len()])
+    });
+    register!("poke", |m, address: INT, value: INT| {
+        let address = address as usize % m.emu.memory().len();
+        m.emu.write_memory(address, &[value as u8]).unwrap();
+    });
+    register!("pixel", |m, x: INT, y: INT| {
+        let (width, height) = (m.emu.screen_width(), m.emu.screen_height());
+        let (x, y) = (x as usize % width, y as usize % height);
+        INT::from(m.emu.screen()[y * width + x])
+    });
+    register!("is_held", |m, k: INT| m.emu.keys()[key(k)]);
+    register!("press", |m, k: INT| m.keys[key(k)] = true);
+    register!("release", |m, k: INT| m.keys[key(k)] = false);
+    register!("frame", |m| m.frame);
+    register!("pause", |m| m.pause = true);
+    register!("quit", |m| m.quit = true);
+    engine
+}
@@ -0,0 +1,113 @@
+use log::{info, warn};
+use std::fs;
+use std::io;
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chip8emu::chip8::Chip8Emulator;
+use chip8emu::clock::{Clock, TIMER_HZ};
+
+use crate::inspect;
+use crate::net::{read_message, write_message};
+
+// Every message in either direction is a one byte type, a big-endian u16 payload length, and
+// the payload.
+//
+// Sent by the server:
+//   FRAME: the screen's width and height in pixels, one byte each, then the screen packed 8
+//          pixels per byte, most significant bit first, row by row. Sent once on connect and
+//          then whenever the screen changes.
+//   SOUND: one byte, 1 while the sound timer is running and 0 once it stops.
+// Sent by the client:
+//   KEY_DOWN, KEY_UP: one byte, the keypad key 0x0 to 0xF.
+// Unknown message types are skipped.
+const FRAME: u8 = 0x01;
+const SOUND: u8 = 0x02;
+const KEY_DOWN: u8 = 0x01;
+const KEY_UP: u8 = 0x02;
+
+fn pack_screen(emu: &Chip8Emulator) -> Vec<u8> {
+    let size = [emu.screen_width() as u8, emu.screen_height() as u8];
+    let pixels = emu.screen().chunks(8).map(|pixels| {
+        pixels
+            .iter()
+            .fold(0, |byte, planes| (byte << 1) | u8::from(*planes != 0))
+    });
+    size.into_iter().chain(pixels).collect()
+}
+
+/// Run ROMs for external frontends: every client that connects to `address` gets a freshly
+/// loaded machine, receives its screen and sound, and sends its key presses.
+pub fn serve(rom_path: &str, address: &str, clock: Clock) -> io::Result<()> {
+    let listener = TcpListener::bind(address)?;
+    info!("serving {} on {}", rom_path, listener.local_addr()?);
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let peer = stream.peer_addr()?;
+        info!("frontend connected from {}", peer);
+        match run_session(rom_path, stream, clock) {
+            Ok(()) => info!("frontend {} disconnected", peer),
+            Err(e) => warn!("frontend {} disconnected: {}", peer, e),
+        }
+    }
+    Ok(())
+}
+
+fn run_session(rom_path: &str, mut stream: TcpStream, mut clock: Clock) -> io::Result<()> {
+    let rom = fs::read(rom_path)?;
+    let mut emu = Chip8Emulator::new();
+    emu.set_variant(inspect::guess_variant(&rom, 0x200));
+    emu.initialize();
+    emu.load_rom_bytes(&rom).map_err(io::Error::other)?;
+
+    // Key events are read on their own thread so that a quiet client doesn't stall the machine.
+    let (key_sender, key_events) = mpsc::channel();
+    let mut reader = stream.try_clone()?;
+    thread::spawn(move || {
+        while let Ok((kind, payload)) = read_message(&mut reader) {
+            let down = match kind {
+                KEY_DOWN => true,
+                KEY_UP => false,
+                _ => continue,
+            };
+            if let Some(key) = payload.first().filter(|key| **key <= 0xF) {
+                if key_sender.send((usize::from(*key), down)).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+
+    let frame_time = Duration::from_secs_f64(1.0 / f64::from(TIMER_HZ));
+    let mut keys = [false; 16];
+    let mut last_screen = None;
+    let mut sounding = false;
+    loop {
+        let start = Instant::now();
+        loop {
+            match key_events.try_recv() {
+                Ok((key, down)) => keys[key] = down,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return Ok(()),
+            }
+        }
+        emu.set_keys(&keys);
+
+        clock.run_frame(&mut emu).map_err(io::Error::other)?;
+        emu.tick_timers();
+
+        let screen = pack_screen(&emu);
+        if last_screen.as_ref() != Some(&screen) {
+            write_message(&mut stream, FRAME, &screen)?;
+            last_screen = Some(screen);
+        }
+        if sounding != (emu.sound_timer() > 0) {
+            sounding = !sounding;
+            write_message(&mut stream, SOUND, &[u8::from(sounding)])?;
+        }
+
+        thread::sleep(frame_time.saturating_sub(start.elapsed()));
+    }
+}
@@ -0,0 +1,74 @@
+use signal_hook::consts::{SIGINT, SIGTERM};
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use chip8emu::chip8::Chip8Emulator;
+use chip8emu::disasm;
+use chip8emu::palette::Palette;
+
+use crate::capture;
+
+/// Signals from scripts managing the emulator, checked from the event loop.
+pub struct Signals {
+    // Set by SIGUSR1: write a state dump and screenshot.
+    dump: Arc<AtomicBool>,
+    // Set by SIGTERM or SIGINT: save and exit.
+    terminate: Arc<AtomicBool>,
+}
+
+impl Signals {
+    pub fn register() -> io::Result<Self> {
+        let dump = Arc::new(AtomicBool::new(false));
+        let terminate = Arc::new(AtomicBool::new(false));
+        #[cfg(unix)]
+        signal_hook::flag::register(signal_hook::consts::SIGUSR1, Arc::clone(&dump))?;
+        signal_hook::flag::register(SIGTERM, Arc::clone(&terminate))?;
+        signal_hook::flag::register(SIGINT, Arc::clone(&terminate))?;
+        Ok(Self { dump, terminate })
+    }
+
+    /// Returns `true` once for each dump requested.
+    pub fn take_dump_request(&self) -> bool {
+        self.dump.swap(false, Ordering::Relaxed)
+    }
+
+    pub fn terminate_requested(&self) -> bool {
+        self.terminate.load(Ordering::Relaxed)
+    }
+}
+
+/// Write the machine state as text and the screen as a PNG to the working directory. Returns
+/// the name of the text file.
+pub fn write_dump(emu: &Chip8Emulator, rom_info: &str, palette: &Palette) -> io::Result<String> {
+    let timestamp = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let name = format!("chip8-dump-{}", timestamp);
+
+    let mut text = String::new();
+    writeln!(text, "ROM: {}", rom_info).unwrap();
+    writeln!(
+        text,
+        "PC: {:#05X}  I: {:#05X}  SP: {}  DT: {}  ST: {}",
+        emu.pc(),
+        emu.i(),
+        emu.sp(),
+        emu.delay_timer(),
+        emu.sound_timer()
+    )
+    .unwrap();
+    for (reg, value) in emu.registers().iter().enumerate() {
+        writeln!(text, "V{:X}: {:#04X}", reg, value).unwrap();
+    }
+    text.push_str(&disasm::hex_dump(emu.memory(), 0));
+    fs::write(format!("{}.txt", name), text)?;
+
+    capture::write_png(format!("{}.png", name), emu, palette)?;
+
+    Ok(format!("{}.txt", name))
+}
@@ -0,0 +1,145 @@
+// Only Unix has the sockets this needs, elsewhere it reports itself unsupported.
+#![cfg_attr(not(unix), allow(dead_code, unused_imports))]
+
+use log::{info, warn};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+// What the running instance says as soon as a later one connects, so that the later one can
+// tell it apart from anything else that might hold the socket.
+const GREETING: &str = "chip8emu single-instance 1";
+
+// How long either side waits for the other before giving up.
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Socket the first instance listens on for ROMs opened by later ones, in the user's runtime
+/// directory so that other users can't reach it.
+fn socket_path() -> PathBuf {
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => PathBuf::from(dir).join("chip8emu.sock"),
+        None => {
+            let user = std::env::var("USER").unwrap_or_default();
+            std::env::temp_dir().join(format!("chip8emu-{}.sock", user))
+        }
+    }
+}
+
+/// Hand the ROM to an instance that is already running. Returns `true` if one accepted it.
+pub fn forward_to_running_instance(rom_path: &str) -> bool {
+    // The other instance may have a different working directory.
+    let path = match fs::canonicalize(rom_path) {
+        Ok(path) => path,
+        Err(_) => return false,
+    };
+    match forward(&path) {
+        Ok(()) => true,
+        Err(e) => {
+            info!("no running instance took the ROM: {}", e);
+            false
+        }
+    }
+}
+
+#[cfg(unix)]
+fn forward(path: &Path) -> io::Result<()> {
+    let stream = UnixStream::connect(socket_path())?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    let mut reader = BufReader::new(&stream);
+    expect_line(&mut reader, GREETING)?;
+    writeln!(&stream, "{}", path.display())?;
+    expect_line(&mut reader, "ok")
+}
+
+#[cfg(not(unix))]
+fn forward(_path: &Path) -> io::Result<()> {
+    Err(unsupported())
+}
+
+fn expect_line(reader: &mut impl BufRead, expected: &str) -> io::Result<()> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    if line.trim_end() != expected {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("the socket's owner answered {:?}", line.trim_end()),
+        ));
+    }
+    Ok(())
+}
+
+/// Listen for ROMs forwarded by later instances, calling `open_rom` with each path. Fails if
+/// something else holds the socket.
+#[cfg(unix)]
+pub fn listen<F>(open_rom: F) -> io::Result<()>
+where
+    F: Fn(PathBuf) + Send + 'static,
+{
+    let path = socket_path();
+    let listener = match UnixListener::bind(&path) {
+        // Left behind by an instance that has exited.
+        Err(e) if e.kind() == io::ErrorKind::AddrInUse && is_stale(&path) => {
+            fs::remove_file(&path)?;
+            UnixListener::bind(&path)?
+        }
+        listener => listener?,
+    };
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream.and_then(receive) {
+                Ok(path) => {
+                    info!("opening ROM from another instance: {}", path.display());
+                    open_rom(path);
+                }
+                Err(e) => warn!("failed to read forwarded ROM path: {}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn listen<F>(_open_rom: F) -> io::Result<()>
+where
+    F: Fn(PathBuf) + Send + 'static,
+{
+    Err(unsupported())
+}
+
+/// Greet a later instance and read the ROM path it sends.
+#[cfg(unix)]
+fn receive(stream: UnixStream) -> io::Result<PathBuf> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    writeln!(&stream, "{}", GREETING)?;
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    let path = line.trim_end();
+    if path.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "no path sent"));
+    }
+    writeln!(&stream, "ok")?;
+    Ok(PathBuf::from(path))
+}
+
+/// Whether the socket at `path` is a file nobody is listening on any more.
+#[cfg(unix)]
+fn is_stale(path: &Path) -> bool {
+    matches!(UnixStream::connect(path), Err(e) if e.kind() == io::ErrorKind::ConnectionRefused)
+}
+
+#[cfg(not(unix))]
+fn unsupported() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        "single-instance mode needs Unix domain sockets",
+    )
+}
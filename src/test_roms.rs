@@ -0,0 +1,224 @@
+use serde::Deserialize;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chip8emu::chip8::{Chip8Emulator, Variant};
+use chip8emu::clock::Clock;
+use chip8emu::headless::{self, Report};
+use chip8emu::quirks::Quirks;
+
+use crate::config::parsed;
+use crate::inspect;
+
+/// A list of ROMs to run and the screens they should end up showing, read from TOML:
+///
+/// ```toml
+/// [[test]]
+/// rom = "roms/3-corax+.ch8"
+/// frames = 60
+/// quirks = "chip8"
+/// golden = "golden/corax-chip8.pbm"
+/// ```
+///
+/// Paths are relative to the manifest.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Manifest {
+    #[serde(rename = "test")]
+    tests: Vec<RomTest>,
+}
+
+/// One ROM run under one set of quirks.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RomTest {
+    /// What the report calls the test, the ROM's file name and quirks by default.
+    name: Option<String>,
+    rom: PathBuf,
+    /// How many 60 Hz frames to run for.
+    #[serde(default = "default_frames")]
+    frames: u32,
+    /// The dialect to emulate, guessed from the ROM by default.
+    #[serde(default, deserialize_with = "parsed")]
+    variant: Option<Variant>,
+    /// A quirks preset (chip8, schip or xochip), those of the variant by default.
+    quirks: Option<String>,
+    /// Instructions per second, the emulator's default otherwise.
+    clock_hz: Option<u32>,
+    /// Seed for the ROM's random numbers, for ROMs that draw them.
+    seed: Option<u64>,
+    /// The expected screen as a plain PBM, as `--screenshot` writes them.
+    golden: Option<PathBuf>,
+    /// Or just the SHA-1 of that PBM, as `--headless` prints it.
+    sha1: Option<String>,
+}
+
+fn default_frames() -> u32 {
+    60
+}
+
+/// Run every ROM in the manifest at `path` and print whether each ended up showing the
+/// screen it should, writing an image of the differences next to golden images that don't
+/// match. With `update`, golden images are written from the screens instead. Fails if any
+/// test did.
+pub fn run(path: &Path, update: bool, clock: Clock) -> Result<(), Box<dyn Error>> {
+    let manifest: Manifest = toml::from_str(&fs::read_to_string(path)?)
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let mut failed = 0;
+    for test in &manifest.tests {
+        let name = test.name.clone().unwrap_or_else(|| {
+            let file = test.rom.file_name().unwrap_or_default().to_string_lossy();
+            match &test.quirks {
+                Some(quirks) => format!("{} ({})", file, quirks),
+                None => file.into_owned(),
+            }
+        });
+        match test.check(dir, update, clock) {
+            Ok(None) => println!("PASS {}", name),
+            Ok(Some(problem)) => {
+                println!("FAIL {}: {}", name, problem);
+                failed += 1;
+            }
+            Err(e) => {
+                println!("FAIL {}: {}", name, e);
+                failed += 1;
+            }
+        }
+    }
+    let total = manifest.tests.len();
+    println!("{} passed, {} failed", total - failed, failed);
+    if failed > 0 {
+        return Err(format!("{} of {} ROM tests failed", failed, total).into());
+    }
+    Ok(())
+}
+
+impl RomTest {
+    /// Run the ROM, returning what is wrong with the screen it ends up showing, if anything.
+    fn check(
+        &self,
+        dir: &Path,
+        update: bool,
+        clock: Clock,
+    ) -> Result<Option<String>, Box<dyn Error>> {
+        let emu = self.run(dir, clock)?;
+        let pbm = headless::screen_pbm(&emu);
+        if let Some(golden) = &self.golden {
+            let golden = dir.join(golden);
+            if update {
+                if let Some(parent) = golden.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::write(&golden, &pbm)?;
+                return Ok(None);
+            }
+            let expected =
+                fs::read_to_string(&golden).map_err(|e| format!("{}: {}", golden.display(), e))?;
+            let (width, height, expected) = parse_pbm(&expected)
+                .ok_or_else(|| format!("{} is not a plain PBM", golden.display()))?;
+            if (width, height) != (emu.screen_width(), emu.screen_height()) {
+                return Ok(Some(format!(
+                    "screen is {}x{}, expected {}x{}",
+                    emu.screen_width(),
+                    emu.screen_height(),
+                    width,
+                    height
+                )));
+            }
+            let lit: Vec<bool> = emu.screen().iter().map(|planes| *planes != 0).collect();
+            let differing = lit.iter().zip(&expected).filter(|(a, b)| a != b).count();
+            if differing == 0 {
+                return Ok(None);
+            }
+            let diff_path = golden.with_extension("diff.png");
+            fs::write(&diff_path, diff_png(width, height, &lit, &expected))?;
+            return Ok(Some(format!(
+                "{} pixels differ, see {}",
+                differing,
+                diff_path.display()
+            )));
+        }
+        if let Some(sha1) = &self.sha1 {
+            let actual = Report::of(&emu, 0).screen_sha1;
+            if !actual.eq_ignore_ascii_case(sha1) {
+                return Ok(Some(format!("screen SHA-1 is {}", actual)));
+            }
+            return Ok(None);
+        }
+        Err("the test has neither a golden image nor a SHA-1".into())
+    }
+
+    fn run(&self, dir: &Path, clock: Clock) -> Result<Chip8Emulator, Box<dyn Error>> {
+        let path = dir.join(&self.rom);
+        let rom = fs::read(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut emu = Chip8Emulator::new();
+        emu.set_variant(
+            self.variant
+                .unwrap_or_else(|| inspect::guess_variant(&rom, 0x200)),
+        );
+        if let Some(quirks) = &self.quirks {
+            emu.set_quirks(quirks.parse::<Quirks>()?);
+        }
+        if let Some(seed) = self.seed {
+            emu.set_rng_seed(seed);
+        }
+        emu.initialize();
+        emu.load_rom_bytes(&rom)?;
+        let clock = self.clock_hz.map_or(clock, Clock::from_hz);
+        headless::run_frames(&mut emu, self.frames, clock)?;
+        Ok(emu)
+    }
+}
+
+/// The width, height and pixels of a plain PBM, lit pixels being true.
+fn parse_pbm(text: &str) -> Option<(usize, usize, Vec<bool>)> {
+    // Comments run from `#` to the end of the line.
+    let text: String = text
+        .lines()
+        .map(|line| line.split('#').next().unwrap())
+        .collect::<Vec<_>>()
+        .join("\n");
+    let mut header = text.split_whitespace();
+    if header.next()? != "P1" {
+        return None;
+    }
+    let width = header.next()?.parse().ok()?;
+    let height = header.next()?.parse().ok()?;
+    // Pixels need no whitespace between them.
+    let pixels: Vec<bool> = header
+        .flat_map(str::chars)
+        .map(|c| match c {
+            '0' => Some(false),
+            '1' => Some(true),
+            _ => None,
+        })
+        .collect::<Option<_>>()?;
+    (pixels.len() == width * height).then_some((width, height, pixels))
+}
+
+/// An image of where two screens differ: pixels lit in both white, pixels only `actual` lit
+/// red, and pixels only `expected` lit green.
+fn diff_png(width: usize, height: usize, actual: &[bool], expected: &[bool]) -> Vec<u8> {
+    let pixels: Vec<u8> = actual
+        .iter()
+        .zip(expected)
+        .flat_map(|pair| match pair {
+            (true, true) => [0xff, 0xff, 0xff],
+            (true, false) => [0xff, 0, 0],
+            (false, true) => [0, 0xff, 0],
+            (false, false) => [0, 0, 0],
+        })
+        .collect();
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    // Writing to a Vec can't fail.
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&pixels))
+        .unwrap();
+    png
+}
@@ -0,0 +1,136 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::chip8::Chip8Emulator;
+use crate::clock::Clock;
+use crate::error::Chip8Error;
+use crate::headless;
+use crate::movie::Movie;
+
+/// How many frames apart the snapshots kept along a timeline are. Seeking runs at most this
+/// many frames.
+pub const SNAPSHOT_INTERVAL: usize = 60;
+
+/// A movie with snapshots of the machine along it, so that any frame of it can be reached
+/// quickly by restoring the nearest snapshot before it and playing the rest, and so that its
+/// frames can be edited without playing it from the start again. Branches of the movie saved
+/// through it keep the machine at their branch point too, so switching to them is immediate.
+pub struct Timeline {
+    movie: Movie,
+    // The machine and clock at the start of every SNAPSHOT_INTERVALth frame reached so far,
+    // before its keys are held.
+    snapshots: BTreeMap<usize, (Chip8Emulator, Clock)>,
+    // The machine and clock at the end of each branch saved since the last restart. Branches
+    // read with the movie are played to instead.
+    branch_states: HashMap<String, (Chip8Emulator, Clock)>,
+}
+
+impl Timeline {
+    /// A timeline of `movie` played from `start`, the machine with the ROM just loaded.
+    pub fn new(movie: Movie, start: &Chip8Emulator, clock: Clock) -> Self {
+        let mut timeline = Self {
+            movie,
+            snapshots: BTreeMap::new(),
+            branch_states: HashMap::new(),
+        };
+        timeline.restart(start, clock);
+        timeline
+    }
+
+    /// Play the movie from `start` instead, e.g. after the ROM was restarted.
+    pub fn restart(&mut self, start: &Chip8Emulator, clock: Clock) {
+        self.snapshots.clear();
+        self.snapshots.insert(0, (start.clone(), clock));
+        self.branch_states.clear();
+    }
+
+    pub fn movie(&self) -> &Movie {
+        &self.movie
+    }
+
+    /// Call at the start of frame `frame` of playback, before holding its keys, to keep a
+    /// snapshot if one is due there.
+    pub fn reached(&mut self, frame: usize, emu: &Chip8Emulator, clock: Clock) {
+        if frame.is_multiple_of(SNAPSHOT_INTERVAL) {
+            self.snapshots.insert(frame, (emu.clone(), clock));
+        }
+    }
+
+    /// The machine and clock at the start of `frame`, after the frames before it were played.
+    /// Frames past the end of the movie are played without keys held.
+    pub fn seek(&mut self, frame: usize) -> Result<(Chip8Emulator, Clock), Chip8Error> {
+        // There is always one at the start.
+        let (&from, (emu, clock)) = self.snapshots.range(..=frame).next_back().unwrap();
+        let (mut emu, mut clock) = (emu.clone(), *clock);
+        for n in from..frame {
+            if n.is_multiple_of(SNAPSHOT_INTERVAL) {
+                self.snapshots.insert(n, (emu.clone(), clock));
+            }
+            if emu.is_halted() {
+                break;
+            }
+            emu.set_keys(&self.movie.frame(n).unwrap_or_default());
+            let instructions = clock.instructions_this_frame();
+            headless::run_frame(&mut emu, &mut clock, instructions)?;
+            if n < self.movie.len() {
+                self.movie.hash_frame(n, &emu);
+            }
+        }
+        Ok((emu, clock))
+    }
+
+    /// Change the keys held in `frame`, which must be in the movie.
+    pub fn set_keys(&mut self, frame: usize, keys: &[bool; 16]) {
+        self.movie.set_frame(frame, keys);
+        self.forget_after(frame);
+    }
+
+    /// Add a frame with nothing held before `frame`, or at the end if `frame` is the length
+    /// of the movie.
+    pub fn insert_frame(&mut self, frame: usize) {
+        self.movie.insert_frame(frame, &[false; 16]);
+        self.forget_after(frame);
+    }
+
+    /// Drop `frame`, which must be in the movie.
+    pub fn remove_frame(&mut self, frame: usize) {
+        self.movie.remove_frame(frame);
+        self.forget_after(frame);
+    }
+
+    /// Drop every frame from `len` on.
+    pub fn truncate(&mut self, len: usize) {
+        self.movie.truncate(len);
+        self.forget_after(len);
+    }
+
+    /// Save the frames before `frame` as a branch called `name`, along with the machine there.
+    pub fn save_branch(&mut self, name: &str, frame: usize) -> Result<(), Chip8Error> {
+        let state = self.seek(frame)?;
+        let name = self.movie.save_branch(name, frame).name.clone();
+        self.branch_states.insert(name, state);
+        Ok(())
+    }
+
+    /// Replace the movie's frames with those of the branch called `name`, returning the frame
+    /// at its branch point to seek to, or `None` if there is no such branch.
+    pub fn switch_branch(&mut self, name: &str) -> Option<usize> {
+        let changed = self.movie.switch_branch(name)?;
+        self.forget_after(changed);
+        let len = self.movie.len();
+        if let Some(state) = self.branch_states.get(name) {
+            self.snapshots.insert(len, state.clone());
+        }
+        Some(len)
+    }
+
+    pub fn remove_branch(&mut self, name: &str) {
+        self.movie.remove_branch(name);
+        self.branch_states.remove(name);
+    }
+
+    // Snapshots after an edited frame no longer follow from the movie. The one at its start
+    // still does.
+    fn forget_after(&mut self, frame: usize) {
+        self.snapshots.split_off(&(frame + 1));
+    }
+}
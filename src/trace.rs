@@ -0,0 +1,158 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use chip8emu::chip8::Chip8Emulator;
+use chip8emu::disasm;
+
+/// Register state compared before and after each instruction.
+#[derive(Clone, Copy)]
+struct Registers {
+    v: [u8; 16],
+    i: usize,
+    sp: usize,
+    delay_timer: u8,
+    sound_timer: u8,
+}
+
+impl Registers {
+    fn of(emu: &Chip8Emulator) -> Self {
+        Self {
+            v: *emu.registers(),
+            i: emu.i(),
+            sp: emu.sp(),
+            delay_timer: emu.delay_timer(),
+            sound_timer: emu.sound_timer(),
+        }
+    }
+
+    /// Registers whose value differs in `after`, with their new values.
+    fn changes(&self, after: &Self) -> BTreeMap<String, usize> {
+        let mut changes = BTreeMap::new();
+        for (reg, (old, new)) in self.v.iter().zip(after.v.iter()).enumerate() {
+            if old != new {
+                changes.insert(format!("V{:X}", reg), usize::from(*new));
+            }
+        }
+        let others = [
+            ("I", self.i, after.i),
+            ("SP", self.sp, after.sp),
+            ("DT", self.delay_timer.into(), after.delay_timer.into()),
+            ("ST", self.sound_timer.into(), after.sound_timer.into()),
+        ];
+        for (name, old, new) in others {
+            if old != new {
+                changes.insert(name.to_string(), new);
+            }
+        }
+        changes
+    }
+}
+
+/// One line of the trace.
+#[derive(Serialize)]
+struct Record {
+    cycle: u64,
+    pc: usize,
+    opcode: u16,
+    mnemonic: Option<String>,
+    changes: BTreeMap<String, usize>,
+}
+
+/// Writes the execution trace as JSON Lines, one object per executed instruction:
+///
+/// ```json
+/// {"cycle":0,"pc":512,"opcode":27138,"mnemonic":"LD VA, 0x02","changes":{"VA":2}}
+/// ```
+///
+/// Only registers that the instruction changed are listed, so two traces can be diffed line
+/// by line.
+pub struct TraceWriter {
+    out: BufWriter<File>,
+    cycle: u64,
+    // State captured by `before_cycle`, or `None` if no instruction is about to run.
+    pending: Option<(usize, u16, Registers)>,
+}
+
+impl TraceWriter {
+    pub fn create(path: &str) -> io::Result<Self> {
+        Ok(Self {
+            out: BufWriter::new(File::create(path)?),
+            cycle: 0,
+            pending: None,
+        })
+    }
+
+    /// Capture the state before the emulator runs its next cycle.
+    pub fn before_cycle(&mut self, emu: &Chip8Emulator) {
+        // Nothing is executed while waiting for a key.
+        if emu.is_waiting_for_key() {
+            self.pending = None;
+            return;
+        }
+        let pc = emu.pc();
+        // The second byte of an instruction in the last address wraps around to the first.
+        let memory = emu.memory();
+        let opcode = u16::from(memory[pc]) << 8 | u16::from(memory[(pc + 1) % memory.len()]);
+        self.pending = Some((pc, opcode, Registers::of(emu)));
+    }
+
+    /// Write the record for the cycle that just ran.
+    pub fn after_cycle(&mut self, emu: &Chip8Emulator) -> io::Result<()> {
+        if let Some((pc, opcode, before)) = self.pending.take() {
+            let record = Record {
+                cycle: self.cycle,
+                pc,
+                opcode,
+                mnemonic: disasm::format_instruction(opcode),
+                changes: before.changes(&Registers::of(emu)),
+            };
+            serde_json::to_writer(&mut self.out, &record)?;
+            self.out.write_all(b"\n")?;
+            self.cycle += 1;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chip8emu::chip8::Variant;
+
+    #[test]
+    fn instructions_in_the_last_address_are_traced() {
+        let path =
+            std::env::temp_dir().join(format!("chip8emu-trace-{}.jsonl", std::process::id()));
+        let mut trace = TraceWriter::create(path.to_str().unwrap()).unwrap();
+        let mut emu = Chip8Emulator::new();
+        emu.set_variant(Variant::Chip8);
+        emu.initialize();
+        // Jump to the last address, where a jump whose low byte wraps to address 0 waits.
+        emu.load_rom_bytes(&[0x1F, 0xFF]).unwrap();
+        emu.write_memory(0xFFF, &[0x12]).unwrap();
+        for _ in 0..2 {
+            trace.before_cycle(&emu);
+            emu.emulate_cycle().unwrap();
+            trace.after_cycle(&emu).unwrap();
+        }
+        trace.flush().unwrap();
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let records: Vec<serde_json::Value> = text
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1]["pc"], 0xFFF);
+        let opcode = 0x1200 | u16::from(emu.memory()[0]);
+        assert_eq!(records[1]["opcode"], opcode);
+        assert_eq!(emu.pc(), usize::from(opcode & 0xFFF));
+    }
+}
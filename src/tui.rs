@@ -0,0 +1,144 @@
+use ratatui::buffer::Buffer;
+use ratatui::crossterm::event::{
+    self, Event, KeyCode, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use ratatui::crossterm::{execute, terminal};
+use ratatui::layout::Rect;
+use ratatui::style::{Color, Style};
+use ratatui::widgets::Widget;
+use ratatui::DefaultTerminal;
+use std::error::Error;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use chip8emu::chip8::Chip8Emulator;
+use chip8emu::clock::{Clock, TIMER_HZ};
+use chip8emu::palette::Palette;
+
+// The COSMAC VIP keypad laid over the left of a QWERTY keyboard, as in the window, indexed by
+// CHIP-8 key.
+const KEYS: [char; 16] = [
+    'x', '1', '2', '3', 'q', 'w', 'e', 'a', 's', 'd', 'z', 'c', '4', 'r', 'f', 'v',
+];
+
+// Most terminals only send key presses, and repeats while a key is held. Without releases, a
+// key counts as held for this long after its last press or repeat, which bridges the delay
+// before repeats start.
+const HOLD: Duration = Duration::from_millis(300);
+
+const HELP: &str = "Esc quits | keypad 1234 QWER ASDF ZXCV";
+
+/// Run a machine in the terminal until Esc or Ctrl+C is pressed, drawing two pixels to a
+/// character with half blocks.
+pub fn run(emu: &mut Chip8Emulator, clock: Clock, palette: Palette) -> Result<(), Box<dyn Error>> {
+    let mut terminal = ratatui::init();
+    // Terminals that can report key releases let keys be held exactly.
+    let releases = terminal::supports_keyboard_enhancement().unwrap_or(false);
+    if releases {
+        execute!(
+            io::stdout(),
+            PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+        )?;
+    }
+    let result = run_loop(&mut terminal, emu, clock, &palette, releases);
+    if releases {
+        let _ = execute!(io::stdout(), PopKeyboardEnhancementFlags);
+    }
+    ratatui::restore();
+    result
+}
+
+fn run_loop(
+    terminal: &mut DefaultTerminal,
+    emu: &mut Chip8Emulator,
+    mut clock: Clock,
+    palette: &Palette,
+    releases: bool,
+) -> Result<(), Box<dyn Error>> {
+    let frame = Duration::from_secs(1) / TIMER_HZ;
+    // When each CHIP-8 key was last pressed, while it is held.
+    let mut pressed: [Option<Instant>; 16] = [None; 16];
+    let mut beeping = false;
+    let mut next_frame = Instant::now();
+    loop {
+        while event::poll(Duration::ZERO)? {
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            let ctrl_c =
+                key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+            if key.code == KeyCode::Esc || ctrl_c {
+                return Ok(());
+            }
+            let KeyCode::Char(c) = key.code else {
+                continue;
+            };
+            if let Some(chip8_key) = KEYS.iter().position(|k| *k == c.to_ascii_lowercase()) {
+                pressed[chip8_key] = match key.kind {
+                    KeyEventKind::Release => None,
+                    _ => Some(Instant::now()),
+                };
+            }
+        }
+        let now = Instant::now();
+        let keys = pressed
+            .map(|since| since.is_some_and(|since| releases || now.duration_since(since) < HOLD));
+        emu.set_keys(&keys);
+
+        clock.run_frame(emu)?;
+        emu.tick_timers();
+        // Ring the terminal bell as the buzzer starts.
+        if emu.is_beeping() && !beeping {
+            io::stdout().write_all(b"\x07")?;
+        }
+        beeping = emu.is_beeping();
+
+        terminal.draw(|f| f.render_widget(Screen { emu, palette }, f.area()))?;
+
+        next_frame += frame;
+        match next_frame.checked_duration_since(Instant::now()) {
+            Some(wait) => std::thread::sleep(wait),
+            // Running behind, e.g. in a slow terminal. Carry on from here rather than rushing
+            // to catch up.
+            None => next_frame = Instant::now(),
+        }
+    }
+}
+
+/// The screen, with the upper pixel of each pair in a cell's foreground color and the lower one
+/// in its background, and a line of help below.
+struct Screen<'a> {
+    emu: &'a Chip8Emulator,
+    palette: &'a Palette,
+}
+
+impl Widget for Screen<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let (width, height) = (self.emu.screen_width(), self.emu.screen_height());
+        let colors = self
+            .palette
+            .colors()
+            .map(|[r, g, b, _]| Color::Rgb(r, g, b));
+        let color = |planes: u8| colors[usize::from(planes & 3)];
+        let pixels = &self.emu.screen()[..width * height];
+        for (row, pair) in pixels.chunks(2 * width).enumerate() {
+            for x in 0..width {
+                let position = (area.x + x as u16, area.y + row as u16);
+                if !area.contains(position.into()) {
+                    continue;
+                }
+                if let Some(cell) = buf.cell_mut(position) {
+                    cell.set_char('▀')
+                        .set_fg(color(pair[x]))
+                        .set_bg(color(pair[width + x]));
+                }
+            }
+        }
+
+        let help_row = area.y + (height / 2) as u16;
+        if help_row < area.bottom() {
+            buf.set_stringn(area.x, help_row, HELP, area.width.into(), Style::default());
+        }
+    }
+}
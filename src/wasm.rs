@@ -0,0 +1,109 @@
+use wasm_bindgen::prelude::*;
+
+use crate::chip8::{Chip8Emulator, Variant};
+use crate::clock::Clock;
+use crate::palette::Palette;
+
+// Instructions per second until the page sets a clock rate.
+const DEFAULT_CLOCK_HZ: u32 = 500;
+
+/// The emulator for JavaScript. The page drives it from `requestAnimationFrame`: call
+/// `run_frame()` once per frame, then put `frame()` into the canvas as `ImageData`.
+#[wasm_bindgen]
+pub struct WebEmulator {
+    emu: Chip8Emulator,
+    clock: Clock,
+    keys: [bool; 16],
+    palette: Palette,
+}
+
+#[wasm_bindgen]
+impl WebEmulator {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        let mut emu = Chip8Emulator::new();
+        emu.initialize();
+        Self {
+            emu,
+            clock: Clock::from_hz(DEFAULT_CLOCK_HZ),
+            keys: [false; 16],
+            palette: Palette::default(),
+        }
+    }
+
+    /// Reset the machine and load a ROM for a dialect (`chip8`, `schip` or `xochip`, `chip8`
+    /// by default).
+    pub fn load_rom(&mut self, rom: &[u8], variant: Option<String>) -> Result<(), JsError> {
+        let variant = match variant {
+            Some(name) => name.parse().map_err(|e: String| JsError::new(&e))?,
+            None => Variant::default(),
+        };
+        let mut emu = Chip8Emulator::new();
+        emu.set_variant(variant);
+        emu.initialize();
+        emu.load_rom_bytes(rom)?;
+        self.emu = emu;
+        Ok(())
+    }
+
+    pub fn set_clock_hz(&mut self, instructions_per_second: u32) {
+        self.clock = Clock::from_hz(instructions_per_second);
+    }
+
+    /// Set the colors of lit and unlit pixels, as `0xRRGGBB`.
+    pub fn set_colors(&mut self, foreground: u32, background: u32) {
+        let rgba = |color: u32| {
+            let [_, r, g, b] = color.to_be_bytes();
+            [r, g, b, 0xff]
+        };
+        self.palette = Palette::new(rgba(foreground), rgba(background));
+    }
+
+    /// Use a theme (`white`, `green`, `amber`, `high-contrast` or `octo`) or colors like
+    /// `#FFCC00,#996600`, as with `--palette`.
+    pub fn set_palette(&mut self, palette: &str) -> Result<(), JsError> {
+        self.palette = palette.parse().map_err(|e: String| JsError::new(&e))?;
+        Ok(())
+    }
+
+    /// Press or release a CHIP-8 key, 0x0 to 0xF.
+    pub fn set_key(&mut self, key: usize, down: bool) {
+        if let Some(held) = self.keys.get_mut(key) {
+            *held = down;
+            self.emu.set_keys(&self.keys);
+        }
+    }
+
+    /// Run one 60 Hz frame: a batch of instructions, then a timer tick.
+    pub fn run_frame(&mut self) -> Result<(), JsError> {
+        self.clock.run_frame(&mut self.emu)?;
+        self.emu.tick_timers();
+        Ok(())
+    }
+
+    pub fn width(&self) -> usize {
+        self.emu.screen_width()
+    }
+
+    pub fn height(&self) -> usize {
+        self.emu.screen_height()
+    }
+
+    /// The screen as RGBA bytes, `width() * height() * 4` long.
+    pub fn frame(&self) -> Vec<u8> {
+        let mut frame = vec![0; 4 * self.emu.screen().len()];
+        self.emu.draw_screen_with_palette(&mut frame, &self.palette);
+        frame
+    }
+
+    /// Whether the page should be sounding the buzzer.
+    pub fn is_beeping(&self) -> bool {
+        self.emu.is_beeping()
+    }
+}
+
+impl Default for WebEmulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
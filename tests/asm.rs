@@ -0,0 +1,68 @@
+use chip8emu::asm::assemble;
+use chip8emu::disasm::disassemble;
+use chip8emu::opcode;
+
+#[test]
+fn every_instruction_assembles_to_itself() {
+    for word in 0..=u16::MAX {
+        if let Ok(op) = opcode::from_value(word) {
+            let rom = assemble(&op.to_string(), 0x200).unwrap();
+            assert_eq!(rom, word.to_be_bytes(), "{}", op);
+        }
+    }
+}
+
+#[test]
+fn disassembly_reassembles_to_the_same_rom() {
+    let source = "
+        start:
+            ld i, sprite
+            LD V0, 10     ; x
+            LD V1, 0b1000 ; y
+        loop: DRW V0, V1, 5
+            CALL wait
+            SKP V2
+            JP loop
+            LD I, LONG sprite
+            SAVE V0 - V3
+            EXIT
+        wait:
+            LD V3, DT
+            SE V3, 0
+            JP wait
+            RET
+        sprite:
+            db 0xF0, 0x90, 0xF0
+    ";
+    let rom = assemble(source, 0x200).unwrap();
+    assert_eq!(&rom[..4], [0xA2, 0x1E, 0x60, 0x0A]);
+    assert_eq!(&rom[0x0E..0x12], [0xF0, 0x00, 0x02, 0x1E]);
+    assert_eq!(rom.len(), 0x21);
+
+    let listing = disassemble(&rom, 0x200);
+    assert_eq!(assemble(&listing, 0x200).unwrap(), rom);
+}
+
+#[test]
+fn errors_name_the_line() {
+    let error = assemble("CLS\nJP nowhere", 0x200).unwrap_err();
+    assert_eq!(error.line, 2);
+    assert!(error.message.contains("nowhere"));
+
+    assert_eq!(assemble("ADD V1, 0x100", 0x200).unwrap_err().line, 1);
+    assert_eq!(assemble("a:\na: RET", 0x200).unwrap_err().line, 2);
+    assert_eq!(assemble("DRW V1, 3", 0x200).unwrap_err().line, 1);
+}
+
+#[test]
+fn disassembly_stops_at_the_end_of_the_address_space() {
+    // Clears fill memory up to 0xFFFF, with two bytes left over past it.
+    let rom: Vec<u8> = [0x00, 0xE0].repeat((0x10000 - 0x200) / 2 + 1);
+    let listing = disassemble(&rom, 0x200);
+    assert!(listing.trim_end().ends_with("; 0xFFFE: 00E0"));
+    assert_eq!(assemble(&listing, 0x200).unwrap(), rom[..0x10000 - 0x200]);
+
+    // A lone byte in the last address is data.
+    let listing = disassemble(&[0x12, 0x34, 0x56], 0xFFFD);
+    assert!(listing.trim_end().ends_with("; 0xFFFF: 01010110"));
+}
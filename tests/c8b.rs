@@ -0,0 +1,67 @@
+use chip8emu::c8b::{self, C8b};
+use chip8emu::chip8::Variant;
+use chip8emu::palette::Palette;
+use chip8emu::quirks::Quirks;
+
+#[test]
+fn settings_survive_a_round_trip() {
+    let mut keys = [None; 16];
+    keys[5] = Some('W');
+    keys[0xA] = Some('0');
+    let c8b = C8b {
+        variant: Variant::SuperChip,
+        quirks: Some(Quirks::for_variant(Variant::SuperChip)),
+        clock_hz: Some(900),
+        palette: Palette::theme("amber"),
+        keys,
+        program: vec![0x00, 0xE0, 0x12, 0x00],
+    };
+    let data = c8b.to_bytes();
+    assert!(c8b::is_c8b(&data));
+    assert!(data.ends_with(&c8b.program));
+    assert_eq!(C8b::parse(&data).unwrap(), c8b);
+
+    let bare = C8b {
+        program: vec![0x12, 0x00],
+        ..C8b::default()
+    };
+    assert_eq!(bare.to_bytes(), b"CBF\0\0\0\x12\0");
+    assert_eq!(C8b::parse(&bare.to_bytes()).unwrap(), bare);
+}
+
+#[test]
+fn hand_written_files_are_read() {
+    let data = [
+        b'C', b'B', b'F', 0, 2, // XO-CHIP
+        9, 2, 0xAB, 0xCD, // a property from a later version
+        3, 3, 0x11, 0x22, 0x33, // only a background color
+        4, 2, 0x5, b'w', // a lowercase key
+        0, 0xF0, 0x00,
+    ];
+    let c8b = C8b::parse(&data).unwrap();
+    assert_eq!(c8b.variant, Variant::XoChip);
+    assert_eq!(c8b.quirks, None);
+    assert_eq!(c8b.clock_hz, None);
+    let palette = c8b.palette.unwrap();
+    assert_eq!(palette.background, [0x11, 0x22, 0x33, 0xff]);
+    assert_eq!(palette.foreground, Palette::default().foreground);
+    assert_eq!(c8b.keys[5], Some('W'));
+    assert_eq!(c8b.program, [0xF0, 0x00]);
+}
+
+#[test]
+fn damaged_files_are_rejected() {
+    let broken: [&[u8]; 7] = [
+        b"CBF",
+        b"CBF\x01\x00\x00",
+        b"CBF\x00\x03\x00",
+        b"CBF\x00\x00\x01\x04\x00\x00",
+        b"CBF\x00\x00\x02\x02\x00\x00\x00",
+        b"CBF\x00\x00\x04\x02\x10A\x00",
+        b"CBF\x00\x00",
+    ];
+    for data in broken {
+        assert!(C8b::parse(data).is_err(), "{:?}", data);
+    }
+    assert!(!c8b::is_c8b(&[0x00, 0xE0]));
+}
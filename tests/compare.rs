@@ -0,0 +1,43 @@
+use chip8emu::compare::Comparison;
+use chip8emu::{Chip8Emulator, Quirks};
+
+fn run(rom: &[u8], quirks: Quirks, frames: u64) -> Comparison {
+    let mut emu = Chip8Emulator::new();
+    emu.set_quirks(Quirks::for_variant(chip8emu::chip8::Variant::SuperChip));
+    emu.initialize();
+    emu.load_rom_bytes(rom).unwrap();
+    let mut comparison = Comparison::new(&emu, quirks);
+    for _ in 0..frames {
+        for _ in 0..4 {
+            emu.emulate_cycle().unwrap();
+            comparison.step(&emu);
+        }
+        emu.tick_timers();
+        comparison.end_frame(&emu);
+    }
+    comparison
+}
+
+#[test]
+fn copies_draw_the_same_random_numbers() {
+    // V0 := random, V1 := random, then loop.
+    let rom = [0xC0, 0xFF, 0xC1, 0xFF, 0x12, 0x04];
+    let quirks = Quirks::for_variant(chip8emu::chip8::Variant::SuperChip);
+    assert_eq!(run(&rom, quirks, 10).divergence(), None);
+}
+
+#[test]
+fn finds_the_frame_where_quirks_differ() {
+    // Wait for a delay of 4, which runs out in the sixth frame at 4 instructions a frame, then
+    // VF := 5 and V0 |= V1, which resets VF with the vf-reset quirk.
+    let rom = [
+        0x60, 0x04, 0xF0, 0x15, 0xF0, 0x07, 0x30, 0x00, 0x12, 0x04, 0x6F, 0x05, 0x80, 0x11, 0x12,
+        0x0E,
+    ];
+    let mut quirks = Quirks::for_variant(chip8emu::chip8::Variant::SuperChip);
+    quirks.vf_reset = true;
+    let comparison = run(&rom, quirks, 10);
+    let divergence = comparison.divergence().unwrap();
+    assert_eq!(divergence.frame, 5);
+    assert_eq!(divergence.differences, ["VF 05/00"]);
+}
@@ -0,0 +1,222 @@
+use chip8emu::clock::Clock;
+use chip8emu::headless::run_movie;
+use chip8emu::movie::{Movie, HASH_INTERVAL};
+use chip8emu::timeline::Timeline;
+use chip8emu::{Chip8Emulator, Chip8Error};
+
+// V0 := random, then V2 += 1 for every loop with key 5 held.
+const ROM: [u8; 10] = [0xC0, 0xFF, 0x61, 0x05, 0xE1, 0xA1, 0x72, 0x01, 0x12, 0x00];
+
+fn movie(seed: u64, held: &[bool]) -> Movie {
+    let mut movie = Movie::new(seed, &ROM);
+    for held in held {
+        let mut keys = [false; 16];
+        keys[5] = *held;
+        movie.record(&keys);
+    }
+    movie
+}
+
+fn play(movie: &Movie) -> (u64, [u8; 16]) {
+    let mut emu = Chip8Emulator::new();
+    emu.initialize();
+    emu.load_rom_bytes(&ROM).unwrap();
+    let report = run_movie(&mut emu, movie, Clock::from_ipf(10)).unwrap();
+    (report.cycles, report.v)
+}
+
+#[test]
+fn movies_survive_saving() {
+    let movie = movie(42, &[false, true, true, false]);
+    let loaded = Movie::from_bytes(&movie.to_bytes()).unwrap();
+    assert_eq!(loaded, movie);
+    assert!(loaded.frame(1).unwrap()[5]);
+    assert_eq!(loaded.frame(4), None);
+    assert!(loaded.matches_rom(&ROM));
+    assert!(Movie::from_bytes(b"C8M0").is_err());
+}
+
+#[test]
+fn playback_is_deterministic() {
+    let held = movie(42, &[false, true, true, false, true]);
+    let (cycles, v) = play(&held);
+    assert_eq!(cycles, 50);
+    assert_eq!(play(&held), (cycles, v));
+    assert!(v[2] > 0);
+
+    let idle = movie(42, &[false; 5]);
+    assert_eq!(play(&idle).1[2], 0);
+    assert_ne!(play(&movie(43, &[false; 5])).1[0], play(&idle).1[0]);
+}
+
+// Record a movie as a frontend would: hold the frame's keys, run it, then end it.
+fn record(emu: &mut Chip8Emulator, seed: u64, frames: usize) -> Movie {
+    emu.set_rng_seed(seed);
+    let mut movie = Movie::new(seed, &ROM);
+    let mut clock = Clock::from_ipf(10);
+    for n in 0..frames {
+        let mut keys = [false; 16];
+        keys[5] = n % 3 == 0;
+        movie.record(&keys);
+        emu.set_keys(&keys);
+        emu.run_for(clock.instructions_this_frame()).unwrap();
+        emu.tick_timers();
+        movie.end_frame(emu);
+    }
+    movie
+}
+
+#[test]
+fn playback_stops_at_a_desync() {
+    let mut emu = Chip8Emulator::new();
+    emu.initialize();
+    emu.load_rom_bytes(&ROM).unwrap();
+    let movie = Movie::from_bytes(&record(&mut emu, 7, 2 * HASH_INTERVAL + 5).to_bytes()).unwrap();
+    assert_eq!(play(&movie).1, *emu.registers());
+
+    // A faster clock runs the loop more often than when the movie was recorded.
+    let mut emu = Chip8Emulator::new();
+    emu.initialize();
+    emu.load_rom_bytes(&ROM).unwrap();
+    let error = run_movie(&mut emu, &movie, Clock::from_ipf(11)).unwrap_err();
+    assert!(matches!(error, Chip8Error::Desync { frame } if frame == HASH_INTERVAL - 1));
+    assert_eq!(
+        error.to_string(),
+        format!("desync at frame {}", HASH_INTERVAL - 1)
+    );
+}
+
+#[test]
+fn movies_without_state_hashes_still_load() {
+    let movie = movie(42, &[false, true, true]);
+    let mut old = b"C8M1".to_vec();
+    old.extend_from_slice(&movie.to_bytes()[4..4 + 8 + 20]);
+    old.extend_from_slice(&[0, 0, 0x20, 0, 0x20, 0]);
+    assert_eq!(Movie::from_bytes(&old).unwrap(), movie);
+}
+
+fn loaded() -> Chip8Emulator {
+    let mut emu = Chip8Emulator::new();
+    emu.initialize();
+    emu.load_rom_bytes(&ROM).unwrap();
+    emu
+}
+
+// The machine after playing the first `frames` frames of `movie` from the start.
+fn played(movie: &Movie, frames: usize) -> Chip8Emulator {
+    let mut movie = movie.clone();
+    movie.truncate(frames);
+    let mut emu = loaded();
+    run_movie(&mut emu, &movie, Clock::from_ipf(10)).unwrap();
+    emu
+}
+
+fn timeline(movie: &Movie) -> Timeline {
+    let mut start = loaded();
+    start.set_rng_seed(movie.seed);
+    Timeline::new(movie.clone(), &start, Clock::from_ipf(10))
+}
+
+#[test]
+fn seeking_matches_playing_from_the_start() {
+    let movie = record(&mut loaded(), 7, 3 * HASH_INTERVAL + 10);
+    let mut timeline = timeline(&movie);
+    for frame in [150, 20, 150, 0, movie.len()] {
+        let (emu, _) = timeline.seek(frame).unwrap();
+        assert_eq!(
+            emu.state_hash(),
+            played(&movie, frame).state_hash(),
+            "frame {}",
+            frame
+        );
+    }
+}
+
+#[test]
+fn edits_are_played_from_the_nearest_snapshot() {
+    let mut movie = record(&mut loaded(), 7, 3 * HASH_INTERVAL + 10);
+    let mut timeline = timeline(&movie);
+    timeline.seek(130).unwrap();
+
+    let mut keys = movie.frame(70).unwrap();
+    keys[5] = !keys[5];
+    timeline.set_keys(70, &keys);
+    movie.set_frame(70, &keys);
+    timeline.insert_frame(100);
+    movie.insert_frame(100, &[false; 16]);
+    timeline.remove_frame(3);
+    movie.remove_frame(3);
+    assert_eq!(timeline.movie(), &movie);
+    let (emu, _) = timeline.seek(130).unwrap();
+    assert_eq!(emu.state_hash(), played(&movie, 130).state_hash());
+
+    // Playing the edited frames put back the state hashes they dropped.
+    timeline.seek(movie.len()).unwrap();
+    timeline.truncate(2 * HASH_INTERVAL + 1);
+    let saved = Movie::from_bytes(&timeline.movie().to_bytes()).unwrap();
+    assert_eq!(saved.len(), 2 * HASH_INTERVAL + 1);
+    let mut emu = loaded();
+    run_movie(&mut emu, &saved, Clock::from_ipf(10)).unwrap();
+    let error = run_movie(&mut loaded(), &saved, Clock::from_ipf(11)).unwrap_err();
+    assert!(matches!(error, Chip8Error::Desync { .. }));
+}
+
+#[test]
+fn branches_survive_saving() {
+    let mut movie = movie(42, &[false, true, true, false]);
+    movie.save_branch("start", 1);
+    movie.save_branch("ünïcode", 3);
+    movie.save_branch("start", 2);
+    let loaded = Movie::from_bytes(&movie.to_bytes()).unwrap();
+    assert_eq!(loaded, movie);
+    let names: Vec<_> = loaded
+        .branches()
+        .iter()
+        .map(|b| (&*b.name, b.len()))
+        .collect();
+    assert_eq!(names, [("start", 2), ("ünïcode", 3)]);
+
+    let long = "é".repeat(200);
+    assert_eq!(movie.save_branch(&long, 0).name.len(), 254);
+
+    // Version 2, before branches.
+    let mut old = b"C8M2".to_vec();
+    old.extend_from_slice(&movie.to_bytes()[4..4 + 8 + 20]);
+    old.extend_from_slice(&[4, 0, 0, 0, 0, 0, 0x20, 0, 0x20, 0, 0, 0]);
+    let old = Movie::from_bytes(&old).unwrap();
+    assert_eq!(old.len(), 4);
+    assert!(old.branches().is_empty());
+
+    let mut truncated = movie.to_bytes();
+    truncated.pop();
+    assert!(Movie::from_bytes(&truncated).is_err());
+}
+
+#[test]
+fn switching_branches_restores_their_state() {
+    let original = record(&mut loaded(), 7, 3 * HASH_INTERVAL);
+    let mut timeline = timeline(&original);
+    timeline.save_branch("a", 90).unwrap();
+    let mut keys = original.frame(50).unwrap();
+    keys[5] = !keys[5];
+    timeline.set_keys(50, &keys);
+    timeline.save_branch("b", 120).unwrap();
+    let edited = timeline.movie().clone();
+
+    assert_eq!(timeline.switch_branch("a"), Some(90));
+    assert_eq!(timeline.movie().len(), 90);
+    let (emu, _) = timeline.seek(90).unwrap();
+    assert_eq!(emu.state_hash(), played(&original, 90).state_hash());
+    assert_eq!(timeline.switch_branch("b"), Some(120));
+    let (emu, _) = timeline.seek(120).unwrap();
+    assert_eq!(emu.state_hash(), played(&edited, 120).state_hash());
+    assert_eq!(timeline.switch_branch("c"), None);
+
+    // Branches read from a file are played to.
+    let mut timeline = self::timeline(&Movie::from_bytes(&edited.to_bytes()).unwrap());
+    assert_eq!(timeline.switch_branch("a"), Some(90));
+    let (emu, _) = timeline.seek(90).unwrap();
+    assert_eq!(emu.state_hash(), played(&original, 90).state_hash());
+    timeline.remove_branch("a");
+    assert_eq!(timeline.movie().branches().len(), 1);
+}
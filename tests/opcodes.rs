@@ -0,0 +1,703 @@
+use chip8emu::chip8::{StepOutcome, Variant};
+use chip8emu::clock::{Clock, CycleTable, VIP_CYCLES_PER_FRAME};
+use chip8emu::{Chip8Emulator, Chip8Error, Chip8Observer, Quirks};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+/// Loads a program, runs it and checks the machine afterwards, one call at a time.
+struct EmuTest {
+    emu: Chip8Emulator,
+}
+
+impl EmuTest {
+    fn new() -> Self {
+        let mut emu = Chip8Emulator::new();
+        emu.initialize();
+        Self { emu }
+    }
+
+    /// Emulate another dialect. Call before `with_rom()`, since it resets memory.
+    fn with_variant(mut self, variant: Variant) -> Self {
+        self.emu.set_variant(variant);
+        self.emu.initialize();
+        self
+    }
+
+    fn with_quirks(mut self, quirks: Quirks) -> Self {
+        self.emu.set_quirks(quirks);
+        self
+    }
+
+    fn with_rom(mut self, rom: &[u8]) -> Self {
+        self.emu.load_rom_bytes(rom).unwrap();
+        self
+    }
+
+    fn seed(mut self, seed: u64) -> Self {
+        self.emu.set_rng_seed(seed);
+        self
+    }
+
+    fn press(mut self, key: usize) -> Self {
+        let mut keys = *self.emu.keys();
+        keys[key] = true;
+        self.emu.set_keys(&keys);
+        self
+    }
+
+    fn run_cycles(mut self, cycles: usize) -> Self {
+        for _ in 0..cycles {
+            self.emu.emulate_cycle().unwrap();
+        }
+        self
+    }
+
+    fn tick_timers(mut self, ticks: usize) -> Self {
+        for _ in 0..ticks {
+            self.emu.tick_timers();
+        }
+        self
+    }
+
+    fn reset(mut self) -> Self {
+        self.emu.reset().unwrap();
+        self
+    }
+
+    fn assert_reg(self, reg: usize, value: u8) -> Self {
+        assert_eq!(
+            self.emu.registers()[reg],
+            value,
+            "V{:X} at pc {:#X}",
+            reg,
+            self.emu.pc()
+        );
+        self
+    }
+
+    fn assert_pc(self, pc: usize) -> Self {
+        assert_eq!(self.emu.pc(), pc, "pc");
+        self
+    }
+
+    fn assert_i(self, i: usize) -> Self {
+        assert_eq!(self.emu.i(), i, "I");
+        self
+    }
+
+    fn assert_memory(self, address: usize, bytes: &[u8]) -> Self {
+        assert_eq!(
+            &self.emu.memory()[address..address + bytes.len()],
+            bytes,
+            "memory at {:#X}",
+            address
+        );
+        self
+    }
+
+    fn assert_pixel(self, x: usize, y: usize, lit: bool) -> Self {
+        let planes = self.emu.screen()[y * self.emu.screen_width() + x];
+        assert_eq!(planes != 0, lit, "pixel ({}, {})", x, y);
+        self
+    }
+
+    fn assert_delay_timer(self, value: u8) -> Self {
+        assert_eq!(self.emu.delay_timer(), value, "delay timer");
+        self
+    }
+}
+
+#[test]
+fn add_sets_carry() {
+    // V0 := 0xFF, V1 := 0x01, V0 += V1
+    EmuTest::new()
+        .with_rom(&[0x60, 0xFF, 0x61, 0x01, 0x80, 0x14])
+        .run_cycles(3)
+        .assert_reg(0, 0x00)
+        .assert_reg(0xF, 1);
+    EmuTest::new()
+        .with_rom(&[0x60, 0x10, 0x61, 0x01, 0x80, 0x14])
+        .run_cycles(3)
+        .assert_reg(0, 0x11)
+        .assert_reg(0xF, 0);
+}
+
+#[test]
+fn flag_wins_when_vf_is_the_destination() {
+    // VF := 0xFF, V1 := 0x02, VF += V1
+    EmuTest::new()
+        .with_rom(&[0x6F, 0xFF, 0x61, 0x02, 0x8F, 0x14])
+        .run_cycles(3)
+        .assert_reg(0xF, 1);
+}
+
+#[test]
+fn sub_clears_flag_on_borrow() {
+    // V0 := 5, V1 := 3, V0 -= V1
+    EmuTest::new()
+        .with_rom(&[0x60, 0x05, 0x61, 0x03, 0x80, 0x15])
+        .run_cycles(3)
+        .assert_reg(0, 2)
+        .assert_reg(0xF, 1);
+    EmuTest::new()
+        .with_rom(&[0x60, 0x03, 0x61, 0x05, 0x80, 0x15])
+        .run_cycles(3)
+        .assert_reg(0, 0xFE)
+        .assert_reg(0xF, 0);
+}
+
+#[test]
+fn subn_subtracts_vx_from_vy() {
+    // V0 := 3, V1 := 5, V0 =- V1
+    EmuTest::new()
+        .with_rom(&[0x60, 0x03, 0x61, 0x05, 0x80, 0x17])
+        .run_cycles(3)
+        .assert_reg(0, 2)
+        .assert_reg(0xF, 1);
+    EmuTest::new()
+        .with_rom(&[0x60, 0x05, 0x61, 0x03, 0x80, 0x17])
+        .run_cycles(3)
+        .assert_reg(0, 0xFE)
+        .assert_reg(0xF, 0);
+}
+
+#[test]
+fn shift_right_moves_out_low_bit() {
+    // V0 := 0, V1 := 0x05, V0 := V1 >> 1
+    EmuTest::new()
+        .with_rom(&[0x60, 0x00, 0x61, 0x05, 0x80, 0x16])
+        .run_cycles(3)
+        .assert_reg(0, 0x02)
+        .assert_reg(0xF, 1);
+}
+
+#[test]
+fn shift_left_moves_out_high_bit() {
+    // V0 := 0, V1 := 0x81, V0 := V1 << 1
+    EmuTest::new()
+        .with_rom(&[0x60, 0x00, 0x61, 0x81, 0x80, 0x1E])
+        .run_cycles(3)
+        .assert_reg(0, 0x02)
+        .assert_reg(0xF, 1);
+    EmuTest::new()
+        .with_rom(&[0x60, 0x00, 0x61, 0x41, 0x80, 0x1E])
+        .run_cycles(3)
+        .assert_reg(0, 0x82)
+        .assert_reg(0xF, 0);
+}
+
+#[test]
+fn super_chip_shifts_vx_in_place() {
+    // V0 := 0x0C, V1 := 0xFF, V0 >>= 1
+    EmuTest::new()
+        .with_variant(Variant::SuperChip)
+        .with_quirks(Quirks::for_variant(Variant::SuperChip))
+        .with_rom(&[0x60, 0x0C, 0x61, 0xFF, 0x80, 0x16])
+        .run_cycles(3)
+        .assert_reg(0, 0x06)
+        .assert_reg(0xF, 0);
+}
+
+#[test]
+fn logic_resets_vf_on_cosmac_only() {
+    // VF := 1, V0 |= V1
+    let rom = [0x6F, 0x01, 0x80, 0x11];
+    EmuTest::new()
+        .with_rom(&rom)
+        .run_cycles(2)
+        .assert_reg(0xF, 0);
+    EmuTest::new()
+        .with_quirks(Quirks::for_variant(Variant::SuperChip))
+        .with_rom(&rom)
+        .run_cycles(2)
+        .assert_reg(0xF, 1);
+}
+
+#[test]
+fn draw_reports_collision() {
+    // I := font 0, draw at (0, 0), draw again
+    let rom = [0x60, 0x00, 0xF0, 0x29, 0xD0, 0x05, 0xD0, 0x05];
+    EmuTest::new()
+        .with_rom(&rom)
+        .run_cycles(3)
+        .assert_reg(0xF, 0)
+        .assert_pixel(0, 0, true)
+        .assert_pixel(4, 0, false)
+        .tick_timers(1)
+        .run_cycles(1)
+        .assert_reg(0xF, 1)
+        .assert_pixel(0, 0, false);
+}
+
+#[test]
+fn draws_wait_for_the_display() {
+    // I := font 0, draw, V1 := 1
+    let rom = [0xF0, 0x29, 0xD0, 0x05, 0x61, 0x01];
+    EmuTest::new()
+        .with_rom(&rom)
+        .run_cycles(3)
+        .assert_reg(1, 0)
+        .tick_timers(1)
+        .run_cycles(1)
+        .assert_reg(1, 1);
+    let mut quirks = Quirks::for_variant(Variant::Chip8);
+    quirks.display_wait = false;
+    EmuTest::new()
+        .with_quirks(quirks)
+        .with_rom(&rom)
+        .run_cycles(3)
+        .assert_reg(1, 1);
+}
+
+#[test]
+fn draw_clips_at_the_edge() {
+    // V0 := 62, I := font 0, draw at (62, 0)
+    EmuTest::new()
+        .with_rom(&[0x60, 0x3E, 0x61, 0x00, 0xF1, 0x29, 0xD0, 0x15])
+        .run_cycles(4)
+        .assert_pixel(62, 0, true)
+        .assert_pixel(0, 0, false);
+}
+
+#[test]
+fn bcd_stores_digits_at_i() {
+    // V0 := 234, I := 0x300, bcd V0
+    EmuTest::new()
+        .with_rom(&[0x60, 0xEA, 0xA3, 0x00, 0xF0, 0x33])
+        .run_cycles(3)
+        .assert_i(0x300)
+        .assert_memory(0x300, &[2, 3, 4]);
+}
+
+#[test]
+fn register_dump_and_load() {
+    // V0 := 1, V1 := 2, I := 0x300, save V1, V0 := 0, V1 := 0, I := 0x300, load V1
+    EmuTest::new()
+        .with_rom(&[
+            0x60, 0x01, 0x61, 0x02, 0xA3, 0x00, 0xF1, 0x55, 0x60, 0x00, 0x61, 0x00, 0xA3, 0x00,
+            0xF1, 0x65,
+        ])
+        .run_cycles(4)
+        .assert_memory(0x300, &[1, 2])
+        .assert_i(0x302)
+        .run_cycles(4)
+        .assert_reg(0, 1)
+        .assert_reg(1, 2);
+}
+
+#[test]
+fn skips_on_constant() {
+    // V0 := 5, skip if V0 == 5
+    EmuTest::new()
+        .with_rom(&[0x60, 0x05, 0x30, 0x05])
+        .run_cycles(2)
+        .assert_pc(0x206);
+    // skip if V0 != 5
+    EmuTest::new()
+        .with_rom(&[0x60, 0x05, 0x40, 0x05])
+        .run_cycles(2)
+        .assert_pc(0x204);
+}
+
+#[test]
+fn skips_on_register() {
+    // V0 := 5, V1 := 5, skip if V0 == V1, then skip if V0 != V1
+    EmuTest::new()
+        .with_rom(&[0x60, 0x05, 0x61, 0x05, 0x50, 0x10])
+        .run_cycles(3)
+        .assert_pc(0x208);
+    EmuTest::new()
+        .with_rom(&[0x60, 0x05, 0x61, 0x05, 0x90, 0x10])
+        .run_cycles(3)
+        .assert_pc(0x206);
+}
+
+#[test]
+fn skips_on_key() {
+    // V0 := 7, skip if key V0 is held
+    let rom = [0x60, 0x07, 0xE0, 0x9E];
+    EmuTest::new()
+        .with_rom(&rom)
+        .press(7)
+        .run_cycles(2)
+        .assert_pc(0x206);
+    EmuTest::new().with_rom(&rom).run_cycles(2).assert_pc(0x204);
+    // skip if key V0 isn't held
+    EmuTest::new()
+        .with_rom(&[0x60, 0x07, 0xE0, 0xA1])
+        .run_cycles(2)
+        .assert_pc(0x206);
+}
+
+#[test]
+fn waits_for_key() {
+    // V3 := key
+    EmuTest::new()
+        .with_rom(&[0xF3, 0x0A])
+        .run_cycles(5)
+        .assert_pc(0x200)
+        .press(0xB)
+        .assert_reg(3, 0xB)
+        .assert_pc(0x202);
+}
+
+#[test]
+fn delay_timer_counts_down() {
+    // V0 := 3, delay := V0, V1 := delay
+    EmuTest::new()
+        .with_rom(&[0x60, 0x03, 0xF0, 0x15, 0xF1, 0x07])
+        .run_cycles(2)
+        .assert_delay_timer(3)
+        .tick_timers(2)
+        .run_cycles(1)
+        .assert_reg(1, 1)
+        .tick_timers(5)
+        .assert_delay_timer(0);
+}
+
+#[test]
+fn reset_restarts_the_rom() {
+    // V0 := 5, I := font 0, draw
+    EmuTest::new()
+        .with_rom(&[0x60, 0x05, 0xF0, 0x29, 0xD0, 0x05])
+        .run_cycles(3)
+        .reset()
+        .assert_pc(0x200)
+        .assert_reg(0, 0)
+        .assert_pixel(0, 0, false)
+        .assert_memory(0x200, &[0x60, 0x05])
+        .run_cycles(1)
+        .assert_reg(0, 5);
+}
+
+#[test]
+fn written_memory_is_executed() {
+    // V0 := 1, patched to V0 := 7 before it runs
+    let mut emu = Chip8Emulator::new();
+    emu.initialize();
+    emu.load_rom_bytes(&[0x60, 0x01]).unwrap();
+    emu.write_memory(0x201, &[0x07]).unwrap();
+    assert_eq!(emu.read_memory(emu.rom_range()), &[0x60, 0x07]);
+    emu.emulate_cycle().unwrap();
+    assert_eq!(emu.registers()[0], 7);
+}
+
+#[test]
+fn only_display_instructions_change_the_screen() {
+    // V0 := 1, draw, clear
+    let mut emu = Chip8Emulator::new();
+    emu.initialize();
+    emu.load_rom_bytes(&[0x60, 0x01, 0xD0, 0x05, 0x00, 0xE0])
+        .unwrap();
+    emu.take_screen_changed();
+    emu.emulate_cycle().unwrap();
+    assert!(!emu.take_screen_changed());
+    emu.emulate_cycle().unwrap();
+    assert!(emu.take_screen_changed());
+    assert!(!emu.take_screen_changed());
+    emu.tick_timers();
+    emu.emulate_cycle().unwrap();
+    assert!(emu.take_screen_changed());
+}
+
+#[test]
+fn runs_stop_where_the_machine_has_to_wait() {
+    // V0 := 1, V1 := 2, wait for a key into V2, I := font 0, draw, exit
+    let rom = [
+        0x60, 0x01, 0x61, 0x02, 0xF2, 0x0A, 0xF0, 0x29, 0xD0, 0x05, 0x00, 0xFD,
+    ];
+    let mut emu = Chip8Emulator::new();
+    emu.set_variant(Variant::SuperChip);
+    let mut quirks = emu.quirks();
+    quirks.display_wait = true;
+    emu.set_quirks(quirks);
+    emu.initialize();
+    emu.load_rom_bytes(&rom).unwrap();
+
+    assert_eq!(emu.run_for(1).unwrap(), StepOutcome::Completed);
+    assert_eq!(
+        emu.run_until(|emu| emu.pc() == 0x204).unwrap(),
+        StepOutcome::HitBreakpoint
+    );
+    assert_eq!(emu.run_for(100).unwrap(), StepOutcome::WaitingForKey);
+    assert_eq!(emu.executed(), 3);
+    let mut keys = [false; 16];
+    keys[7] = true;
+    emu.set_keys(&keys);
+    assert_eq!(emu.run_for(100).unwrap(), StepOutcome::DisplayUpdated);
+    emu.tick_timers();
+    assert_eq!(emu.run_for(100).unwrap(), StepOutcome::Halted);
+    assert_eq!(emu.registers()[2], 7);
+}
+
+#[test]
+fn vip_timing_runs_a_frame_of_machine_cycles() {
+    // V0 := 1, jump back to it
+    let mut emu = Chip8Emulator::new();
+    emu.initialize();
+    emu.load_rom_bytes(&[0x60, 0x01, 0x12, 0x00]).unwrap();
+    let mut clock = Clock::vip();
+
+    assert_eq!(clock.run_frame(&mut emu).unwrap(), StepOutcome::Completed);
+    // 26 loops, then V0 := 1 leaves 10 cycles, and the jump runs 42 past the end.
+    assert_eq!(emu.executed(), 54);
+    assert_eq!(emu.cycles(), VIP_CYCLES_PER_FRAME + 42);
+    // Running over the end of a frame takes the time from the next.
+    clock.run_frame(&mut emu).unwrap();
+    assert!(emu.cycles() - 2 * VIP_CYCLES_PER_FRAME < 52);
+}
+
+#[test]
+fn cycle_tables_replace_the_vip_costs() {
+    // V0 := 1 costs 10 cycles instead of 46, and the jump keeps the VIP's 52.
+    let costs = BTreeMap::from([("6XNN".to_string(), 10)]);
+    let table = CycleTable::new(&costs, Some(124)).unwrap();
+    let mut clock = Clock::from_cycle_table(&table);
+    let mut emu = Chip8Emulator::new();
+    emu.set_cycle_table(Some(Arc::new(table)));
+    emu.initialize();
+    emu.load_rom_bytes(&[0x60, 0x01, 0x12, 0x00]).unwrap();
+
+    clock.run_frame(&mut emu).unwrap();
+    assert_eq!(emu.executed(), 4);
+    assert_eq!(emu.cycles(), 124);
+
+    let unknown = BTreeMap::from([("6XYZ".to_string(), 10)]);
+    assert!(CycleTable::new(&unknown, None)
+        .unwrap_err()
+        .contains("6XYZ"));
+    assert!(CycleTable::new(&costs, Some(0)).is_err());
+}
+
+/// Writes down each hook called, in order.
+struct Recorder(Arc<Mutex<Vec<String>>>);
+
+impl Chip8Observer for Recorder {
+    fn on_draw(&mut self, _emu: &Chip8Emulator) {
+        self.0.lock().unwrap().push("draw".to_string());
+    }
+
+    fn on_sound_start(&mut self, emu: &Chip8Emulator) {
+        let event = format!("sound start {}", emu.sound_timer());
+        self.0.lock().unwrap().push(event);
+    }
+
+    fn on_sound_stop(&mut self, _emu: &Chip8Emulator) {
+        self.0.lock().unwrap().push("sound stop".to_string());
+    }
+
+    fn on_key_wait(&mut self, _emu: &Chip8Emulator) {
+        self.0.lock().unwrap().push("key wait".to_string());
+    }
+
+    fn on_memory_write(&mut self, _emu: &Chip8Emulator, address: usize, value: u8) {
+        let event = format!("write {:#05X} {}", address, value);
+        self.0.lock().unwrap().push(event);
+    }
+
+    fn on_unknown_opcode(&mut self, _emu: &Chip8Emulator, opcode: u16) {
+        let event = format!("unknown {:04X}", opcode);
+        self.0.lock().unwrap().push(event);
+    }
+}
+
+#[test]
+fn observers_hear_what_the_rom_does() {
+    // V0 := 1, sound timer := V0, I := 0x300, BCD of V0, draw, wait for a key into V1, then an
+    // invalid instruction
+    let rom = [
+        0x60, 0x01, 0xF0, 0x18, 0xA3, 0x00, 0xF0, 0x33, 0xD0, 0x01, 0xF1, 0x0A, 0xFF, 0xFF,
+    ];
+    let mut emu = Chip8Emulator::new();
+    emu.initialize();
+    emu.load_rom_bytes(&rom).unwrap();
+    let events = Arc::new(Mutex::new(Vec::new()));
+    emu.add_observer(Box::new(Recorder(Arc::clone(&events))));
+
+    assert_eq!(emu.run_for(10).unwrap(), StepOutcome::DisplayUpdated);
+    emu.tick_timers();
+    assert_eq!(emu.run_for(10).unwrap(), StepOutcome::WaitingForKey);
+    let mut keys = [false; 16];
+    keys[3] = true;
+    emu.set_keys(&keys);
+    assert!(emu.emulate_cycle().is_err());
+    // Clones run without the original's observers.
+    emu.clone().initialize();
+
+    assert_eq!(
+        *events.lock().unwrap(),
+        [
+            "sound start 1",
+            "write 0x300 0",
+            "write 0x301 0",
+            "write 0x302 1",
+            "draw",
+            "sound stop",
+            "key wait",
+            "unknown FFFF",
+        ]
+    );
+}
+
+#[test]
+fn memory_access_past_the_end() {
+    let mut emu = Chip8Emulator::new();
+    emu.initialize();
+    let size = emu.memory().len();
+    assert!(emu.write_memory(size - 1, &[1, 2]).is_err());
+    assert_eq!(
+        emu.read_memory(size - 1..size + 4),
+        &[emu.memory()[size - 1]]
+    );
+    assert!(emu.read_memory(size + 1..size + 4).is_empty());
+}
+
+#[test]
+fn seeded_random_numbers_repeat() {
+    // V0 := random 0xFF, V1 := random 0xFF
+    let rom = [0xC0, 0xFF, 0xC1, 0xFF];
+    let run = |seed| {
+        let mut emu = Chip8Emulator::new();
+        emu.set_rng_seed(seed);
+        emu.initialize();
+        emu.load_rom_bytes(&rom).unwrap();
+        emu.emulate_cycle().unwrap();
+        emu.emulate_cycle().unwrap();
+        emu.registers()[..2].to_vec()
+    };
+    assert_eq!(run(1), run(1));
+    assert_ne!(run(1), run(2));
+
+    let first = run(7);
+    EmuTest::new()
+        .with_rom(&rom)
+        .seed(7)
+        .run_cycles(2)
+        .reset()
+        .run_cycles(2)
+        .assert_reg(0, first[0])
+        .assert_reg(1, first[1]);
+}
+
+#[test]
+fn roms_load_at_the_load_address() {
+    // V0 := 3, jump to 0x600
+    let mut emu = Chip8Emulator::new();
+    emu.set_load_address(0x600);
+    emu.initialize();
+    emu.load_rom_bytes(&[0x60, 0x03, 0x16, 0x00]).unwrap();
+    assert_eq!(emu.pc(), 0x600);
+    assert_eq!(emu.rom_range(), 0x600..0x604);
+    assert_eq!(emu.read_memory(0x200..0x202), &[0, 0]);
+    emu.emulate_cycle().unwrap();
+    emu.emulate_cycle().unwrap();
+    assert_eq!(emu.registers()[0], 3);
+    assert_eq!(emu.pc(), 0x600);
+    emu.reset().unwrap();
+    assert_eq!(emu.pc(), 0x600);
+}
+
+#[test]
+fn reset_fails_when_the_rom_no_longer_fits() {
+    let mut emu = Chip8Emulator::new();
+    emu.set_variant(Variant::XoChip);
+    emu.initialize();
+    emu.load_rom_bytes(&[0x00; 0x1000]).unwrap();
+
+    emu.set_variant(Variant::Chip8);
+    assert!(matches!(emu.reset(), Err(Chip8Error::RomTooLarge { .. })));
+
+    // The ROM is kept, so it can restart once there is room again.
+    emu.set_variant(Variant::XoChip);
+    emu.reset().unwrap();
+    assert_eq!(emu.pc(), 0x200);
+}
+
+#[test]
+fn pc_wraps_around_the_end_of_xo_chip_memory() {
+    // V0 := 0 all the way to the last instruction, which steps past 0xFFFF.
+    for last in [[0x30, 0x00], [0xF0, 0x00]] {
+        let mut rom = [0x60, 0x00].repeat((0xFFFE - 0x200) / 2);
+        rom.extend_from_slice(&last);
+        let mut emu = Chip8Emulator::new();
+        emu.set_variant(Variant::XoChip);
+        emu.initialize();
+        emu.load_rom_bytes(&rom).unwrap();
+        // Call 0x006, which exits.
+        emu.write_memory(0x002, &[0x20, 0x06, 0x00, 0x00, 0x00, 0xFD])
+            .unwrap();
+
+        assert_eq!(emu.run_for(0x10000).unwrap(), StepOutcome::Halted);
+        assert_eq!(emu.pc(), 0x006);
+        assert_eq!(emu.stack(), [0x002]);
+    }
+}
+
+#[test]
+fn damaged_save_states_are_rejected() {
+    let mut emu = Chip8Emulator::new();
+    emu.initialize();
+    // V0 := 5, wait for a key into V3
+    emu.load_rom_bytes(&[0x60, 0x05, 0xF3, 0x0A]).unwrap();
+    emu.run_for(2).unwrap();
+    let saved: serde_json::Value = serde_json::from_slice(&emu.save_state()).unwrap();
+    emu.load_state(&serde_json::to_vec(&saved).unwrap())
+        .unwrap();
+
+    for (field, value) in [("key_reg", 16), ("pc", 0x1000), ("planes", 4), ("sp", 17)] {
+        let mut state = saved.clone();
+        state[field] = value.into();
+        let error = emu
+            .load_state(&serde_json::to_vec(&state).unwrap())
+            .unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData, "{}", field);
+    }
+    assert_eq!(emu.pc(), 0x202);
+    assert!(emu.is_waiting_for_key());
+}
+
+#[test]
+fn save_states_are_compressed() {
+    // Draw the font's 0 and wait for a key, with most of XO-CHIP's 64K memory unused.
+    let mut emu = Chip8Emulator::new();
+    emu.set_variant(Variant::XoChip);
+    emu.initialize();
+    emu.load_rom_bytes(&[0xA0, 0x00, 0xD0, 0x05, 0xF0, 0x0A])
+        .unwrap();
+    emu.run_for(3).unwrap();
+    let state = emu.save_state();
+    assert!(state.len() < 4096, "{} bytes", state.len());
+
+    let mut restored = Chip8Emulator::new();
+    restored.load_state(&state).unwrap();
+    assert_eq!(restored.state_hash(), emu.state_hash());
+    assert_eq!(restored.memory(), emu.memory());
+    assert_eq!(restored.screen(), emu.screen());
+
+    let mut damaged: serde_json::Value = serde_json::from_slice(&state).unwrap();
+    damaged["memory"] = "80".into();
+    assert!(restored
+        .load_state(&serde_json::to_vec(&damaged).unwrap())
+        .is_err());
+}
+
+#[test]
+fn version_2_save_states_still_load() {
+    let mut emu = Chip8Emulator::new();
+    emu.initialize();
+    emu.load_rom_bytes(&[0x60, 0x05, 0xF3, 0x0A]).unwrap();
+    emu.run_for(2).unwrap();
+
+    // Version 2 stored memory and the whole screen buffer as arrays of bytes.
+    let mut old: serde_json::Value = serde_json::from_slice(&emu.save_state()).unwrap();
+    old["version"] = 2.into();
+    old["memory"] = emu.memory().to_vec().into();
+    old["screen"] = vec![0u8; 128 * 64].into();
+
+    let mut restored = Chip8Emulator::new();
+    restored
+        .load_state(&serde_json::to_vec(&old).unwrap())
+        .unwrap();
+    assert_eq!(restored.state_hash(), emu.state_hash());
+    assert!(restored.is_waiting_for_key());
+}
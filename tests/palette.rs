@@ -0,0 +1,35 @@
+use chip8emu::palette::{Palette, THEMES};
+
+#[test]
+fn parses_themes_by_name() {
+    assert_eq!("amber".parse::<Palette>(), Ok(THEMES[2].1));
+}
+
+#[test]
+fn parses_two_colors_with_default_planes() {
+    let palette: Palette = "#FFCC00,#996600".parse().unwrap();
+    assert_eq!(palette.foreground, [0xff, 0xcc, 0x00, 0xff]);
+    assert_eq!(palette.background, [0x99, 0x66, 0x00, 0xff]);
+    assert_eq!(palette.plane_2, Palette::default().plane_2);
+}
+
+#[test]
+fn parses_four_colors_for_xo_chip() {
+    let palette: Palette = "#FFFFFF,#000000,#FF0000,#00FF00".parse().unwrap();
+    assert_eq!(palette.plane_2, [0xff, 0, 0, 0xff]);
+    assert_eq!(palette.both_planes, [0, 0xff, 0, 0xff]);
+}
+
+#[test]
+fn rejects_other_color_counts_and_unknown_names() {
+    assert!("#FFFFFF,#000000,#FF0000".parse::<Palette>().is_err());
+    assert!("sepia".parse::<Palette>().is_err());
+}
+
+#[test]
+fn displays_as_it_parses() {
+    for spec in ["green", "#123456,#654321,#ABCDEF,#FEDCBA"] {
+        let palette: Palette = spec.parse().unwrap();
+        assert_eq!(palette.to_string(), spec);
+    }
+}
@@ -0,0 +1,87 @@
+use proptest::prelude::*;
+
+use chip8emu::asm::assemble;
+use chip8emu::chip8::Variant;
+use chip8emu::clock::Clock;
+use chip8emu::quirks::QUIRK_NAMES;
+use chip8emu::{opcode, run_headless, Chip8Emulator, Opcode};
+
+fn instruction() -> impl Strategy<Value = Opcode> {
+    any::<u16>().prop_filter_map("not an instruction", |word| opcode::from_value(word).ok())
+}
+
+fn variant() -> impl Strategy<Value = Variant> {
+    prop_oneof![
+        Just(Variant::Chip8),
+        Just(Variant::SuperChip),
+        Just(Variant::XoChip)
+    ]
+}
+
+// Skips, XO-CHIP's `i := long` and calls are what go wrong when pc runs off the end of memory.
+fn instruction_word() -> impl Strategy<Value = u16> {
+    prop_oneof![
+        Just(0xF000),
+        0x2000..0x5000u16,
+        0x9000..0x9010u16,
+        any::<u16>()
+    ]
+}
+
+proptest! {
+    #[test]
+    fn instructions_encode_to_what_they_decode_from(op in instruction()) {
+        prop_assert_eq!(opcode::from_value(op.to_value()), Ok(op));
+    }
+
+    #[test]
+    fn mnemonics_assemble_to_the_instruction(op in instruction()) {
+        let rom = assemble(&op.to_string(), 0x200).unwrap();
+        prop_assert_eq!(rom, op.to_value().to_be_bytes());
+    }
+
+    // Whatever a ROM holds, running it either works or stops with an error, and never panics.
+    #[test]
+    fn any_rom_runs_without_panicking(
+        rom in prop::collection::vec(any::<u8>(), 1..256),
+        variant in variant(),
+        quirks in prop::array::uniform6(any::<bool>()),
+    ) {
+        let mut emu = Chip8Emulator::new();
+        emu.set_variant(variant);
+        let mut toggled = emu.quirks();
+        for (name, enabled) in QUIRK_NAMES.iter().zip(quirks) {
+            toggled.set(name, enabled).unwrap();
+        }
+        emu.set_quirks(toggled);
+        emu.set_rng_seed(0);
+        emu.initialize();
+        emu.load_rom_bytes(&rom).unwrap();
+        let _ = run_headless(&mut emu, 5_000, Clock::from_ipf(30));
+    }
+
+    // Code at the end of memory carries on from its start, wherever that leaves pc.
+    #[test]
+    fn code_at_the_end_of_memory_runs_without_panicking(
+        words in prop::collection::vec(instruction_word(), 1..32),
+        from_end in 1..32usize,
+        variant in variant(),
+    ) {
+        let mut emu = Chip8Emulator::new();
+        emu.set_variant(variant);
+        emu.set_rng_seed(0);
+        emu.initialize();
+        let len = emu.memory().len();
+        let start = len - 2 * from_end;
+        let code: Vec<u8> = words.iter().flat_map(|word| word.to_be_bytes()).collect();
+        for (offset, byte) in code.iter().enumerate() {
+            emu.write_memory((start + offset) % len, &[*byte]).unwrap();
+        }
+        emu.set_pc(start);
+        for _ in 0..1_000 {
+            if emu.emulate_cycle().is_err() {
+                break;
+            }
+        }
+    }
+}